@@ -86,6 +86,7 @@ fn main() {
                     EscapeType::Osc => "OSC",
                     EscapeType::BracketPaste => "BRACKET_PA",
                     EscapeType::Title => "TITLE",
+                    EscapeType::Dcs => "DCS",
                 };
 
                 let param_str = cmd.params.iter()
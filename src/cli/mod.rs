@@ -1,6 +1,12 @@
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::encoder::{ColorSpace, SilentAudio};
+use crate::theme::Padding;
+
+/// TrueType font size when neither --font-size nor the theme sets one
+pub const DEFAULT_FONT_SIZE: usize = 16;
+
 #[derive(Parser, Debug)]
 #[command(name = "ttyvid")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -22,6 +28,14 @@ pub struct Args {
     #[arg(short, long, global = true, default_value = "default")]
     pub theme: String,
 
+    /// Override one theme field by dotted path, e.g. --set background=0 --set padding.left=20 (repeatable)
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub theme_overrides: Vec<String>,
+
+    /// Fail instead of skipping theme layers whose images cannot be found
+    #[arg(long, global = true)]
+    pub strict_layers: bool,
+
     /// Font name (bitmap font)
     #[arg(short = 'f', long, global = true)]
     pub font: Option<String>,
@@ -35,22 +49,62 @@ pub struct Args {
     #[arg(long, global = true)]
     pub system_font: Option<String>,
 
-    /// Font size in pixels for TrueType fonts (height of the character cell)
-    #[arg(long, global = true, default_value = "16")]
-    pub font_size: usize,
+    /// Font size in pixels for TrueType fonts (height of the character cell) [default: theme's, else 16]
+    #[arg(long, global = true)]
+    pub font_size: Option<usize>,
+
+    /// Position glyphs by their own widths (variable-width .fd fonts)
+    #[arg(long, global = true)]
+    pub proportional: bool,
+
+    /// Raise the contrast of anti-aliased TrueType glyphs: faint edge coverage is dropped and
+    /// strong coverage made solid (a contrast stretch, not hinting; fontdue has no hinter)
+    #[arg(long, global = true)]
+    pub glyph_contrast: bool,
 
-    /// Frames per second (3-100)
+    /// Frames per second (1-100; other values are clamped with a warning)
     #[arg(long, global = true, default_value = "10")]
     pub fps: u32,
 
+    /// Largest number of frames to render; see --on-overflow for what happens past it
+    #[arg(long, global = true, value_name = "N")]
+    pub max_frames: Option<usize>,
+
+    /// What to do when a conversion needs more than --max-frames frames
+    #[arg(long, global = true, default_value = "error", value_parser = ["error", "reduce-fps"])]
+    pub on_overflow: String,
+
+    /// Hold unchanged frames instead of repeating them, emitting idle stretches at IDLE_FPS (GIF only)
+    #[arg(long, global = true, value_name = "IDLE_FPS", num_args = 0..=1, default_missing_value = "1",
+          value_parser = clap::value_parser!(u32).range(1..=100))]
+    pub adaptive_fps: Option<u32>,
+
     /// Video quality for WebM (0-100, higher is better)
     #[arg(short = 'q', long, global = true, default_value = "50")]
     pub quality: u8,
 
-    /// Speed multiplier
-    #[arg(long, global = true, default_value = "1.0")]
+    /// Add a silent audio track to WebM output, for platforms that reject video without audio
+    /// ("match" = as long as the video, or a length in seconds)
+    #[arg(long, global = true, value_name = "SECONDS|match", num_args = 0..=1,
+          default_missing_value = "match", value_parser = parse_silent_audio)]
+    pub silent_audio: Option<SilentAudio>,
+
+    /// YUV matrix for WebM output
+    #[arg(long, global = true, value_name = "bt601|bt709", default_value = "bt709", value_parser = parse_color_space)]
+    pub color_space: ColorSpace,
+
+    /// Reduce the output palette to at most N colors (2-256) for smaller files
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u16).range(2..=256))]
+    pub max_colors: Option<u16>,
+
+    /// Speed multiplier (greater than 0)
+    #[arg(long, global = true, default_value = "1.0", allow_negative_numbers = true, value_parser = parse_speed)]
     pub speed: f64,
 
+    /// YAML/JSON list of {start, end, multiplier} segments retiming parts of the recording
+    #[arg(long, global = true, value_name = "FILE")]
+    pub speed_curve: Option<PathBuf>,
+
     /// Terminal width in columns
     #[arg(short = 'c', long, global = true)]
     pub columns: Option<usize>,
@@ -59,11 +113,27 @@ pub struct Args {
     #[arg(short = 'r', long, global = true)]
     pub rows: Option<usize>,
 
+    /// Largest accepted terminal width; wider sizes are clamped
+    #[arg(long, global = true, default_value = "1000")]
+    pub max_columns: usize,
+
+    /// Largest accepted terminal height; taller sizes are clamped
+    #[arg(long, global = true, default_value = "1000")]
+    pub max_rows: usize,
+
+    /// Largest composited frame in megabytes (one byte per pixel); bigger configurations are refused up front
+    #[arg(long, global = true, value_name = "MB", default_value = "256")]
+    pub max_canvas_mb: usize,
+
     /// Number of loops (0 = infinite)
     #[arg(short, long, global = true, default_value = "0")]
     pub r#loop: u16,
 
-    /// Delay before loop restart (milliseconds)
+    /// Write GIF frames interlaced so browsers can show them progressively while loading
+    #[arg(long, global = true)]
+    pub interlace: bool,
+
+    /// Delay before loop restart (milliseconds, held on the last GIF frame)
     #[arg(short, long, global = true, default_value = "100")]
     pub delay: u16,
 
@@ -71,22 +141,89 @@ pub struct Args {
     #[arg(short = 'g', long, global = true)]
     pub no_gaps: bool,
 
+    /// Mark frames where --no-gaps shortened a pause with a badge showing the time cut
+    #[arg(long, global = true, requires = "no_gaps")]
+    pub gap_indicator: bool,
+
     /// Add trailer at end (1.5s pause before loop)
     #[arg(long, global = true)]
     pub trailer: bool,
 
-    /// Title text
+    /// Drop trailing events that don't change the screen (e.g. idle time after exit)
+    #[arg(long, global = true)]
+    pub trim_trailing_blank: bool,
+
+    /// Skip output events that repeat the previous one byte for byte when replaying it cannot
+    /// change the screen (e.g. a spinner redrawing the same frame), to save emulator work
+    #[arg(long, global = true)]
+    pub coalesce_events: bool,
+
+    /// Title text [default: the recording's own title]
     #[arg(long, global = true)]
     pub title: Option<String>,
 
+    /// Draw no title, even when the recording has one
+    #[arg(long, global = true, conflicts_with = "title")]
+    pub no_title: bool,
+
     /// Disable auto line wrap
     #[arg(long, global = true)]
     pub no_autowrap: bool,
 
+    /// Ignore alternate screen switches so full-screen programs draw on the main screen
+    #[arg(long, global = true)]
+    pub no_alt_screen: bool,
+
+    /// Terminal quirks to emulate (linux: a bare line feed also returns to column 0)
+    /// [default: from the recording's TERM, else linux]
+    #[arg(long, global = true, value_parser = ["xterm", "linux"])]
+    pub term_mode: Option<String>,
+
+    /// Backspace at column 0 moves to the end of the previous line instead of stopping
+    #[arg(long, global = true)]
+    pub bs_wrap: bool,
+
+    /// Draw a dim guide where tabs advanced the cursor, to show indentation
+    #[arg(long, global = true)]
+    pub show_tabs: bool,
+
+    /// Skip, with a warning, any sequence that faults the emulator instead of aborting (for untrusted recordings)
+    #[arg(long, global = true)]
+    pub fuzz_safe: bool,
+
+    /// Draw text hidden with SGR 8 (conceal), e.g. to debug a recording of a password prompt
+    #[arg(long, global = true)]
+    pub reveal_concealed: bool,
+
     /// Hide cursor in output
     #[arg(long, global = true)]
     pub no_cursor: bool,
 
+    /// Minimum contrast ratio between the cursor and the cell behind it (1.0 = plain inversion)
+    #[arg(long, global = true, value_name = "RATIO", default_value = "1.5")]
+    pub cursor_contrast: f32,
+
+    /// Render at 2x pixel density for HiDPI (retina) displays: glyphs are drawn at twice the size
+    /// (bitmap fonts scaled, TrueType rasterized at double --font-size) and the theme scaled to match
+    #[arg(long, global = true)]
+    pub retina: bool,
+
+    /// CRT effect: darken alternate pixel rows like scanlines
+    #[arg(long, global = true)]
+    pub crt: bool,
+
+    /// Vignette effect: darken toward the corners (0.0-1.0)
+    #[arg(long, global = true, value_name = "STRENGTH")]
+    pub vignette: Option<f32>,
+
+    /// Flash (invert) the frame when the recording rings the bell (BEL)
+    #[arg(long, global = true)]
+    pub visual_bell: bool,
+
+    /// Palette file to use instead of the theme's (.gpl, JSON, or one #rrggbb per line)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub palette_file: Option<PathBuf>,
+
     /// Use terminal's default color palette instead of theme palette
     #[arg(long, global = true)]
     pub terminal_colors: bool,
@@ -107,13 +244,69 @@ pub struct Args {
     #[arg(long, global = true, value_name = "FORMAT")]
     pub format: Option<String>,
 
-    /// Generate multiple formats (comma-separated: cast,gif,webm,md)
+    /// Template for auto-generated output names ({basename}, {date}, {theme}, {index})
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    pub output_template: Option<String>,
+
+    /// Generate multiple formats (comma-separated: cast,gif,webm,svg,html,md)
     #[arg(long, global = true, value_delimiter = ',')]
     pub formats: Vec<String>,
 
+    /// Markdown template for the md format ({title}, {gif}, {webm}, {cast}, {version}, {repository})
+    #[arg(long, global = true, value_name = "FILE")]
+    pub md_template: Option<PathBuf>,
+
     /// Start MCP (Model Context Protocol) server
     #[arg(long)]
     pub mcp: bool,
+
+    /// Write the full session text, including lines scrolled off screen, to FILE
+    #[arg(long, global = true, value_name = "FILE")]
+    pub export_scrollback: Option<PathBuf>,
+
+    /// Padding in pixels around the terminal, replacing the theme's: one value or L,T,R,B
+    #[arg(long, global = true, value_name = "PX", value_parser = parse_padding)]
+    pub pad: Option<Padding>,
+
+    /// Multiply the theme's padding, layer images and positions by FACTOR, to match a larger or smaller font
+    #[arg(long, global = true, value_name = "FACTOR", value_parser = parse_theme_scale)]
+    pub theme_scale: Option<f64>,
+
+    /// Exact output size in pixels (WxH); the frame is centered and letterboxed with the background
+    #[arg(long, global = true, value_name = "WxH", value_parser = parse_canvas_size)]
+    pub canvas: Option<(usize, usize)>,
+
+    /// Render on the CPU even when built with GPU support, for output identical to a CPU-only build
+    #[arg(long, global = true)]
+    pub force_cpu: bool,
+
+    /// GPU to prefer when several are present (low = integrated, power-saving)
+    #[arg(long, global = true, default_value = "high", value_parser = ["low", "high"])]
+    pub gpu_power: String,
+
+    /// Render on the first GPU whose name contains NAME (case-insensitive)
+    #[arg(long, global = true, value_name = "NAME")]
+    pub gpu_adapter: Option<String>,
+
+    /// Validate that the recording parses and emulates cleanly, without writing output
+    #[arg(long, global = true)]
+    pub check: bool,
+
+    /// Print the final terminal grid as text to stdout instead of writing output ("ansi" adds colors)
+    #[arg(long, global = true, value_name = "plain|ansi", num_args = 0..=1, default_missing_value = "plain",
+          value_parser = ["plain", "ansi"])]
+    pub grid_only: Option<String>,
+
+    /// Verbose output (show detailed messages)
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+impl Default for Args {
+    /// The settings `ttyvid convert` uses when no options are given
+    fn default() -> Self {
+        Self::parse_from(["ttyvid"])
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -140,13 +333,25 @@ pub enum Command {
         #[arg(long)]
         stats: bool,
 
-        /// Verbose output (show detailed messages)
-        #[arg(short, long)]
-        verbose: bool,
-
         /// Enable unbuffered output (sets PYTHONUNBUFFERED=1 for Python scripts)
         #[arg(short = 'u', long)]
         unbuffered: bool,
+
+        /// Stop after SECONDS, ending the command if it is still running
+        #[arg(long, value_name = "SECONDS", value_parser = parse_duration)]
+        duration: Option<f64>,
+
+        /// Add to an existing .cast, continuing its timestamps after its last event
+        #[arg(long)]
+        append: bool,
+
+        /// Also write the raw terminal output to FILE, as a plain log without timing
+        #[arg(long, value_name = "FILE")]
+        tee: Option<PathBuf>,
+
+        /// Set an environment variable for the recorded program (repeatable); TERM and SHELL are also stored in the cast header
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<(String, String)>,
     },
 
     /// Convert .cast file to video
@@ -155,11 +360,60 @@ pub enum Command {
         #[arg(short, long)]
         input: PathBuf,
 
+        /// Output file (.gif, .webm or .svg)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Render two recordings side by side in time, tinting cells that differ
+    Diff {
+        /// Baseline asciicast file
+        a: PathBuf,
+
+        /// Asciicast file to compare against the baseline
+        b: PathBuf,
+
         /// Output file (.gif or .webm)
         #[arg(short, long)]
         output: PathBuf,
     },
 
+    /// Tile frames from several points in a recording into one labeled PNG
+    ContactSheet {
+        /// Input asciicast file
+        input: PathBuf,
+
+        /// Output PNG file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Times in seconds to capture, comma-separated (e.g. 1,2.5,10)
+        #[arg(long, value_delimiter = ',', conflicts_with = "samples")]
+        frame_at: Vec<f64>,
+
+        /// Number of evenly spaced frames to capture [default: 4]
+        #[arg(long)]
+        samples: Option<usize>,
+
+        /// Tiles per row [default: square-ish grid]
+        #[arg(long)]
+        columns: Option<usize>,
+    },
+
+    /// Write the text of every line a recording displayed, in order
+    Transcript {
+        /// Input asciicast file
+        input: PathBuf,
+
+        /// Output text file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Prefix each line with the time (seconds) it first appeared
+        #[arg(long)]
+        timestamps: bool,
+    },
+
     /// List available fonts
     ListFonts {
         /// Show system TrueType fonts
@@ -209,6 +463,20 @@ pub enum Command {
         bg: Option<u8>,
     },
 
+    /// Generate a reference card of the 256 ANSI colors (palette from --theme)
+    AnsiCard {
+        /// Output PNG file path
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Export the palette from --theme as a GIMP .gpl, a list of hex codes or JSON
+    ExportPalette {
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
     /// Generate font card image showing all characters
     FontCard {
         /// Font name (embedded) or .fd file path
@@ -224,3 +492,97 @@ pub enum Command {
         size: usize,
     },
 }
+
+/// Parse `--pad`: a single value for every side, or four comma-separated values (left,top,right,bottom)
+fn parse_padding(value: &str) -> Result<Padding, String> {
+    let sides = value
+        .split(',')
+        .map(|part| part.trim().parse::<u16>().map(i32::from))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid padding '{}': expected pixel counts", value))?;
+
+    match sides[..] {
+        [all] => Ok(Padding { left: all, top: all, right: all, bottom: all }),
+        [left, top, right, bottom] => Ok(Padding { left, top, right, bottom }),
+        _ => Err(format!("invalid padding '{}': expected 1 or 4 values (L,T,R,B)", value)),
+    }
+}
+
+/// Parse `--canvas WxH`
+fn parse_canvas_size(value: &str) -> Result<(usize, usize), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("invalid canvas size '{}': expected WxH, e.g. 1280x720", value))?;
+    match (width.trim().parse::<usize>(), height.trim().parse::<usize>()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("invalid canvas size '{}': expected WxH, e.g. 1280x720", value)),
+    }
+}
+
+/// Parse `--speed`: timestamps are divided by it, so it must be a positive, finite number
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        Ok(_) => Err(format!("speed must be a finite number greater than 0, got {}", value)),
+        Err(_) => Err(format!("invalid speed '{}': expected a number such as 1.5", value)),
+    }
+}
+
+/// Parse `--theme-scale`: a positive, finite factor
+fn parse_theme_scale(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        _ => Err(format!("invalid theme scale '{}': expected a number greater than 0, e.g. 2", value)),
+    }
+}
+
+/// Parse `record --duration`: a positive, finite number of seconds
+fn parse_duration(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(seconds),
+        _ => Err(format!("invalid duration '{}': expected a number of seconds greater than 0", value)),
+    }
+}
+
+/// Parse `--env`: a non-empty variable name, `=`, and a (possibly empty) value
+fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, val)) if !key.is_empty() => Ok((key.to_string(), val.to_string())),
+        _ => Err(format!("invalid environment variable '{}': expected KEY=VALUE", value)),
+    }
+}
+
+/// Parse `--color-space`: "bt601" or "bt709"
+fn parse_color_space(value: &str) -> Result<ColorSpace, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "bt601" => Ok(ColorSpace::Bt601),
+        "bt709" => Ok(ColorSpace::Bt709),
+        _ => Err(format!("invalid color space '{}': expected bt601 or bt709", value)),
+    }
+}
+
+/// Parse `--silent-audio`: "match" or a positive number of seconds
+fn parse_silent_audio(value: &str) -> Result<SilentAudio, String> {
+    if value.eq_ignore_ascii_case("match") {
+        return Ok(SilentAudio::MatchVideo);
+    }
+    match value.trim().parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(SilentAudio::Seconds(seconds)),
+        _ => Err(format!("invalid audio length '{}': expected seconds or \"match\"", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_must_be_positive() {
+        let parse = |speed: &str| Args::try_parse_from(["ttyvid", "--speed", speed]);
+        assert_eq!(parse("2.5").unwrap().speed, 2.5);
+        for speed in ["0", "-1", "inf", "fast"] {
+            let error = parse(speed).unwrap_err();
+            assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation, "--speed {}", speed);
+        }
+    }
+}
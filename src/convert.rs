@@ -0,0 +1,1854 @@
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthChar;
+
+use crate::cli::{self, Args};
+use crate::input::{Event, EventType, Metadata, SpeedCurve};
+use crate::terminal::{TerminalEmulator, Grid};
+use crate::renderer::{Palette, Canvas, Font, query_terminal_font, RenderOptions};
+use crate::renderer::effects::{FrameEffect, Scanlines, Vignette};
+#[cfg(feature = "gpu")]
+use crate::renderer::{GpuRenderer, GpuSelection, RenderBackend};
+use crate::renderer::Rasterizer;
+use crate::encoder::{EncoderWrapper, OutputFormat, ParallelEncoder};
+use crate::theme::{Theme, TextLayer};
+use crate::theme::layers::{LayerClock, LayerRenderer, LayerImage};
+
+/// Options for [`convert_events`]: the same settings as the `convert` command line,
+/// starting from `ConvertOptions::default()`
+#[cfg_attr(not(test), allow(dead_code))] // library API; the binary converts through convert_to_targets
+pub type ConvertOptions = Args;
+
+/// Render events built in memory (no recording file) to `output`, typed by
+/// `options.format` or else the file extension
+#[cfg_attr(not(test), allow(dead_code))]
+pub fn convert_events(events: Vec<Event>, metadata: Metadata, output: &Path, options: ConvertOptions) -> Result<()> {
+    let format = output_format(&options, Some(output))?;
+    convert_to_targets(&options, "in-memory events", events, metadata, &[(output.to_path_buf(), format)])
+}
+
+/// Format of the first output: --format when given (warning when it disagrees with
+/// the file extension), else detected from the extension, else GIF
+pub(crate) fn output_format(args: &Args, output: Option<&Path>) -> Result<OutputFormat> {
+    // Determine output format
+    let output_format = if let Some(ref fmt_str) = args.format {
+        // Explicit format specified
+        let explicit_format = match fmt_str.to_lowercase().as_str() {
+            "gif" => OutputFormat::Gif,
+            #[cfg(feature = "webm")]
+            "webm" => OutputFormat::Webm,
+            #[cfg(feature = "svg")]
+            "svg" => OutputFormat::Svg,
+            _ => {
+                let supported = OutputFormat::names().join(", ");
+                #[cfg(feature = "webm")]
+                {
+                    anyhow::bail!("Unknown format: {}. Supported formats: {}", fmt_str, supported)
+                }
+                #[cfg(not(feature = "webm"))]
+                {
+                    anyhow::bail!("Unknown format: {}. Supported formats: {} (compile with --features webm for WebM support)", fmt_str, supported)
+                }
+            }
+        };
+
+        // Warn if explicit format doesn't match output file extension
+        if let Some(path) = output {
+            if let Some(detected_format) = OutputFormat::from_path(path) {
+                if detected_format != explicit_format {
+                    eprintln!("Warning: Specified format '{:?}' doesn't match output file extension '.{}'",
+                        explicit_format, path.extension().and_then(|e| e.to_str()).unwrap_or("?"));
+                    eprintln!("         Using specified format: {:?}", explicit_format);
+                }
+            }
+        }
+
+        explicit_format
+    } else if let Some(path) = output {
+        // Auto-detect from output file extension
+        OutputFormat::from_path(path).unwrap_or(OutputFormat::Gif)
+    } else {
+        // Default to GIF
+        OutputFormat::Gif
+    };
+
+    Ok(output_format)
+}
+
+/// Render recorded events into every target from a single render pass
+pub(crate) fn convert_to_targets(
+    args: &Args,
+    input_label: &str,
+    mut events: Vec<Event>,
+    metadata: Metadata,
+    targets: &[(PathBuf, OutputFormat)],
+) -> Result<()> {
+    // Query terminal size if requested
+    let (term_cols, term_rows) = if args.clone || args.terminal_size {
+        use crossterm::terminal;
+        if let Ok((cols, rows)) = terminal::size() {
+            eprintln!("Using terminal size: {}x{}", cols, rows);
+            (Some(cols as usize), Some(rows as usize))
+        } else {
+            eprintln!("Warning: Could not query terminal size, using defaults");
+            (None, None)
+        }
+    } else {
+        (None, None)
+    };
+
+    // Determine dimensions: terminal size > explicit args > metadata
+    let width = term_cols.or(args.columns).unwrap_or(metadata.width);
+    let height = term_rows.or(args.rows).unwrap_or(metadata.height);
+    let width = clamp_dimension("columns", width, args.max_columns)?;
+    let height = clamp_dimension("rows", height, args.max_rows)?;
+
+    // Recorded resizes are followed from the header size, unless the size was given explicitly
+    let size_locked = term_cols.is_some() || args.columns.is_some() || args.rows.is_some();
+    if size_locked {
+        events.retain(|event| !matches!(event.event_type, EventType::Resize));
+    }
+    // The output frame fits the largest size the terminal reaches
+    let (frame_cols, frame_rows) = events.iter()
+        .filter_map(|event| event.resize_dimensions())
+        .fold((width, height), |(w, h), (cols, rows)| {
+            (w.max(cols.min(args.max_columns)), h.max(rows.min(args.max_rows)))
+        });
+
+    let mut theme = load_theme(args)?;
+    // --retina draws at twice the pixel density: the font, and the theme's geometry to match it
+    let density = pixel_density(args);
+    let theme_scale = args.theme_scale.unwrap_or(1.0) * density as f64;
+    if theme_scale != 1.0 {
+        theme.scale(theme_scale);
+    }
+
+    println!(" - input: {}", input_label);
+    for (path, format) in targets {
+        println!(" - output: {} ({:?})", path.display(), format);
+    }
+    println!(" - theme: {}", theme.name);
+
+    // Title text: --title, else the title stored in the recording (--no-title draws none)
+    let title_text = if args.no_title { None } else { args.title.clone().or_else(|| metadata.title.clone()) };
+    if let (Some(ref text), Some(_)) = (&title_text, &theme.title) {
+        println!(" - title: {}", text);
+    }
+    println!(" - speed: {}", args.speed);
+    println!(" - events: {}", events.len());
+    println!(" - character dimensions: {}x{}", width, height);
+    if (frame_cols, frame_rows) != (width, height) {
+        println!(" - resized up to: {}x{}", frame_cols, frame_rows);
+    }
+
+    // Apply speed curve or multiplier to timestamps
+    let speed_curve = match args.speed_curve {
+        Some(ref curve_path) => {
+            println!(" - speed curve: {}", curve_path.display());
+            Some(SpeedCurve::load(curve_path, args.speed)?)
+        }
+        None => None,
+    };
+    if let Some(ref curve) = speed_curve {
+        for event in &mut events {
+            event.timestamp = curve.retime(event.timestamp);
+        }
+    } else if args.speed != 1.0 {
+        for event in &mut events {
+            event.timestamp /= args.speed;
+        }
+    }
+
+    // Remove gaps if requested, remembering where they were for --gap-indicator
+    let compressed_gaps = if args.no_gaps { remove_gaps(&mut events) } else { Vec::new() };
+
+    // Drop trailing events that leave the screen unchanged
+    if args.trim_trailing_blank {
+        let trimmed = trim_trailing_blank(&mut events, args, &metadata, (width, height), (frame_cols, frame_rows));
+        if trimmed > 0 {
+            println!(" - trimmed: {} trailing events with no visible change", trimmed);
+        }
+    }
+
+    // Skip repeats of redraws that land on the same cells again
+    if args.coalesce_events {
+        let coalesced = coalesce_repeated_events(&mut events, width);
+        if coalesced > 0 {
+            println!(" - coalesced: {} repeated events", coalesced);
+        }
+    }
+
+    // Calculate total duration and frame count
+    let duration = if !events.is_empty() {
+        events.last().unwrap().timestamp
+    } else {
+        0.0
+    };
+    let mut frame_rate = effective_fps(args.fps);
+    // Add 1 frame to ensure all events are processed (last event needs a frame AFTER it to be visible)
+    // plus trailer frames if requested (1.5 seconds holding the final state)
+    let frame_counts = |fps: u32| {
+        let frames = ((duration * fps as f64).ceil() as usize + 1).max(1);
+        let trailer = if args.trailer { (fps as f64 * 1.5).round() as usize } else { 0 };
+        (frames, trailer)
+    };
+
+    // --max-frames: refuse, or lower the frame rate until the whole recording fits
+    if let Some(max_frames) = args.max_frames {
+        let (frames, trailer) = frame_counts(frame_rate);
+        if frames + trailer > max_frames {
+            if args.on_overflow == "reduce-fps" {
+                let reduced = (1..frame_rate).rev()
+                    .find(|&fps| {
+                        let (frames, trailer) = frame_counts(fps);
+                        frames + trailer <= max_frames
+                    })
+                    .ok_or_else(|| anyhow::anyhow!(
+                        "{:.1}s recording needs more than --max-frames {} frames even at 1 fps", duration, max_frames
+                    ))?;
+                eprintln!("Warning: {} frames at {} fps exceeds --max-frames {}; reducing to {} fps",
+                    frames + trailer, frame_rate, max_frames, reduced);
+                frame_rate = reduced;
+            } else {
+                anyhow::bail!(
+                    "{:.1}s recording at {} fps needs {} frames, more than --max-frames {}. \
+                     Lower --fps, trim the recording, or pass --on-overflow reduce-fps",
+                    duration, frame_rate, frames + trailer, max_frames
+                );
+            }
+        }
+    }
+    let (frame_count, trailer_frame_count) = frame_counts(frame_rate);
+    let total_frame_count = frame_count + trailer_frame_count;
+
+    println!(" - frame rate: {}", frame_rate);
+    println!(" - frames: {}", frame_count);
+    println!(" - seconds: {:.2}", duration);
+    if args.trailer {
+        println!(" - trailer: {} frames (1.5s)", trailer_frame_count);
+    }
+
+    // Query terminal colors early if needed (gets palette + default colors in one go)
+    let (palette, term_default_fg, term_default_bg) = frame_palette(args, &theme)?;
+
+    // Create terminal emulator with colors (terminal colors override theme)
+    let default_fg = term_default_fg.unwrap_or(theme.default_foreground);
+    let default_bg = term_default_bg.unwrap_or(theme.default_background);
+    eprintln!("Using colors: fg={}, bg={}", default_fg, default_bg);
+    let mut terminal = TerminalEmulator::new(width, height, !args.no_autowrap, default_fg, default_bg);
+    terminal.set_verbose(args.verbose);
+    terminal.collect_unhandled(args.verbose);
+    configure_emulation(&mut terminal, args, &metadata);
+    if args.export_scrollback.is_some() {
+        terminal.enable_scrollback(SCROLLBACK_LINES);
+    }
+    terminal.set_palette(palette.clone());
+
+    // Theme fonts apply only when no font was picked on the command line
+    let cli_font_chosen = args.font.is_some() || args.font_file.is_some() || args.system_font.is_some() || args.clone;
+    let font_choice = if cli_font_chosen { args.font.clone() } else { theme.font.clone() };
+    let system_font = if cli_font_chosen { args.system_font.clone() } else { theme.system_font.clone() };
+    let font_size = args.font_size.or(theme.font_size).unwrap_or(cli::DEFAULT_FONT_SIZE);
+    let render_options = RenderOptions {
+        proportional: args.proportional,
+        cursor_contrast: args.cursor_contrast,
+        reveal_concealed: args.reveal_concealed,
+    };
+
+    // Create rasterizer with font (GPU-accelerated if compiled with --features gpu)
+    let mut font = load_font(args, font_choice.as_deref(), system_font.as_deref(), font_size);
+    if density > 1 {
+        font = font.scaled(density);
+    }
+    font.set_glyph_contrast(args.glyph_contrast);
+
+    #[cfg(feature = "gpu")]
+    let rasterizer = {
+        let renderer: Box<dyn RenderBackend> = if args.force_cpu {
+            // The same rasterizer a build without the gpu feature uses; the GPU is never touched
+            let mut renderer = Rasterizer::with_font(font);
+            renderer.set_palette(palette.clone());
+            renderer.set_options(render_options);
+            Box::new(renderer)
+        } else {
+            let selection = GpuSelection { low_power: args.gpu_power == "low", adapter: args.gpu_adapter.clone() };
+            let mut renderer = GpuRenderer::with_gpu_selection(font, palette.clone(), &selection);
+            renderer.set_options(render_options);
+            Box::new(renderer)
+        };
+        renderer
+    };
+
+    #[cfg(not(feature = "gpu"))]
+    let rasterizer = {
+        let mut rasterizer = Rasterizer::with_font(font);
+        rasterizer.set_palette(palette.clone());
+        rasterizer.set_options(render_options);
+        rasterizer
+    };
+
+    let (term_pixel_width, term_pixel_height) = rasterizer.canvas_size(frame_cols, frame_rows);
+
+    // Apply padding (--pad replaces the theme's, which is already scaled with the theme)
+    let (mut padding_left, mut padding_top, mut padding_right, mut padding_bottom) = if let Some(padding) = args.pad.as_ref() {
+        (padding.left as usize * density, padding.top as usize * density, padding.right as usize * density, padding.bottom as usize * density)
+    } else if let Some(padding) = theme.padding.as_ref() {
+        (padding.left as usize, padding.top as usize, padding.right as usize, padding.bottom as usize)
+    } else {
+        (0, 0, 0, 0)
+    };
+
+    // --canvas: letterbox to an exact size by growing the padding evenly on both sides
+    let target_canvas = args.canvas.map(|(width, height)| (width * density, height * density));
+    if let Some((target_width, target_height)) = target_canvas {
+        let extra_width = target_width.saturating_sub(term_pixel_width + padding_left + padding_right);
+        let extra_height = target_height.saturating_sub(term_pixel_height + padding_top + padding_bottom);
+        padding_left += extra_width / 2;
+        padding_right += extra_width - extra_width / 2;
+        padding_top += extra_height / 2;
+        padding_bottom += extra_height - extra_height / 2;
+    }
+
+    let pixel_width = term_pixel_width + padding_left + padding_right;
+    let pixel_height = term_pixel_height + padding_top + padding_bottom;
+
+    println!(" - terminal pixel dimensions: {}x{}", term_pixel_width, term_pixel_height);
+    if padding_left > 0 || padding_top > 0 || padding_right > 0 || padding_bottom > 0 {
+        println!(" - padding: L:{} T:{} R:{} B:{}", padding_left, padding_top, padding_right, padding_bottom);
+    }
+
+    // A frame bigger than --canvas is cropped around its center
+    let mut crop = None;
+    if let Some((target_width, target_height)) = target_canvas {
+        if pixel_width > target_width || pixel_height > target_height {
+            eprintln!("Warning: frame {}x{} is larger than --canvas {}x{}, cropping to the center",
+                pixel_width, pixel_height, target_width, target_height);
+            crop = Some(((pixel_width - target_width.min(pixel_width)) / 2, (pixel_height - target_height.min(pixel_height)) / 2,
+                target_width.min(pixel_width), target_height.min(pixel_height)));
+        }
+    }
+    let (output_width, output_height) = crop.map_or((pixel_width, pixel_height), |(_, _, w, h)| (w, h));
+    println!(" - final pixel dimensions: {}x{}", output_width, output_height);
+
+    if density > 1 {
+        println!(" - retina: {}x pixel density", density);
+    }
+
+    // Every frame allocates a canvas this big (before cropping);
+    // refuse sizes that would exhaust memory before any are allocated
+    let canvas_bytes = pixel_width * pixel_height;
+    if canvas_bytes > args.max_canvas_mb.saturating_mul(1024 * 1024) {
+        anyhow::bail!(
+            "A {}x{} frame needs {} MB per canvas, over the --max-canvas-mb limit of {} MB; \
+             reduce --font-size, --columns/--rows or --canvas, or raise the limit",
+            pixel_width,
+            pixel_height,
+            canvas_bytes.div_ceil(1024 * 1024),
+            args.max_canvas_mb
+        );
+    }
+
+    // Load theme layers (without pre-processing - render per mode in frame loop)
+    let mut layer_renderer = LayerRenderer::new();
+    for layer in &theme.layers {
+        let layer_name = if layer.file.is_empty() { "<inline>" } else { layer.file.as_str() };
+
+        let loaded = if let Some(ref data) = layer.data {
+            // Inline image: the theme file is self-contained
+            LayerImage::load_from_base64(data, layer_name)
+        } else {
+            // If theme is embedded, load ONLY from embedded layers (skip filesystem search)
+            // If theme is from filesystem, search filesystem first then fallback to embedded
+            let layer_path = if theme.is_embedded {
+                // Embedded theme: use layer path as-is (LayerImage::load will check embedded layers)
+                PathBuf::from(&layer.file)
+            } else {
+                // External theme: search filesystem locations
+                find_layer_file(&layer.file)
+            };
+            LayerImage::load(&layer_path)
+        };
+
+        match loaded {
+            Ok(layer_image) => {
+                let layer_image = if theme_scale != 1.0 { layer_image.scaled(theme_scale) } else { layer_image };
+                let anim_info = if layer_image.is_animated {
+                    format!(" [{} frames, animated]", layer_image.frame_count())
+                } else {
+                    String::new()
+                };
+                println!(" - loaded layer: {} ({}x{}) mode={:?} depth={}{}",
+                    layer_name, layer_image.width, layer_image.height, layer.mode, layer.depth, anim_info);
+                layer_renderer.add_layer(layer_image, layer.clone());
+            }
+            Err(e) if args.strict_layers => {
+                let searched = if layer.data.is_some() {
+                    vec!["inline data".to_string()]
+                } else if theme.is_embedded {
+                    vec!["embedded layers".to_string(), layer.file.clone()]
+                } else {
+                    layer_file_candidates(&layer.file).iter().map(|path| path.display().to_string()).collect()
+                };
+                return Err(e.context(format!(
+                    "Theme layer {} could not be loaded (--strict-layers); searched:\n  {}",
+                    layer_name,
+                    searched.join("\n  ")
+                )));
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to load layer image {}: {}", layer_name, e);
+            }
+        }
+    }
+
+    // Image layers, text layers and the title in one list, drawn in depth order: negative
+    // depths under the terminal, the rest over it. The title is always over the terminal,
+    // and the sort is stable, so at equal depths the title comes first, then image layers,
+    // then text layers, each in theme order
+    let title = title_text.as_deref()
+        .filter(|text| !text.is_empty())
+        .and_then(|text| theme.title.as_ref().map(|config| (text, config)));
+    let mut scene: Vec<(i32, SceneLayer)> = Vec::new();
+    if let Some((_, config)) = title {
+        scene.push((config.depth.max(0), SceneLayer::Title));
+    }
+    scene.extend(layer_renderer.depths().enumerate().map(|(index, depth)| (depth, SceneLayer::Image(index))));
+    scene.extend(theme.text_layers.iter().map(|text| (text.depth, SceneLayer::Text(text))));
+    scene.sort_by_key(|(depth, _)| *depth);
+    let (underlays, overlays) = scene.split_at(scene.partition_point(|(depth, _)| *depth < 0));
+
+    // Use terminal background for canvas fill (overrides theme background)
+    let background_color = term_default_bg.unwrap_or(theme.background);
+    eprintln!("Canvas background color index: {}", background_color);
+
+
+    // Don't use transparency in final GIF - transparency is handled during layer compositing
+    // The final output should be fully opaque with the theme background color
+    let transparent_index = None;
+
+    // --max-colors: encode with a reduced palette, remapping every frame just before encoding
+    let color_reduction = args.max_colors.map(|max| palette.reduce(max as usize, &[background_color, default_fg]));
+    if let Some((ref reduced, _)) = color_reduction {
+        println!(" - palette reduced to {} colors", reduced.colors().len() / 3);
+    }
+    let encode_palette = color_reduction.as_ref().map_or(&palette, |(reduced, _)| reduced);
+
+    // SVG targets are written from the text grid; the rest share the rendered frames
+    #[cfg(feature = "svg")]
+    let mut svg_encoders: Vec<_> = targets.iter()
+        .filter(|(_, format)| !format.is_raster())
+        .map(|(path, _)| {
+            let mut svg = crate::encoder::SvgEncoder::new(path, frame_cols, frame_rows, &palette, background_color, font_size, args.r#loop);
+            svg.set_reveal_concealed(args.reveal_concealed);
+            svg
+        })
+        .collect();
+
+    if args.silent_audio.is_some() && !targets.iter().any(|(_, format)| format.is_constant_rate()) {
+        eprintln!("Warning: --silent-audio only applies to WebM output; ignoring it");
+    }
+
+    // One encoder thread per output, all fed from the same rendered frames
+    let encoders = targets.iter().filter(|(_, format)| format.is_raster()).map(|(path, format)| {
+        EncoderWrapper::new(
+            path,
+            output_width,
+            output_height,
+            encode_palette,
+            *format,
+            args.r#loop,
+            frame_rate,
+            args.quality.clamp(0, 100),
+            transparent_index,
+            args.silent_audio,
+            args.color_space,
+            args.interlace,
+        )
+    }).collect::<Result<Vec<_>>>()?;
+    let mut encoder = ParallelEncoder::new(encoders);
+
+    // GPU BATCH MODE: Process frames in chunks of BATCH_CHUNK_FRAMES
+    // Pass 1: Collect the chunk's Grid snapshots
+    // Pass 2: Batch render the chunk at once (ONE GPU sync per chunk!)
+    // Pass 3: Composite and encode, then drop the chunk before starting the next
+
+    let frame_duration = 1.0 / frame_rate as f64;
+    let delay_centiseconds = (100.0 / frame_rate as f64).round() as u16;
+    // Loop restart delay is held on the last frame (GIF centiseconds; WebM ignores delays)
+    let last_frame_delay = delay_centiseconds.saturating_add(args.delay / 10);
+
+    // Adaptive fps: identical frames are folded into the previous frame's delay, up to
+    // one idle-rate interval. WebM plays at a constant rate, so it needs every frame.
+    let idle_delay_limit = match args.adaptive_fps {
+        Some(_) if targets.iter().any(|(_, format)| format.is_constant_rate()) => {
+            eprintln!("Warning: --adaptive-fps only applies to GIF output; emitting every frame");
+            None
+        }
+        Some(idle_fps) => Some(((100.0 / idle_fps as f64).round() as u16).max(delay_centiseconds)),
+        None => None,
+    };
+    let mut held_frame: Option<(Canvas, u16)> = None;
+    let mut emitted_frames = 0usize;
+
+    #[cfg(feature = "gpu")]
+    let use_batch_rendering = rasterizer.is_gpu_available();
+    #[cfg(not(feature = "gpu"))]
+    let use_batch_rendering = false;
+
+    // Visual bell: frames where BEL rang are drawn with inverted colors
+    let bell_invert_map = palette.invert_map();
+    let mut bell_frames = Vec::new();
+
+    // Frames whose grid changed substantially from the previous one get a keyframe hint
+    let mut scene_changes = Vec::new();
+    let mut previous_grid: Option<Grid> = None;
+
+    // Post-compositing effects, applied in order to every frame
+    let mut effects: Vec<Box<dyn FrameEffect>> = Vec::new();
+    if args.crt {
+        effects.push(Box::new(Scanlines::new(&palette)));
+    }
+    if let Some(strength) = args.vignette {
+        effects.push(Box::new(Vignette::new(&palette, strength)));
+    }
+
+    // The current chunk: grid snapshots, rendered canvases, bell and scene-change
+    // flags for frames chunk_start..chunk_start + grids.len()
+    let mut chunk_start = 0;
+    let mut grids: Vec<Grid> = Vec::new();
+    let mut term_canvases: Vec<Canvas> = Vec::new();
+
+    let start_time = std::time::Instant::now();
+    let mut event_idx = 0;
+    for frame_num in 0..total_frame_count {
+        let current_time = frame_num as f64 * frame_duration;
+
+        if use_batch_rendering && frame_num == chunk_start + grids.len() {
+            chunk_start = frame_num;
+            let chunk_end = (chunk_start + BATCH_CHUNK_FRAMES).min(total_frame_count);
+            let previous_chunk_grid = grids.pop();
+            grids.clear();
+            scene_changes.clear();
+            bell_frames.clear();
+
+            // PASS 1: Collect the chunk's grid snapshots
+            for chunk_frame in chunk_start..chunk_end {
+                let snapshot_time = chunk_frame as f64 * frame_duration;
+
+                // Process all events up to current time (only for non-trailer frames)
+                if chunk_frame < frame_count {
+                    while event_idx < events.len() && events[event_idx].timestamp <= snapshot_time {
+                        feed_event(&mut terminal, &events[event_idx], (frame_cols, frame_rows));
+                        event_idx += 1;
+                    }
+                }
+
+                // Clone the grid snapshot (Grid is cheap to clone - just Vec<Cell> where Cell is Copy)
+                let grid = blink_phase(frame_grid(&terminal, frame_cols, frame_rows), snapshot_time).into_owned();
+                let previous = grids.last().or(previous_chunk_grid.as_ref());
+                scene_changes.push(previous.is_some_and(|prev| is_scene_change(prev, &grid)));
+                grids.push(grid);
+                bell_frames.push(terminal.take_bell());
+            }
+            #[cfg(test)]
+            PEAK_BATCH_FRAMES.with(|peak| peak.set(peak.get().max(grids.len())));
+
+            // PASS 2: GPU BATCH RENDER (ONE sync for the whole chunk!)
+            #[cfg(feature = "gpu")]
+            let rendered = match rasterizer.render_grids_batch(&grids) {
+                Ok(canvases) => canvases,
+                Err(e) => {
+                    eprintln!("GPU batch render failed: {}, falling back to frame-by-frame", e);
+                    // Fallback: render each grid individually
+                    grids.iter().map(|grid| {
+                        if !args.no_cursor && terminal.state().display_cursor {
+                            let (cursor_x, cursor_y) = terminal.state().cursor_get_position();
+                            rasterizer.render_grid_with_cursor(grid, cursor_x as usize, cursor_y as usize)
+                        } else {
+                            rasterizer.render_grid(grid)
+                        }
+                    }).collect()
+                }
+            };
+            #[cfg(not(feature = "gpu"))]
+            let rendered = Vec::new(); // Won't be used
+            term_canvases = rendered;
+        }
+
+        // PASS 3: Composite with layers and encode
+        // Get or render terminal canvas; transparent-terminal themes also mark the pixels of
+        // default-background cells, which are left out when compositing
+        let (mut term_canvas, see_through) = if use_batch_rendering {
+            // Use pre-rendered canvas from batch
+            let canvas = term_canvases[frame_num - chunk_start].clone();
+            let see_through = theme.transparent_background
+                .then(|| rasterizer.default_background_mask(&grids[frame_num - chunk_start], &canvas, default_bg, None));
+            (canvas, see_through)
+        } else {
+            // CPU path: process events and render frame-by-frame
+            if frame_num < frame_count {
+                while event_idx < events.len() && events[event_idx].timestamp <= current_time {
+                    feed_event(&mut terminal, &events[event_idx], (frame_cols, frame_rows));
+                    event_idx += 1;
+                }
+            }
+
+            let grid = blink_phase(frame_grid(&terminal, frame_cols, frame_rows), current_time);
+            let cursor = (!args.no_cursor && terminal.state().display_cursor).then(|| {
+                let (cursor_x, cursor_y) = terminal.state().cursor_get_position();
+                (cursor_x as usize, cursor_y as usize)
+            });
+            let canvas = match cursor {
+                Some((cursor_x, cursor_y)) => rasterizer.render_grid_with_cursor(&grid, cursor_x, cursor_y),
+                None => rasterizer.render_grid(&grid),
+            };
+            let see_through = theme.transparent_background
+                .then(|| rasterizer.default_background_mask(&grid, &canvas, default_bg, cursor));
+            (canvas, see_through)
+        };
+
+        let bell_rang = if use_batch_rendering {
+            bell_frames[frame_num - chunk_start]
+        } else {
+            terminal.take_bell()
+        };
+        if args.visual_bell && bell_rang {
+            term_canvas.remap_colors(&bell_invert_map);
+        }
+
+        let scene_change = if use_batch_rendering {
+            scene_changes[frame_num - chunk_start]
+        } else {
+            let grid = frame_grid(&terminal, frame_cols, frame_rows);
+            let changed = previous_grid.as_ref().is_some_and(|prev| is_scene_change(prev, &grid));
+            previous_grid = Some(grid.into_owned());
+            changed
+        };
+        // Create final canvas with padding
+        let mut canvas = Canvas::new(pixel_width, pixel_height, &palette);
+
+        // Fill with background color (terminal bg overrides theme bg)
+        canvas.fill(background_color);
+
+        // Layer animation clocks in milliseconds: output time, and the recording time it shows
+        let recording_time = speed_curve.as_ref().map_or(current_time * args.speed, |curve| curve.source_time(current_time));
+        let layer_clock = LayerClock { output_ms: current_time * 1000.0, recording_ms: recording_time * 1000.0 };
+
+        let draw_layers = |canvas: &mut Canvas, layers: &[(i32, SceneLayer)]| {
+            for (_, layer) in layers {
+                match *layer {
+                    SceneLayer::Title => {
+                        if let Some((text, config)) = title {
+                            rasterizer.render_title(canvas, config.x, config.y, text, config.foreground, config.background, config.font_size);
+                        }
+                    }
+                    SceneLayer::Image(index) => layer_renderer.render_layer_at(index, canvas, palette.colors(), layer_clock),
+                    SceneLayer::Text(text) => {
+                        rasterizer.render_title(canvas, text.x, text.y, &text.text, text.foreground, text.background, text.font_size);
+                    }
+                }
+            }
+        };
+
+        // Render underlay layers (depth < 0)
+        draw_layers(&mut canvas, underlays);
+
+        // Composite terminal output onto canvas with padding offset
+        for y in 0..term_pixel_height {
+            for x in 0..term_pixel_width {
+                if see_through.as_ref().is_some_and(|mask| mask[y * term_canvas.width() + x]) {
+                    continue;
+                }
+                if let Some(color) = term_canvas.get_pixel(x, y) {
+                    canvas.set_pixel(x + padding_left, y + padding_top, color);
+                }
+            }
+        }
+
+        // Render overlay layers and the title (depth >= 0)
+        draw_layers(&mut canvas, overlays);
+
+        // --gap-indicator: badge the terminal's top-right corner just after a shortened pause
+        if args.gap_indicator {
+            let shown_for = GAP_INDICATOR_SECONDS.max(frame_duration);
+            if let Some(&(_, cut)) = compressed_gaps.iter().find(|(at, _)| (*at..*at + shown_for).contains(&current_time)) {
+                let label = format!(" \u{bb} {:.0}s ", cut);
+                let (cell_width, cell_height) = (term_pixel_width / frame_cols.max(1), term_pixel_height / frame_rows.max(1));
+                let badge_width = label.chars().count() * cell_width;
+                let x = (padding_left + term_pixel_width).saturating_sub(badge_width + cell_width / 2);
+                let y = padding_top + cell_height / 4;
+                canvas.fill_rect(x, y, badge_width, cell_height, default_fg);
+                rasterizer.render_title(&mut canvas, x as i32, y as i32, &label, background_color, default_fg, 1.0);
+            }
+        }
+
+        if let Some((x, y, width, height)) = crop {
+            canvas = canvas.subcanvas(x, y, width, height);
+        }
+
+        for effect in &effects {
+            effect.apply(&mut canvas);
+        }
+
+
+        if let Some((_, ref color_map)) = color_reduction {
+            canvas.remap_colors(color_map);
+        }
+
+        // Add frame to GIF
+        let delay = if frame_num + 1 == total_frame_count { last_frame_delay } else { delay_centiseconds };
+        #[cfg(feature = "svg")]
+        if !svg_encoders.is_empty() {
+            let grid = if use_batch_rendering {
+                Cow::Borrowed(&grids[frame_num - chunk_start])
+            } else {
+                blink_phase(frame_grid(&terminal, frame_cols, frame_rows), current_time)
+            };
+            for svg in &mut svg_encoders {
+                svg.add_frame(&grid, delay);
+            }
+        }
+        if let Some(limit) = idle_delay_limit {
+            match held_frame {
+                Some((ref held, ref mut held_delay))
+                    if held.data() == canvas.data() && (held_delay.saturating_add(delay) <= limit || frame_num + 1 == total_frame_count) =>
+                {
+                    *held_delay = held_delay.saturating_add(delay);
+                }
+                _ => {
+                    if let Some((held, held_delay)) = held_frame.take() {
+                        encoder.add_frame(held, held_delay)?;
+                        emitted_frames += 1;
+                    }
+                    if scene_change {
+                        encoder.request_keyframe()?;
+                    }
+                    held_frame = Some((canvas, delay));
+                }
+            }
+        } else {
+            if scene_change {
+                encoder.request_keyframe()?;
+            }
+            encoder.add_frame(canvas, delay)?;
+            emitted_frames += 1;
+        }
+
+        // Progress indicator with ETA
+        let percent = ((frame_num + 1) as f64 / total_frame_count as f64 * 100.0) as usize;
+        let status = if frame_num >= frame_count {
+            format!("[TRAILER {}/{}]", frame_num - frame_count + 1, trailer_frame_count)
+        } else {
+            format!("{} of {:.2}s", (current_time as f32).min(duration as f32), duration)
+        };
+
+        // Calculate ETA
+        let elapsed = start_time.elapsed().as_secs_f64();
+        let eta_str = if frame_num > 0 {
+            let frames_remaining = total_frame_count - (frame_num + 1);
+            let seconds_per_frame = elapsed / (frame_num + 1) as f64;
+            let eta_seconds = frames_remaining as f64 * seconds_per_frame;
+            if eta_seconds < 60.0 {
+                format!("ETA: {:.1}s", eta_seconds)
+            } else {
+                format!("ETA: {:.1}m", eta_seconds / 60.0)
+            }
+        } else {
+            "ETA: --".to_string()
+        };
+
+        print!("\r  {} {}% Frame: {}/{} {} FPS {}       ",
+            status,
+            percent,
+            frame_num + 1,
+            total_frame_count,
+            frame_rate,
+            eta_str
+        );
+        use std::io::Write;
+        std::io::stdout().flush()?;
+    }
+
+    println!();
+
+    if let Some((held, held_delay)) = held_frame.take() {
+        encoder.add_frame(held, held_delay)?;
+        emitted_frames += 1;
+    }
+    if idle_delay_limit.is_some() {
+        println!(" - adaptive fps: {} of {} frames emitted", emitted_frames, total_frame_count);
+    }
+
+    // Finish encoding
+    encoder.finish()?;
+    #[cfg(feature = "svg")]
+    for svg in svg_encoders {
+        svg.finish()?;
+    }
+
+    if let Some(column) = terminal.clipped_column() {
+        eprintln!("Warning: the recording moves the cursor to column {} but the terminal is {} columns wide; \
+                   content past column {} was clipped (pass --columns {} to include it)", column, width, width, column);
+    }
+
+    if !terminal.unhandled().is_empty() {
+        println!(" - unsupported sequences (ignored):");
+        for (sequence, count) in terminal.unhandled() {
+            println!("     {} ({} times)", sequence, count);
+        }
+    }
+
+    if let Some(ref path) = args.export_scrollback {
+        std::fs::write(path, terminal.scrollback_text())
+            .with_context(|| format!("Failed to write scrollback: {}", path.display()))?;
+        println!(" - scrollback exported: {}", path.display());
+    }
+
+    let total_time = start_time.elapsed();
+    let time_str = if total_time.as_secs() < 60 {
+        format!("{:.1}s", total_time.as_secs_f64())
+    } else {
+        let minutes = total_time.as_secs() / 60;
+        let seconds = total_time.as_secs() % 60;
+        format!("{}m {}s", minutes, seconds)
+    };
+
+    println!();
+    for (path, format) in targets {
+        println!("✓ {:?} created: {} (total time: {})", format, path.display(), time_str);
+    }
+
+    Ok(())
+}
+/// Something drawn under or over the terminal, ordered by depth in the frame loop
+#[derive(Clone, Copy)]
+enum SceneLayer<'a> {
+    Title,
+    /// Index into the `LayerRenderer`'s image layers
+    Image(usize),
+    Text(&'a TextLayer),
+}
+
+/// Load --theme (a theme file path, else a theme name) and apply the --set overrides
+pub(crate) fn load_theme(args: &Args) -> Result<Theme> {
+    let theme_path = std::path::Path::new(&args.theme);
+    let mut theme = if theme_path.exists() && theme_path.is_file() {
+        // Direct path to theme file
+        Theme::load(theme_path)?
+    } else {
+        // Load by name (searches filesystem + embedded)
+        Theme::load_by_name(&args.theme)?
+    };
+    for assignment in &args.theme_overrides {
+        theme.apply_override(assignment)?;
+    }
+    Ok(theme)
+}
+
+/// The palette frames are drawn with, and the default foreground and background reported
+/// by the terminal (which override the theme's): the terminal's colors under --clone or
+/// --terminal-colors, else the theme's palette, else the default one. --palette-file
+/// replaces the colors but keeps any reported defaults
+pub(crate) fn frame_palette(args: &Args, theme: &Theme) -> Result<(Palette, Option<u8>, Option<u8>)> {
+    let (palette, default_fg, default_bg) = if args.clone || args.terminal_colors {
+        eprintln!("Querying terminal for colors...");
+        // This queries the full palette AND default fg/bg colors
+        let (pal, fg, bg) = Palette::from_terminal();
+        eprintln!("Terminal colors detected: fg={:?}, bg={:?}", fg, bg);
+        (pal, fg, bg)
+    } else if let Some(ref theme_palette) = theme.palette {
+        // Use theme's custom palette
+        (Palette::from_theme(theme_palette), None, None)
+    } else {
+        // Fall back to default
+        (Palette::default(), None, None)
+    };
+    let palette = match args.palette_file {
+        Some(ref path) => crate::palette_tools::export::load_palette_file(path)?,
+        None => palette,
+    };
+    Ok((palette, default_fg, default_bg))
+}
+
+/// The terminal font: --font-file, else a system TrueType font (--system-font, the theme's,
+/// or the one --clone detects), else an embedded bitmap font
+fn load_font(args: &Args, font_choice: Option<&str>, system_font: Option<&str>, font_size: usize) -> Font {
+    if let Some(ref font_file) = args.font_file {
+        eprintln!("Loading bitmap font from file: {}", font_file.display());
+        match Font::load_from_file(font_file) {
+            Ok(bitmap_font) => {
+                eprintln!("Successfully loaded bitmap font (cell size: {}x{})", bitmap_font.width(), bitmap_font.height());
+                bitmap_font
+            }
+            Err(e) => {
+                eprintln!("Failed to load font file: {}", e);
+                eprintln!("Falling back to embedded bitmap font");
+                Font::load(font_choice)
+            }
+        }
+    } else if let Some(system_font) = system_font {
+        eprintln!("Loading system font: {} at size {}", system_font, font_size);
+        if let Some(ttf_font) = Font::from_system_font(system_font, font_size) {
+            eprintln!("Successfully loaded system font (cell size: {}x{})", ttf_font.width(), ttf_font.height());
+            ttf_font
+        } else {
+            eprintln!("Failed to load system font, falling back to embedded bitmap font");
+            Font::load(font_choice)
+        }
+    } else if args.clone {
+        if let Some(font_name) = query_terminal_font() {
+            eprintln!("Terminal font detected: {}", font_name);
+            if let Some(ttf_font) = Font::from_system_font(&font_name, font_size) {
+                eprintln!("Loaded TrueType font: {} (cell size: {}x{})", font_name, ttf_font.width(), ttf_font.height());
+                ttf_font
+            } else {
+                eprintln!("Could not load font '{}', falling back to embedded font", font_name);
+                Font::load(font_choice)
+            }
+        } else {
+            eprintln!("Could not detect terminal font, using embedded font");
+            Font::load(font_choice)
+        }
+    } else {
+        Font::load(font_choice)
+    }
+}
+
+
+/// Places a theme layer file is looked for, in search order
+fn layer_file_candidates(layer_file: &str) -> Vec<PathBuf> {
+    // Try as absolute path first
+    let mut candidates = vec![PathBuf::from(layer_file)];
+
+    // User directories
+    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", "ttyvid") {
+        for base_path in [proj_dirs.data_dir(), proj_dirs.config_dir()] {
+            candidates.push(base_path.join("themes").join(layer_file));
+        }
+    }
+
+    // Current directory, then system theme directories
+    candidates.push(PathBuf::from("themes").join(layer_file));
+    #[cfg(not(windows))]
+    {
+        candidates.push(PathBuf::from("/usr/share/ttyvid/themes").join(layer_file));
+        candidates.push(PathBuf::from("/usr/local/share/ttyvid/themes").join(layer_file));
+    }
+
+    candidates
+}
+
+/// Find layer file in theme directories
+fn find_layer_file(layer_file: &str) -> PathBuf {
+    layer_file_candidates(layer_file)
+        .into_iter()
+        .find(|path| path.exists())
+        // Fall back to relative path (will fail later if not found)
+        .unwrap_or_else(|| PathBuf::from("themes").join(layer_file))
+}
+
+/// Reject a zero terminal dimension and clamp oversized ones to `max`
+pub(crate) fn clamp_dimension(name: &str, value: usize, max: usize) -> Result<usize> {
+    if value == 0 {
+        anyhow::bail!("Terminal {} must be greater than 0", name);
+    }
+    if value > max {
+        eprintln!("Warning: {} {} exceeds maximum of {}, clamping", name, value, max);
+        return Ok(max);
+    }
+    Ok(value)
+}
+
+/// Emulation settings shared by every command that replays a recording:
+/// alternate screen handling and LF quirks (--term-mode, else the recorded TERM)
+pub(crate) fn configure_emulation(terminal: &mut TerminalEmulator, args: &Args, meta: &Metadata) {
+    terminal.set_alt_screen(!args.no_alt_screen);
+    terminal.set_bs_wrap(args.bs_wrap);
+    terminal.set_show_tabs(args.show_tabs);
+    terminal.set_fuzz_safe(args.fuzz_safe);
+    if let Some(ref mode) = args.term_mode {
+        terminal.set_mode(mode);
+    } else if let Some(term) = meta.env.get("TERM") {
+        terminal.set_term(term);
+    }
+}
+
+/// Pixels per unit of layout: 2 under --retina, else 1
+pub(crate) fn pixel_density(args: &Args) -> usize {
+    if args.retina { 2 } else { 1 }
+}
+
+/// Frame rate actually used for --fps, warning when the request is outside 1-100
+pub(crate) fn effective_fps(requested: u32) -> u32 {
+    let (frame_rate, warning) = clamp_fps(requested);
+    if let Some(warning) = warning {
+        eprintln!("Warning: {}", warning);
+    }
+    frame_rate
+}
+
+/// --fps clamped to 1-100, and the warning to show when that changed it
+fn clamp_fps(requested: u32) -> (u32, Option<String>) {
+    let frame_rate = requested.clamp(1, 100);
+    let warning = (frame_rate != requested)
+        .then(|| format!("--fps {} is outside the supported 1-100 range; using {} fps", requested, frame_rate));
+    (frame_rate, warning)
+}
+
+/// Rows kept for --export-scrollback
+const SCROLLBACK_LINES: usize = 100_000;
+
+/// Shorten every pause longer than a second to one second. Returns, for each
+/// shortened pause, the (new) time playback resumes and the seconds cut
+fn remove_gaps(events: &mut [Event]) -> Vec<(f64, f64)> {
+    let mut compressed = Vec::new();
+    let mut prev_time = 0.0;
+    let mut gap_offset = 0.0;
+
+    for event in events.iter_mut() {
+        // Gaps are measured on the original timeline
+        let gap = event.timestamp - prev_time;
+        prev_time = event.timestamp;
+        if gap > 1.0 {
+            gap_offset += gap - 1.0;
+            compressed.push((event.timestamp - gap_offset, gap - 1.0));
+        }
+        event.timestamp -= gap_offset;
+    }
+    compressed
+}
+
+/// Remove events after the last one that visibly changes the grid; returns how many were dropped
+/// (the replay is emulated the way the frames will be, see [`configure_emulation`])
+fn trim_trailing_blank(events: &mut Vec<Event>, args: &Args, metadata: &Metadata, (width, height): (usize, usize), max_size: (usize, usize)) -> usize {
+    let mut terminal = TerminalEmulator::new(width, height, !args.no_autowrap, 7, 0);
+    configure_emulation(&mut terminal, args, metadata);
+    let mut previous = terminal.grid().clone();
+    let mut last_change = None;
+
+    for (i, event) in events.iter().enumerate() {
+        feed_event(&mut terminal, event, max_size);
+        if terminal.grid() != &previous {
+            last_change = Some(i);
+            previous = terminal.grid().clone();
+        }
+    }
+
+    let keep = last_change.map_or(0, |i| i + 1);
+    let dropped = events.len() - keep;
+    events.truncate(keep);
+    dropped
+}
+
+/// Drop output events identical to the one before them when the data is an
+/// idempotent redraw (see [`is_idempotent_redraw`]), so replaying it again
+/// leaves the screen exactly as the first copy did. The last event is kept so
+/// the recording's duration does not change. Returns how many were dropped
+fn coalesce_repeated_events(events: &mut Vec<Event>, width: usize) -> usize {
+    let before = events.len();
+    let last = before.saturating_sub(1);
+    // Redraws must fit the narrowest the terminal ever gets
+    let min_width = events.iter()
+        .filter_map(|event| event.resize_dimensions().map(|(cols, _)| cols))
+        .fold(width, usize::min);
+    // Insert mode (IRM) shifts text right on every write, so nothing repeats exactly
+    let insert_mode_from = events.iter()
+        .position(|event| event.data.windows(4).any(|window| window == b"\x1b[4h"))
+        .unwrap_or(before);
+
+    let mut index = 0;
+    events.dedup_by(|event, previous| {
+        index += 1;
+        index < insert_mode_from && index != last
+            && matches!(event.event_type, EventType::Output)
+            && matches!(previous.event_type, EventType::Output)
+            && event.data == previous.data
+            && is_idempotent_redraw(&event.data, min_width)
+    });
+    before - events.len()
+}
+
+/// Whether feeding `data` twice leaves the terminal as feeding it once: it
+/// first moves the cursor to an absolute column (CR, CHA or CUP), then only
+/// writes text that stays short of the right margin, erases in line, sets
+/// cursor visibility or sets attributes starting from a reset (SGR 0)
+fn is_idempotent_redraw(data: &[u8], width: usize) -> bool {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return false;
+    };
+    let mut anchored = false;
+    // Text drawn before an SGR reset uses the attributes left by the previous copy,
+    // which only match if the data sets no attributes at all
+    let mut attributes_reset = false;
+    let mut attributes_set = false;
+    let mut printed_unreset = false;
+    let mut column = 0;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                anchored = true;
+                column = 0;
+            }
+            '\x1b' => {
+                if chars.next() != Some('[') {
+                    return false;
+                }
+                let mut params = String::new();
+                let final_byte = loop {
+                    match chars.next() {
+                        Some(c @ ('0'..='9' | ';' | '?')) => params.push(c),
+                        Some(c) => break c,
+                        None => return false,
+                    }
+                };
+                let private = params.starts_with('?');
+                let mut numbers = params.trim_start_matches('?').split(';').map(|n| n.parse::<usize>().unwrap_or(0));
+                match (final_byte, private) {
+                    ('G', false) => {
+                        anchored = true;
+                        column = numbers.next().unwrap_or(1).max(1) - 1;
+                    }
+                    ('H' | 'f', false) => {
+                        anchored = true;
+                        column = numbers.nth(1).unwrap_or(1).max(1) - 1;
+                    }
+                    ('m', false) => {
+                        if printed_unreset {
+                            return false;
+                        }
+                        attributes_set = true;
+                        attributes_reset |= numbers.next() == Some(0);
+                    }
+                    ('K', false) => {}
+                    ('h' | 'l', true) if params == "?25" => {}
+                    _ => return false,
+                }
+            }
+            c if c.is_control() => return false,
+            c => {
+                if !anchored || (attributes_set && !attributes_reset) {
+                    return false;
+                }
+                printed_unreset |= !attributes_reset;
+                // Stay clear of the last column, where a pending wrap would move the next write
+                column += c.width().unwrap_or(0).max(1);
+                if column >= width {
+                    return false;
+                }
+            }
+        }
+    }
+    anchored
+}
+
+/// Apply one recorded event: resizes (capped at `max_size`) change the grid, everything else is output
+pub(crate) fn feed_event(terminal: &mut TerminalEmulator, event: &Event, max_size: (usize, usize)) {
+    match event.event_type {
+        EventType::Resize => {
+            if let Some((cols, rows)) = event.resize_dimensions() {
+                terminal.resize(cols.min(max_size.0), rows.min(max_size.1));
+            }
+        }
+        _ => terminal.feed_bytes(&event.data),
+    }
+}
+
+/// The grid to render, padded out to the frame size while a recorded resize has made it smaller
+pub(crate) fn frame_grid(terminal: &TerminalEmulator, cols: usize, rows: usize) -> Cow<'_, Grid> {
+    let grid = terminal.grid();
+    if grid.width() == cols && grid.height() == rows {
+        Cow::Borrowed(grid)
+    } else {
+        let state = terminal.state();
+        Cow::Owned(grid.resized(cols, rows, state.default_foreground as u8, state.default_background as u8))
+    }
+}
+
+/// Blinking text (SGR 5) is shown for the first half of each cycle and hidden for the second
+const BLINK_PERIOD_SECONDS: f64 = 1.0 / 1.5;
+
+/// The grid as it looks at `time` (output seconds): blinking cells are drawn in
+/// their background color during the "off" half of the blink cycle
+fn blink_phase(grid: Cow<'_, Grid>, time: f64) -> Cow<'_, Grid> {
+    if time % BLINK_PERIOD_SECONDS < BLINK_PERIOD_SECONDS / 2.0 || !grid.has_blinking() {
+        return grid;
+    }
+    let mut grid = grid.into_owned();
+    grid.hide_blinking();
+    Cow::Owned(grid)
+}
+
+/// Frames snapshotted and batch-rendered together on the GPU path; bounds peak memory
+const BATCH_CHUNK_FRAMES: usize = 256;
+
+#[cfg(test)]
+thread_local! {
+    /// Most grid snapshots the batch path has held at once on this thread
+    static PEAK_BATCH_FRAMES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Fraction of cells that must differ between consecutive frames to count as a new scene
+const SCENE_CHANGE_RATIO: f64 = 0.5;
+
+/// How long (seconds of output) the --gap-indicator badge stays up after a shortened pause
+const GAP_INDICATOR_SECONDS: f64 = 0.5;
+
+/// True when most of the screen changed at once (clear, alt-screen switch, resize)
+fn is_scene_change(previous: &Grid, current: &Grid) -> bool {
+    if previous.width() != current.width() || previous.height() != current.height() {
+        return true;
+    }
+    let total = current.cells().len();
+    if total == 0 {
+        return false;
+    }
+    current.changed_cells(previous) as f64 / total as f64 >= SCENE_CHANGE_RATIO
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn output_event(timestamp: f64, text: &str) -> Event {
+        Event { timestamp, event_type: EventType::Output, data: text.as_bytes().to_vec() }
+    }
+
+    fn metadata(width: usize, height: usize) -> Metadata {
+        Metadata { width, height, title: None, env: HashMap::new() }
+    }
+
+    /// `ttyvid-<name>-<pid>.<extension>` in the temp dir
+    fn temp_path(name: &str, extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ttyvid-{}-{}.{}", name, std::process::id(), extension))
+    }
+
+    /// Every frame of the GIF at `path` decoded to RGBA; the file is removed afterwards
+    fn read_frames(path: &Path) -> Vec<gif::Frame<'static>> {
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = decoder.read_info(std::fs::File::open(path).unwrap()).unwrap();
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.clone());
+        }
+        std::fs::remove_file(path).unwrap();
+        frames
+    }
+
+    /// Convert `events` to a GIF in the temp dir and return its frames decoded to RGBA
+    fn gif_frames(name: &str, events: Vec<Event>, mut options: ConvertOptions) -> Vec<gif::Frame<'static>> {
+        options.force_cpu = true;
+        let path = temp_path(name, "gif");
+        convert_events(events, metadata(20, 4), &path, options).unwrap();
+        read_frames(&path)
+    }
+
+    /// Convert a short session to a GIF in the temp dir and return the decoded frames' size
+    fn gif_size(name: &str, options: ConvertOptions) -> (u16, u16) {
+        let frames = gif_frames(name, vec![output_event(0.0, "hello\r\n"), output_event(0.2, "world")], options);
+        (frames[0].width, frames[0].height)
+    }
+
+    #[test]
+    fn convert_events_writes_a_gif() {
+        let path = temp_path("events", "gif");
+        let events = vec![output_event(0.0, "\x1b[31mred\x1b[0m\r\n"), output_event(0.5, "$ ")];
+        let options = ConvertOptions { force_cpu: true, ..ConvertOptions::default() };
+        convert_events(events, metadata(10, 2), &path, options).unwrap();
+
+        let frames = read_frames(&path);
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|frame| frame.width > 0 && frame.height > 0));
+    }
+
+    #[test]
+    fn trailing_events_without_visible_change_are_trimmed() {
+        let mut events = vec![
+            output_event(0.0, "$ exit\r\n"),
+            output_event(0.5, "bye"),
+            // Two seconds of output that leaves the screen as it was
+            output_event(1.0, ""),
+            output_event(1.5, "\x1b[?25h"),
+            output_event(2.0, "\x1b[1;1H\x1b[2;4H"),
+            output_event(2.5, "\x07"),
+        ];
+        let args = Args::default();
+        let dropped = trim_trailing_blank(&mut events, &args, &metadata(20, 4), (20, 4), (20, 4));
+        assert_eq!(dropped, 4);
+        assert_eq!(events.last().unwrap().data, b"bye");
+    }
+
+    #[test]
+    fn retina_doubles_output_dimensions() {
+        let (width, height) = gif_size("1x", ConvertOptions::default());
+        let (retina_width, retina_height) = gif_size("2x", ConvertOptions { retina: true, ..ConvertOptions::default() });
+        assert_eq!((retina_width, retina_height), (width * 2, height * 2));
+    }
+
+    #[test]
+    fn text_layers_draw_in_depth_order_with_image_layers() {
+        use base64::Engine;
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        // A red image tiled over the canvas at depth 1, between a green text at depth 0 and a blue one at depth 2
+        let theme = format!(
+            "name: layered\n\
+             layers:\n\
+             - depth: 1\n  mode: tile\n  data: {}\n\
+             text_layers:\n\
+             - text: under\n  x: 4\n  y: 20\n  fg: 10\n  depth: 0\n\
+             - text: over\n  x: 12\n  y: 6\n  fg: 12\n  depth: 2\n",
+            base64::engine::general_purpose::STANDARD.encode(&png)
+        );
+        let theme_path = temp_path("layered", "yaml");
+        std::fs::write(&theme_path, theme).unwrap();
+        let options = ConvertOptions { theme: theme_path.to_string_lossy().into_owned(), ..ConvertOptions::default() };
+        let frames = gif_frames("layered", vec![output_event(0.0, "hello")], options);
+        std::fs::remove_file(&theme_path).unwrap();
+
+        let frame = &frames[0];
+        let pixels: Vec<(usize, usize, &[u8])> = frame.buffer.chunks(4).enumerate()
+            .map(|(i, rgba)| (i % frame.width as usize, i / frame.width as usize, rgba))
+            .collect();
+        let blue: Vec<(usize, usize)> = pixels.iter().filter(|(_, _, rgba)| rgba[..3] == [0, 0, 255]).map(|&(x, y, _)| (x, y)).collect();
+        assert!(!blue.is_empty());
+        // The glyphs' first lit pixels fall within a cell of the configured position
+        assert!((12..20).contains(&blue.iter().map(|&(x, _)| x).min().unwrap()));
+        assert!((6..22).contains(&blue.iter().map(|&(_, y)| y).min().unwrap()));
+        assert!(pixels.iter().any(|(_, _, rgba)| rgba[..3] == [255, 0, 0]));
+        assert!(!pixels.iter().any(|(_, _, rgba)| rgba[..3] == [0, 255, 0]));
+    }
+
+    #[test]
+    fn visual_bell_inverts_the_frame_it_rings_on() {
+        let events = || vec![output_event(0.0, "hello"), output_event(0.5, "\x07"), output_event(1.0, " world")];
+        let white_pixels = |frames: &[gif::Frame]| {
+            frames.iter().map(|frame| frame.buffer.chunks(4).filter(|rgba| rgba[..3] == [255, 255, 255]).count()).max().unwrap()
+        };
+
+        let plain = gif_frames("no-bell", events(), ConvertOptions::default());
+        let flashed = gif_frames("bell", events(), ConvertOptions { visual_bell: true, ..ConvertOptions::default() });
+        // The black background turns white for the frame where BEL rang
+        assert_eq!(white_pixels(&plain), 0);
+        let (width, height) = (flashed[0].width as usize, flashed[0].height as usize);
+        assert!(white_pixels(&flashed) > width * height / 2);
+    }
+
+    #[test]
+    fn zero_columns_are_rejected_and_huge_ones_clamped() {
+        let path = temp_path("zero", "gif");
+        let options = ConvertOptions { columns: Some(0), force_cpu: true, ..ConvertOptions::default() };
+        assert!(convert_events(vec![output_event(0.0, "hi")], metadata(20, 4), &path, options).is_err());
+
+        assert_eq!(clamp_dimension("columns", 99999, 1000).unwrap(), 1000);
+        let clamped = gif_size("huge", ConvertOptions { columns: Some(99999), max_columns: 30, ..ConvertOptions::default() });
+        assert_eq!(clamped, gif_size("thirty", ConvertOptions { columns: Some(30), ..ConvertOptions::default() }));
+    }
+
+    #[test]
+    fn resize_events_grow_the_frame_unless_the_size_is_given() {
+        let events = || vec![
+            output_event(0.0, "hello"),
+            Event { timestamp: 0.5, event_type: EventType::Resize, data: b"30x6".to_vec() },
+            output_event(1.0, " world"),
+        ];
+        let header_size = gif_size("header", ConvertOptions::default());
+        let resized_size = gif_size("resized", ConvertOptions { columns: Some(30), rows: Some(6), ..ConvertOptions::default() });
+        let frame_size = |frames: Vec<gif::Frame>| (frames[0].width, frames[0].height);
+
+        // Header size, then the recorded resize
+        assert_eq!(frame_size(gif_frames("follow", events(), ConvertOptions::default())), resized_size);
+        // An explicit size wins over both
+        let locked = ConvertOptions { columns: Some(20), rows: Some(4), ..ConvertOptions::default() };
+        assert_eq!(frame_size(gif_frames("locked", events(), locked)), header_size);
+    }
+
+    #[test]
+    fn full_screen_clear_is_a_scene_change() {
+        let mut terminal = TerminalEmulator::new(10, 3, true, 7, 0);
+        terminal.feed_bytes(b"0123456789abcdefghijklmnopqrstuvwxyz");
+        let full = terminal.grid().clone();
+        terminal.feed_bytes(b"!");
+        assert!(!is_scene_change(&full, terminal.grid()));
+        terminal.feed_bytes(b"\x1b[2J");
+        assert!(is_scene_change(&full, terminal.grid()));
+    }
+
+    #[test]
+    fn loop_delay_is_held_on_the_last_frame() {
+        let events = || vec![output_event(0.0, "hello"), output_event(0.5, " world")];
+        let last_delay = |delay| gif_frames("delay", events(), ConvertOptions { delay, ..ConvertOptions::default() }).last().unwrap().delay;
+        assert_eq!(last_delay(300), last_delay(0) + 30);
+    }
+
+    #[test]
+    fn max_colors_limits_the_global_color_table() {
+        let path = temp_path("colors", "gif");
+        let events = vec![output_event(0.0, "\x1b[31mred \x1b[38;5;208morange \x1b[38;5;45mteal")];
+        let options = ConvertOptions { max_colors: Some(16), force_cpu: true, ..ConvertOptions::default() };
+        convert_events(events, metadata(20, 4), &path, options).unwrap();
+
+        let decoder = gif::DecodeOptions::new().read_info(std::fs::File::open(&path).unwrap()).unwrap();
+        let entries = decoder.global_palette().unwrap().len() / 3;
+        std::fs::remove_file(&path).unwrap();
+        assert!(entries <= 16, "{} colors in the global table", entries);
+    }
+
+    /// Position of the first pixel of `rgb` in a decoded RGBA frame, scanning rows top-down
+    fn first_pixel(frame: &gif::Frame, rgb: [u8; 3]) -> Option<(usize, usize)> {
+        frame.buffer.chunks(4).position(|rgba| rgba[..3] == rgb)
+            .map(|i| (i % frame.width as usize, i / frame.width as usize))
+    }
+
+    #[test]
+    fn pad_grows_the_canvas_and_offsets_the_terminal() {
+        // A blue cell marks the terminal's top-left corner
+        let events = || vec![output_event(0.0, "\x1b[44m \x1b[0m")];
+        let plain = gif_frames("unpadded", events(), ConvertOptions::default());
+        let pad = crate::theme::Padding { left: 10, top: 10, right: 10, bottom: 10 };
+        let padded = gif_frames("padded", events(), ConvertOptions { pad: Some(pad), ..ConvertOptions::default() });
+
+        assert_eq!((padded[0].width, padded[0].height), (plain[0].width + 20, plain[0].height + 20));
+        assert_eq!(first_pixel(&plain[0], [0, 0, 128]), Some((0, 0)));
+        assert_eq!(first_pixel(&padded[0], [0, 0, 128]), Some((10, 10)));
+    }
+
+    #[test]
+    fn canvas_letterboxes_a_smaller_terminal_in_the_center() {
+        let events = || vec![output_event(0.0, "\x1b[44m \x1b[0m")];
+        let terminal = gif_frames("natural", events(), ConvertOptions::default());
+        let (width, height) = (terminal[0].width as usize, terminal[0].height as usize);
+        let boxed = gif_frames("letterboxed", events(), ConvertOptions { canvas: Some((width + 40, height + 30)), ..ConvertOptions::default() });
+
+        assert_eq!((boxed[0].width as usize, boxed[0].height as usize), (width + 40, height + 30));
+        assert_eq!(first_pixel(&boxed[0], [0, 0, 128]), Some((20, 15)));
+        // The borders are the theme background
+        assert_eq!(boxed[0].buffer[..3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn adaptive_fps_drops_idle_frames_but_keeps_the_duration() {
+        let events = || vec![output_event(0.0, "$ sleep 4"), output_event(4.0, "\r\n$ ")];
+        let duration = |frames: &[gif::Frame]| frames.iter().map(|frame| frame.delay as u32).sum::<u32>();
+        let every = gif_frames("every-frame", events(), ConvertOptions::default());
+        let adaptive = gif_frames("adaptive", events(), ConvertOptions { adaptive_fps: Some(1), ..ConvertOptions::default() });
+
+        assert!(adaptive.len() * 5 < every.len(), "{} of {} frames", adaptive.len(), every.len());
+        assert_eq!(duration(&adaptive), duration(&every));
+    }
+
+    #[test]
+    fn repeated_conversions_are_byte_identical() {
+        let convert = |run: usize| {
+            let path = temp_path(&format!("repeat-{}", run), "gif");
+            let events = vec![
+                output_event(0.0, "\x1b[31mred \x1b[38;5;208morange \x1b[38;5;45mteal"),
+                output_event(0.5, "\r\n\x1b[48;5;93m \x1b[48;5;160m \x1b[48;5;22m \x1b[0m"),
+            ];
+            let options = ConvertOptions { max_colors: Some(8), force_cpu: true, ..ConvertOptions::default() };
+            convert_events(events, metadata(20, 4), &path, options).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            bytes
+        };
+
+        assert_eq!(convert(0), convert(1));
+    }
+
+    #[test]
+    fn max_frames_errors_or_reduces_the_frame_rate() {
+        // Nine seconds at 10 fps is 91 frames
+        let events = || (0..10).map(|second| output_event(second as f64, &second.to_string())).collect::<Vec<_>>();
+        let capped = |on_overflow: &str| ConvertOptions {
+            max_frames: Some(20),
+            on_overflow: on_overflow.to_string(),
+            force_cpu: true,
+            ..ConvertOptions::default()
+        };
+
+        let path = temp_path("max-frames", "gif");
+        let error = convert_events(events(), metadata(20, 4), &path, capped("error")).unwrap_err();
+        assert!(error.to_string().contains("--max-frames 20"), "{}", error);
+        assert!(!path.exists());
+
+        // 2 fps is the fastest rate that fits: 19 frames
+        let frames = gif_frames("max-frames", events(), capped("reduce-fps"));
+        assert_eq!(frames.len(), 19);
+        assert!(frames[..18].iter().all(|frame| frame.delay == 50));
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn long_recordings_are_batch_rendered_in_chunks() {
+        // 301 frames at 10 fps: a full chunk and a partial one
+        let events = || (0..30).map(|second| output_event(second as f64, &format!("{second}\r\n"))).collect::<Vec<_>>();
+        // Batch-rendered frames carry no cursor, so leave it out of the CPU frames too
+        let options = || ConvertOptions { no_cursor: true, ..ConvertOptions::default() };
+        let path = temp_path("chunks", "gif");
+        convert_events(events(), metadata(20, 4), &path, options()).unwrap();
+        let batched = read_frames(&path);
+
+        let peak = PEAK_BATCH_FRAMES.with(|peak| peak.get());
+        if peak == 0 {
+            eprintln!("no GPU adapter; batch path not exercised");
+            return;
+        }
+        assert_eq!(peak, BATCH_CHUNK_FRAMES);
+
+        let cpu = gif_frames("chunks-cpu", events(), options());
+        assert_eq!(batched.len(), cpu.len());
+        for (index, (batched, cpu)) in batched.iter().zip(&cpu).enumerate() {
+            assert!(batched.buffer == cpu.buffer && batched.delay == cpu.delay, "frame {index} differs");
+        }
+    }
+
+    #[test]
+    fn theme_font_is_used_unless_one_is_given() {
+        let theme_path = temp_path("font-theme", "yaml");
+        std::fs::write(&theme_path, "name: ati\nfont: ATI_8x8\n").unwrap();
+        let theme = theme_path.to_string_lossy().into_owned();
+        let themed = gif_size("theme-font", ConvertOptions { theme: theme.clone(), ..ConvertOptions::default() });
+        let overridden = gif_size("cli-font", ConvertOptions { theme, font: Some("IBM_VGA8".to_string()), ..ConvertOptions::default() });
+        std::fs::remove_file(&theme_path).unwrap();
+
+        let cell = |name| {
+            let font = Font::load(Some(name));
+            (font.width() as u16, font.height() as u16)
+        };
+        let (ati, vga) = (cell("ATI_8x8"), cell("IBM_VGA8"));
+        assert_ne!(ati, vga);
+        assert_eq!(themed, (20 * ati.0, 4 * ati.1));
+        assert_eq!(overridden, (20 * vga.0, 4 * vga.1));
+    }
+
+    #[test]
+    fn theme_override_moves_the_terminal_down() {
+        let theme_path = temp_path("override-theme", "yaml");
+        std::fs::write(&theme_path, "name: plain\n").unwrap();
+        let theme = theme_path.to_string_lossy().into_owned();
+        // A blue cell marks the terminal's top-left corner
+        let events = || vec![output_event(0.0, "\x1b[44m \x1b[0m")];
+        let plain = gif_frames("override-plain", events(), ConvertOptions { theme: theme.clone(), ..ConvertOptions::default() });
+        let overrides = vec!["padding.top=12".to_string()];
+        let moved = gif_frames("override-top", events(), ConvertOptions { theme, theme_overrides: overrides, ..ConvertOptions::default() });
+        std::fs::remove_file(&theme_path).unwrap();
+
+        assert_eq!(first_pixel(&plain[0], [0, 0, 128]), Some((0, 0)));
+        assert_eq!(first_pixel(&moved[0], [0, 0, 128]), Some((0, 12)));
+        assert_eq!(moved[0].height, plain[0].height + 12);
+    }
+
+    #[test]
+    fn strict_layers_lists_where_a_missing_image_was_looked_for() {
+        let layer = format!("ttyvid-missing-layer-{}.png", std::process::id());
+        let theme_path = temp_path("missing-layer", "yaml");
+        std::fs::write(&theme_path, format!("name: missing\nlayers:\n- file: {}\n  mode: copy\n  depth: -1\n", layer)).unwrap();
+        let options = |strict_layers| ConvertOptions {
+            theme: theme_path.to_string_lossy().into_owned(),
+            strict_layers,
+            force_cpu: true,
+            ..ConvertOptions::default()
+        };
+        let path = temp_path("missing-layer", "gif");
+
+        // Skipped with a warning by default
+        convert_events(vec![output_event(0.0, "hi")], metadata(20, 4), &path, options(false)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let error = convert_events(vec![output_event(0.0, "hi")], metadata(20, 4), &path, options(true)).unwrap_err();
+        std::fs::remove_file(&theme_path).unwrap();
+        let message = format!("{:#}", error);
+        assert!(message.contains("--strict-layers"), "{}", message);
+        let searched = message.lines().filter(|line| line.trim_start().ends_with(&layer)).count();
+        assert!(searched >= 2, "{}", message);
+    }
+
+    #[test]
+    fn out_of_range_fps_is_clamped_with_a_warning() {
+        let (frame_rate, warning) = clamp_fps(240);
+        assert_eq!(frame_rate, 100);
+        assert!(warning.unwrap().contains("using 100 fps"));
+        assert_eq!(clamp_fps(0).0, 1);
+        assert_eq!(clamp_fps(30), (30, None));
+
+        let frames = gif_frames("fps-240", vec![output_event(0.0, "a"), output_event(0.05, "b")], ConvertOptions { fps: 240, ..ConvertOptions::default() });
+        assert_eq!(frames[0].delay, 1);
+    }
+
+    #[test]
+    fn title_draws_over_overlays_below_its_depth() {
+        use base64::Engine;
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let png = base64::engine::general_purpose::STANDARD.encode(&png);
+
+        // A red overlay at depth 1 covers the whole canvas, title included
+        let title_is_visible = |title_depth: i32| {
+            let theme = format!(
+                "name: titled\n\
+                 title:\n  foreground: 12\n  background: 0\n  x: 4\n  y: 6\n  depth: {}\n\
+                 layers:\n\
+                 - depth: 1\n  mode: tile\n  data: {}\n",
+                title_depth, png
+            );
+            let name = format!("title-depth-{}", title_depth);
+            let theme_path = temp_path(&name, "yaml");
+            std::fs::write(&theme_path, theme).unwrap();
+            let options = ConvertOptions {
+                theme: theme_path.to_string_lossy().into_owned(),
+                title: Some("Title".to_string()),
+                ..ConvertOptions::default()
+            };
+            let frames = gif_frames(&name, vec![output_event(0.0, "hi")], options);
+            std::fs::remove_file(&theme_path).unwrap();
+            first_pixel(&frames[0], [0, 0, 255]).is_some()
+        };
+
+        assert!(!title_is_visible(0));
+        assert!(title_is_visible(2));
+    }
+
+    /// Screen rows after replaying `output` under the emulation settings for a recording made with `term`
+    fn replay_with_term(term: &str, args: &Args, output: &str) -> Vec<String> {
+        use crate::input::{AsciicastReader, InputSource};
+        // Tests run in parallel and may replay the same TERM
+        static REPLAYS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let replay = REPLAYS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let cast = temp_path(&format!("term-{}", replay), "cast");
+        std::fs::write(&cast, format!("{{\"version\": 2, \"width\": 6, \"height\": 2, \"env\": {{\"TERM\": \"{}\", \"SHELL\": null}}}}\n", term)).unwrap();
+        let meta = AsciicastReader::new(&cast).unwrap().metadata();
+        std::fs::remove_file(&cast).unwrap();
+        assert_eq!(meta.env.get("TERM").map(String::as_str), Some(term));
+        assert!(!meta.env.contains_key("SHELL"));
+
+        let mut terminal = TerminalEmulator::new(meta.width, meta.height, true, 7, 0);
+        configure_emulation(&mut terminal, args, &meta);
+        terminal.feed_bytes(output.as_bytes());
+        (0..2).map(|y| crate::terminal::row_text(terminal.grid().row(y))).collect()
+    }
+
+    #[test]
+    fn recorded_term_picks_the_line_feed_behavior() {
+        // The Linux console also returns to column 0 on a bare LF
+        assert_eq!(replay_with_term("linux", &Args::default(), "ab\ncd"), ["ab", "cd"]);
+        assert_eq!(replay_with_term("xterm-256color", &Args::default(), "ab\ncd"), ["ab", "  cd"]);
+    }
+
+    #[test]
+    fn term_mode_overrides_the_recorded_term() {
+        let mode = |mode: &str| Args { term_mode: Some(mode.to_string()), ..Args::default() };
+        assert_eq!(replay_with_term("xterm", &mode("linux"), "ab\ncd"), ["ab", "cd"]);
+        assert_eq!(replay_with_term("linux", &mode("xterm"), "ab\ncd"), ["ab", "  cd"]);
+    }
+
+    #[test]
+    fn recorded_title_is_drawn_unless_no_title() {
+        let theme_path = temp_path("recorded-title", "yaml");
+        std::fs::write(&theme_path, "name: titled\ntitle:\n  foreground: 12\n  background: 0\n  x: 4\n  y: 6\n").unwrap();
+        let has_title = |name: &str, no_title: bool| {
+            let path = temp_path(name, "gif");
+            let meta = Metadata { title: Some("Recorded".to_string()), ..metadata(20, 4) };
+            let options = ConvertOptions {
+                theme: theme_path.to_string_lossy().into_owned(),
+                no_title,
+                force_cpu: true,
+                ..ConvertOptions::default()
+            };
+            convert_events(vec![output_event(0.0, "hi")], meta, &path, options).unwrap();
+            first_pixel(&read_frames(&path)[0], [0, 0, 255]).is_some()
+        };
+
+        let (titled, untitled) = (has_title("recorded-title", false), has_title("no-title", true));
+        std::fs::remove_file(&theme_path).unwrap();
+        assert!(titled);
+        assert!(!untitled);
+    }
+
+    #[test]
+    fn palette_file_replaces_every_color() {
+        // Index i becomes the gray 255 - i, so each cell's index can be read back from its pixels
+        let path = temp_path("grays", "hex");
+        let grays: String = (0..256).map(|i| format!("#{0:02x}{0:02x}{0:02x}\n", 255 - i)).collect();
+        std::fs::write(&path, grays).unwrap();
+
+        let events = vec![output_event(0.0, "\x1b[31mred \x1b[38;5;208morange \x1b[44mblue")];
+        let options = ConvertOptions { palette_file: Some(path.clone()), ..ConvertOptions::default() };
+        let frames = gif_frames("palette-file", events, options);
+        std::fs::remove_file(&path).unwrap();
+
+        let last = frames.last().unwrap();
+        assert!(last.buffer.chunks(4).all(|rgba| rgba[0] == rgba[1] && rgba[1] == rgba[2]));
+        for index in [1, 208, 4] {
+            assert!(first_pixel(last, [255 - index; 3]).is_some(), "no pixels drawn with color {}", index);
+        }
+    }
+
+    #[test]
+    fn interlace_flags_every_frame_and_keeps_the_image() {
+        let events = || vec![output_event(0.0, "hello\r\n"), output_event(0.2, "\x1b[32mworld\r\nagain")];
+        let path = temp_path("interlace", "gif");
+        let options = ConvertOptions { interlace: true, force_cpu: true, ..ConvertOptions::default() };
+        convert_events(events(), metadata(20, 4), &path, options).unwrap();
+
+        // The decoder clears the flag once it has deinterlaced a frame, so read the descriptors directly
+        let mut decoder = gif::DecodeOptions::new().read_info(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut descriptors = 0;
+        while let Some(frame) = decoder.next_frame_info().unwrap() {
+            assert!(frame.interlaced);
+            let mut pixels = vec![0; decoder.buffer_size()];
+            decoder.read_into_buffer(&mut pixels).unwrap();
+            descriptors += 1;
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let plain = gif_frames("progressive", events(), ConvertOptions::default());
+        let interlaced = gif_frames("interlaced", events(), ConvertOptions { interlace: true, ..ConvertOptions::default() });
+        assert_eq!(descriptors, interlaced.len());
+        assert_eq!(plain.len(), interlaced.len());
+        for (a, b) in plain.iter().zip(&interlaced) {
+            assert_eq!((a.left, a.top, a.width, a.height), (b.left, b.top, b.width, b.height));
+            assert!(a.buffer == b.buffer);
+        }
+    }
+
+    #[test]
+    fn later_frames_are_sub_rectangles_of_the_changed_region() {
+        let mut events = vec![output_event(0.0, "count 0")];
+        for n in 1..5 {
+            events.push(output_event(n as f64 * 0.2, &format!("\x08{}", n)));
+        }
+        let frames = gif_frames("partial", events, ConvertOptions { no_cursor: true, ..ConvertOptions::default() });
+        let (width, height) = (frames[0].width, frames[0].height);
+        assert_eq!((frames[0].left, frames[0].top), (0, 0));
+
+        assert!(frames.len() > 1);
+        for frame in &frames[1..] {
+            assert_eq!(frame.dispose, gif::DisposalMethod::Keep);
+            assert!(frame.width < width && frame.height < height, "{}x{} frame on a {}x{} canvas", frame.width, frame.height, width, height);
+        }
+    }
+
+    /// Full-canvas RGBA images of each decoded frame, pasting each sub-image over the last
+    fn composited(frames: &[gif::Frame]) -> Vec<Vec<u8>> {
+        let width = frames[0].width as usize;
+        let mut canvas = frames[0].buffer.to_vec();
+        let mut images = Vec::new();
+        for frame in frames {
+            for (row, pixels) in frame.buffer.chunks(frame.width as usize * 4).enumerate() {
+                let start = ((frame.top as usize + row) * width + frame.left as usize) * 4;
+                canvas[start..start + pixels.len()].copy_from_slice(pixels);
+            }
+            images.push(canvas.clone());
+        }
+        images
+    }
+
+    #[test]
+    fn gap_indicator_marks_the_frames_after_a_shortened_pause() {
+        let events = || vec![output_event(0.0, "hello"), output_event(5.0, " world"), output_event(5.8, "!")];
+        let run = |gap_indicator| {
+            let options = ConvertOptions { no_gaps: true, gap_indicator, no_cursor: true, ..ConvertOptions::default() };
+            gif_frames(&format!("gap-indicator-{}", gap_indicator), events(), options)
+        };
+        let (plain_frames, marked_frames) = (run(false), run(true));
+        let width = plain_frames[0].width as usize * 4;
+        let (plain, marked) = (composited(&plain_frames), composited(&marked_frames));
+        assert_eq!(plain.len(), marked.len());
+
+        // The pause resumes at 1s: the badge shows from there, in the right half, and is gone by the end
+        let mut shown_until = 0.0;
+        let badge = plain_frames.iter()
+            .position(|frame| {
+                shown_until += frame.delay as f64 / 100.0;
+                shown_until > 1.0
+            })
+            .unwrap();
+        assert!(plain[..badge] == marked[..badge]);
+        let changed: Vec<usize> = plain[badge].iter().zip(&marked[badge]).enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i % width / 4)
+            .collect();
+        assert!(!changed.is_empty());
+        assert!(changed.iter().all(|&x| x >= width / 8), "badge drawn left of center");
+        assert!(plain.last() == marked.last());
+    }
+
+    #[test]
+    fn blinking_text_is_shown_and_hidden_in_turn() {
+        let options = || ConvertOptions { no_cursor: true, ..ConvertOptions::default() };
+        let still = |name: &str, text: &str| {
+            composited(&gif_frames(name, vec![output_event(0.0, text)], options())).pop().unwrap()
+        };
+        let (shown, hidden) = (still("blink-shown", "ALERT"), still("blink-hidden", ""));
+
+        let events = vec![output_event(0.0, "\x1b[5mALERT\x1b[25m"), output_event(2.0, "\x1b[25m")];
+        let images = composited(&gif_frames("blink", events, options()));
+        assert!(images.iter().all(|image| *image == shown || *image == hidden));
+        assert!(images.contains(&shown) && images.contains(&hidden));
+    }
+
+    #[test]
+    fn oversized_canvas_is_refused_before_rendering() {
+        let path = temp_path("huge", "gif");
+        let options = ConvertOptions { columns: Some(1000), rows: Some(1000), max_canvas_mb: 64, force_cpu: true, ..ConvertOptions::default() };
+        let error = convert_events(vec![output_event(0.0, "hello")], metadata(20, 4), &path, options).unwrap_err();
+        assert!(error.to_string().contains("--max-canvas-mb"), "{}", error);
+        assert!(!path.exists(), "output written before the size check");
+    }
+
+    #[cfg(feature = "gpu")]
+    #[test]
+    fn force_cpu_renders_like_a_build_without_the_gpu() {
+        let events = || (0..30).map(|second| output_event(second as f64, &format!("\x1b[3{}m{second}\r\n", second % 8))).collect::<Vec<_>>();
+        let frames = gif_frames("force-cpu", events(), ConvertOptions::default());
+        assert_eq!(PEAK_BATCH_FRAMES.with(|peak| peak.get()), 0, "frames were batch-rendered on the GPU");
+
+        // What the rasterizer alone draws for the final screen, through the default theme's palette
+        let mut emulator = TerminalEmulator::new(20, 4, true, 7, 0);
+        for event in events() {
+            emulator.feed_bytes(&event.data);
+        }
+        let canvas = Rasterizer::with_font(Font::load(None)).render_grid_with_cursor(emulator.grid(), 0, 3);
+        let palette = Theme::load_by_name("default").unwrap().palette.map(|palette| Palette::from_theme(&palette)).unwrap();
+        let expected: Vec<u8> = canvas.data().iter()
+            .flat_map(|&index| {
+                let (r, g, b) = palette.get_rgb(index);
+                [r, g, b, 255]
+            })
+            .collect();
+        assert!(composited(&frames).last().unwrap() == &expected);
+    }
+
+    #[test]
+    fn repeated_redraws_are_coalesced_without_changing_the_frames() {
+        // A spinner that redraws each state three times, then plain text that must stay
+        let events = || {
+            let mut events: Vec<Event> = ["|", "/", "-", "\\"].iter().cycle().take(12)
+                .flat_map(|state| std::iter::repeat_n(format!("\r\x1b[0m{} working\x1b[K", state), 3))
+                .enumerate()
+                .map(|(i, data)| output_event(i as f64 * 0.1, &data))
+                .collect();
+            events.push(output_event(3.7, "ab"));
+            events.push(output_event(3.8, "ab"));
+            events
+        };
+
+        let mut coalesced = events();
+        assert_eq!(coalesce_repeated_events(&mut coalesced, 20), 24);
+        assert_eq!(coalesced.len(), events().len() - 24);
+        assert!(coalesced.iter().filter(|event| event.data == b"ab").count() == 2);
+
+        let plain = gif_frames("uncoalesced", events(), ConvertOptions::default());
+        let merged = gif_frames("coalesced", events(), ConvertOptions { coalesce_events: true, ..ConvertOptions::default() });
+        assert_eq!(plain.len(), merged.len());
+        assert!(composited(&plain) == composited(&merged));
+    }
+}
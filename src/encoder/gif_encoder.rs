@@ -12,11 +12,12 @@ pub struct GifEncoder {
     height: u16,
     previous_frame: Option<Vec<u8>>,
     transparent_index: Option<u8>,
+    interlace: bool,
     global_palette: Vec<u8>, // Store global palette RGB values
 }
 
 impl GifEncoder {
-    pub fn new(path: &Path, width: usize, height: usize, palette: &Palette, loop_count: u16, transparent_index: Option<u8>) -> Result<Self> {
+    pub fn new(path: &Path, width: usize, height: usize, palette: &Palette, loop_count: u16, transparent_index: Option<u8>, interlace: bool) -> Result<Self> {
         let file = File::create(path)?;
         let writer = BufWriter::new(file);
 
@@ -42,6 +43,7 @@ impl GifEncoder {
             height: height as u16,
             previous_frame: None,
             transparent_index,
+            interlace,
             global_palette,
         })
     }
@@ -95,6 +97,13 @@ impl GifEncoder {
         // Create local palette with only colors used in this frame
         let (local_palette, remapped_data) = self.create_local_palette(&frame_data);
 
+        // The encoder writes rows as given, so interlaced frames are reordered here
+        let remapped_data = if self.interlace {
+            interlace_rows(&remapped_data, width as usize, height as usize)
+        } else {
+            remapped_data
+        };
+
         // Create frame with remapped data
         let mut frame = Frame::from_indexed_pixels(
             width,
@@ -102,6 +111,7 @@ impl GifEncoder {
             remapped_data,
             self.transparent_index,
         );
+        frame.interlaced = self.interlace;
 
         frame.delay = delay_centiseconds;
         frame.left = left;
@@ -173,3 +183,15 @@ impl GifEncoder {
         )
     }
 }
+
+/// Reorder rows into GIF interlace order: every 8th row from 0, every 8th from 4,
+/// every 4th from 2, then every odd row
+fn interlace_rows(data: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut interlaced = Vec::with_capacity(data.len());
+    for (start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)] {
+        for y in (start..height).step_by(step) {
+            interlaced.extend_from_slice(&data[y * width..(y + 1) * width]);
+        }
+    }
+    interlaced
+}
@@ -0,0 +1,298 @@
+// Minimal asciicast (v2/v3) player: a small VT100 subset rendered into a <pre>
+(function () {
+  "use strict";
+
+  var lines = document.getElementById("cast").textContent.trim().split("\n");
+  var header = JSON.parse(lines[0]);
+  var cols = header.width || (header.term && header.term.cols) || 80;
+  var rows = header.height || (header.term && header.term.rows) || 24;
+
+  // v3 stores intervals since the previous event; v2 stores absolute times
+  var events = [];
+  var clock = 0;
+  for (var i = 1; i < lines.length; i++) {
+    if (!lines[i].trim() || lines[i][0] !== "[") continue;
+    var ev = JSON.parse(lines[i]);
+    clock = header.version === 3 ? clock + ev[0] : ev[0];
+    if (ev[1] === "o") events.push([clock, ev[2]]);
+    else if (ev[1] === "r") events.push([clock, null, ev[2]]);
+  }
+  var duration = events.length ? events[events.length - 1][0] : 0;
+
+  // xterm 256-color palette
+  var base = ["#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+              "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff"];
+  var palette = base.slice();
+  var steps = [0, 95, 135, 175, 215, 255];
+  function hex(r, g, b) {
+    return "#" + [r, g, b].map(function (v) { return ("0" + v.toString(16)).slice(-2); }).join("");
+  }
+  for (var c = 0; c < 216; c++) palette.push(hex(steps[Math.floor(c / 36)], steps[Math.floor(c / 6) % 6], steps[c % 6]));
+  for (var g = 0; g < 24; g++) palette.push(hex(8 + g * 10, 8 + g * 10, 8 + g * 10));
+
+  var term;
+
+  function blankCell() {
+    return { ch: " ", fg: term.fg, bg: term.bg, bold: false, ul: false, inv: false };
+  }
+  function blankRow() {
+    var row = [];
+    for (var x = 0; x < cols; x++) row.push(blankCell());
+    return row;
+  }
+  function reset() {
+    term = { x: 0, y: 0, fg: null, bg: null, bold: false, ul: false, inv: false, wrapNext: false,
+             top: 0, bottom: rows - 1, saved: [0, 0], cursor: true, autowrap: true, alt: null, pending: "" };
+    term.grid = [];
+    for (var y = 0; y < rows; y++) term.grid.push(blankRow());
+  }
+
+  function clampCursor() {
+    term.x = Math.max(0, Math.min(cols - 1, term.x));
+    term.y = Math.max(0, Math.min(rows - 1, term.y));
+    term.wrapNext = false;
+  }
+  function scrollUp(n) {
+    for (var k = 0; k < n; k++) {
+      term.grid.splice(term.top, 1);
+      term.grid.splice(term.bottom, 0, blankRow());
+    }
+  }
+  function scrollDown(n) {
+    for (var k = 0; k < n; k++) {
+      term.grid.splice(term.bottom, 1);
+      term.grid.splice(term.top, 0, blankRow());
+    }
+  }
+  function lineFeed() {
+    if (term.y === term.bottom) scrollUp(1);
+    else if (term.y < rows - 1) term.y++;
+  }
+  function put(ch) {
+    if (term.wrapNext && term.autowrap) {
+      term.x = 0;
+      lineFeed();
+    }
+    term.wrapNext = false;
+    term.grid[term.y][term.x] = { ch: ch, fg: term.fg, bg: term.bg, bold: term.bold, ul: term.ul, inv: term.inv };
+    if (term.x === cols - 1) term.wrapNext = true;
+    else term.x++;
+  }
+  function erase(y, from, to) {
+    for (var x = from; x < to; x++) term.grid[y][x] = blankCell();
+  }
+
+  function sgr(params) {
+    if (!params.length) params = [0];
+    for (var i = 0; i < params.length; i++) {
+      var p = params[i] || 0;
+      if (p === 0) { term.fg = term.bg = null; term.bold = term.ul = term.inv = false; }
+      else if (p === 1) term.bold = true;
+      else if (p === 4) term.ul = true;
+      else if (p === 7) term.inv = true;
+      else if (p === 22) term.bold = false;
+      else if (p === 24) term.ul = false;
+      else if (p === 27) term.inv = false;
+      else if (p >= 30 && p <= 37) term.fg = p - 30;
+      else if (p === 39) term.fg = null;
+      else if (p >= 40 && p <= 47) term.bg = p - 40;
+      else if (p === 49) term.bg = null;
+      else if (p >= 90 && p <= 97) term.fg = p - 82;
+      else if (p >= 100 && p <= 107) term.bg = p - 92;
+      else if (p === 38 || p === 48) {
+        var color = null;
+        if (params[i + 1] === 5) { color = params[i + 2]; i += 2; }
+        else if (params[i + 1] === 2) { color = hex(params[i + 2] || 0, params[i + 3] || 0, params[i + 4] || 0); i += 4; }
+        if (p === 38) term.fg = color; else term.bg = color;
+      }
+    }
+  }
+
+  function setMode(params, priv, on) {
+    params.forEach(function (p) {
+      if (!priv) return;
+      if (p === 25) term.cursor = on;
+      else if (p === 7) term.autowrap = on;
+      else if (p === 47 || p === 1047 || p === 1049) {
+        if (on && !term.alt) {
+          term.alt = { grid: term.grid, x: term.x, y: term.y };
+          term.grid = [];
+          for (var y = 0; y < rows; y++) term.grid.push(blankRow());
+        } else if (!on && term.alt) {
+          term.grid = term.alt.grid; term.x = term.alt.x; term.y = term.alt.y;
+          term.alt = null;
+        }
+      }
+    });
+  }
+
+  function csi(body, final) {
+    var priv = body[0] === "?";
+    var params = body.replace(/^[?>=]/, "").split(";").map(function (s) { return s === "" ? 0 : parseInt(s, 10); });
+    var n = Math.max(1, params[0] || 0);
+    switch (final) {
+      case "A": term.y -= n; clampCursor(); break;
+      case "B": case "e": term.y += n; clampCursor(); break;
+      case "C": case "a": term.x += n; clampCursor(); break;
+      case "D": term.x -= n; clampCursor(); break;
+      case "E": term.x = 0; term.y += n; clampCursor(); break;
+      case "F": term.x = 0; term.y -= n; clampCursor(); break;
+      case "G": case "`": term.x = n - 1; clampCursor(); break;
+      case "d": term.y = n - 1; clampCursor(); break;
+      case "H": case "f": term.y = Math.max(1, params[0] || 1) - 1; term.x = Math.max(1, params[1] || 1) - 1; clampCursor(); break;
+      case "J":
+        if (params[0] === 0) { erase(term.y, term.x, cols); for (var y = term.y + 1; y < rows; y++) erase(y, 0, cols); }
+        else if (params[0] === 1) { erase(term.y, 0, term.x + 1); for (var y2 = 0; y2 < term.y; y2++) erase(y2, 0, cols); }
+        else for (var y3 = 0; y3 < rows; y3++) erase(y3, 0, cols);
+        break;
+      case "K":
+        if (params[0] === 0) erase(term.y, term.x, cols);
+        else if (params[0] === 1) erase(term.y, 0, term.x + 1);
+        else erase(term.y, 0, cols);
+        break;
+      case "X": erase(term.y, term.x, Math.min(cols, term.x + n)); break;
+      case "P": term.grid[term.y].splice(term.x, n); while (term.grid[term.y].length < cols) term.grid[term.y].push(blankCell()); break;
+      case "@": for (var k = 0; k < n; k++) term.grid[term.y].splice(term.x, 0, blankCell()); term.grid[term.y].length = cols; break;
+      case "L": if (term.y >= term.top && term.y <= term.bottom) { var t = term.top; term.top = term.y; scrollDown(n); term.top = t; } break;
+      case "M": if (term.y >= term.top && term.y <= term.bottom) { var t2 = term.top; term.top = term.y; scrollUp(n); term.top = t2; } break;
+      case "S": scrollUp(n); break;
+      case "T": scrollDown(n); break;
+      case "r": term.top = Math.max(1, params[0] || 1) - 1; term.bottom = Math.min(rows, params[1] || rows) - 1; term.x = term.y = 0; break;
+      case "s": term.saved = [term.x, term.y]; break;
+      case "u": term.x = term.saved[0]; term.y = term.saved[1]; clampCursor(); break;
+      case "m": if (!priv) sgr(body === "" ? [] : params); break;
+      case "h": setMode(params, priv, true); break;
+      case "l": setMode(params, priv, false); break;
+    }
+  }
+
+  function feed(data) {
+    data = term.pending + data;
+    term.pending = "";
+    var i = 0;
+    while (i < data.length) {
+      var ch = data[i];
+      if (ch === "\x1b") {
+        var next = data[i + 1];
+        if (next === undefined) { term.pending = data.slice(i); return; }
+        if (next === "[") {
+          var m = /^\x1b\[([0-9;?>=]*)[ -\/]*([@-~])/.exec(data.slice(i));
+          if (!m) { if (data.length - i < 32) term.pending = data.slice(i); return; }
+          csi(m[1], m[2]);
+          i += m[0].length;
+          continue;
+        }
+        if (next === "]" || next === "P" || next === "_" || next === "^") {
+          var end = data.slice(i).search(/\x07|\x1b\\/);
+          if (end < 0) { term.pending = data.slice(i); return; }
+          i += end + (data[i + end] === "\x07" ? 1 : 2);
+          continue;
+        }
+        if (next === "(" || next === ")" || next === "#") { i += 3; continue; }
+        if (next === "7") term.saved = [term.x, term.y];
+        else if (next === "8") { term.x = term.saved[0]; term.y = term.saved[1]; clampCursor(); }
+        else if (next === "M") { if (term.y === term.top) scrollDown(1); else if (term.y > 0) term.y--; }
+        else if (next === "D") lineFeed();
+        else if (next === "E") { term.x = 0; lineFeed(); }
+        else if (next === "c") reset();
+        i += 2;
+        continue;
+      }
+      if (ch === "\r") { term.x = 0; term.wrapNext = false; }
+      else if (ch === "\n" || ch === "\x0b" || ch === "\x0c") { lineFeed(); term.wrapNext = false; }
+      else if (ch === "\b") { if (term.x > 0) term.x--; term.wrapNext = false; }
+      else if (ch === "\t") { term.x = Math.min(cols - 1, (Math.floor(term.x / 8) + 1) * 8); }
+      else if (ch >= " " && ch !== "\x7f") put(ch);
+      i++;
+    }
+  }
+
+  function escapeHtml(s) {
+    return s.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
+  }
+  function colorOf(value, fallback) {
+    if (value === null) return fallback;
+    return typeof value === "number" ? palette[value] : value;
+  }
+
+  var screen = document.getElementById("screen");
+  function draw() {
+    var html = "";
+    for (var y = 0; y < rows; y++) {
+      var run = "", style = null;
+      for (var x = 0; x < cols; x++) {
+        var cell = term.grid[y][x];
+        var fg = cell.bold && typeof cell.fg === "number" && cell.fg < 8 ? cell.fg + 8 : cell.fg;
+        var fgc = colorOf(fg, "var(--fg)"), bgc = colorOf(cell.bg, "transparent");
+        if (cell.inv) { var tmp = fgc; fgc = bgc === "transparent" ? "var(--bg)" : bgc; bgc = tmp; }
+        if (term.cursor && x === term.x && y === term.y) {
+          var t = fgc; fgc = bgc === "transparent" ? "var(--bg)" : bgc; bgc = t;
+        }
+        var s = "color:" + fgc + ";background:" + bgc + (cell.bold ? ";font-weight:bold" : "") + (cell.ul ? ";text-decoration:underline" : "");
+        if (s !== style) {
+          if (style !== null) html += "<span style=\"" + style + "\">" + escapeHtml(run) + "</span>";
+          style = s; run = "";
+        }
+        run += cell.ch;
+      }
+      html += "<span style=\"" + style + "\">" + escapeHtml(run) + "</span>\n";
+    }
+    screen.innerHTML = html;
+  }
+
+  // Playback state: events up to `applied` have been fed; `position` is in seconds
+  var applied = 0, position = 0, playing = false, lastTick = 0;
+  var seek = document.getElementById("seek");
+  var toggle = document.getElementById("toggle");
+  var speed = document.getElementById("speed");
+  var clockLabel = document.getElementById("clock");
+  seek.max = duration;
+
+  function applyUntil(time) {
+    if (time < position || applied === 0) {
+      reset();
+      applied = 0;
+    }
+    while (applied < events.length && events[applied][0] <= time) {
+      var ev = events[applied++];
+      if (ev[1] !== null) feed(ev[1]);
+    }
+    position = time;
+    seek.value = time;
+    clockLabel.textContent = time.toFixed(1) + "s / " + duration.toFixed(1) + "s";
+    draw();
+  }
+
+  function tick(now) {
+    if (!playing) return;
+    var elapsed = (now - lastTick) / 1000 * parseFloat(speed.value);
+    lastTick = now;
+    var next = position + elapsed;
+    if (next >= duration) {
+      applyUntil(duration);
+      playing = false;
+      toggle.textContent = "▶";
+      return;
+    }
+    applyUntil(next);
+    requestAnimationFrame(tick);
+  }
+
+  function play() {
+    if (position >= duration) applyUntil(0);
+    playing = true;
+    toggle.textContent = "❚❚";
+    lastTick = performance.now();
+    requestAnimationFrame(tick);
+  }
+
+  toggle.addEventListener("click", function () {
+    if (playing) { playing = false; toggle.textContent = "▶"; } else play();
+  });
+  seek.addEventListener("input", function () { applyUntil(parseFloat(seek.value)); });
+  screen.addEventListener("click", function () { toggle.click(); });
+
+  reset();
+  applyUntil(0);
+  play();
+})();
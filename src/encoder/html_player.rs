@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+const PLAYER_SCRIPT: &str = include_str!("html_player.js");
+
+/// Write a self-contained HTML page that embeds the asciicast and a small
+/// vanilla-JS player, so the recording can be replayed (and seeked) in any
+/// browser without network access
+pub fn generate_html_player(cast_file: &Path, html_file: &Path, title: &str) -> Result<()> {
+    let cast = std::fs::read_to_string(cast_file)
+        .with_context(|| format!("Failed to read recording: {}", cast_file.display()))?;
+    std::fs::write(html_file, html_player_page(&cast, title))
+        .with_context(|| format!("Failed to write HTML player: {}", html_file.display()))
+}
+
+/// Build the player page around the raw asciicast text
+pub fn html_player_page(cast: &str, title: &str) -> String {
+    // The cast sits inside a <script> element; "</" only occurs within JSON strings,
+    // where "<\/" is an equivalent escape that cannot close the element
+    let embedded_cast = cast.replace("</", "<\\/");
+    let title = title.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+:root {{ --fg: #e5e5e5; --bg: #000000; }}
+body {{ background: #1e1e1e; color: #ccc; font-family: sans-serif; margin: 2em; }}
+#player {{ display: inline-block; }}
+#screen {{ background: var(--bg); color: var(--fg); font: 14px/1.2 monospace; padding: 8px; margin: 0; cursor: pointer; }}
+#controls {{ display: flex; gap: 8px; align-items: center; margin-top: 6px; font-size: 13px; }}
+#seek {{ flex: 1; }}
+</style>
+</head>
+<body>
+<div id="player">
+<pre id="screen"></pre>
+<div id="controls">
+<button id="toggle">&#9654;</button>
+<input id="seek" type="range" min="0" step="0.01" value="0">
+<span id="clock"></span>
+<select id="speed"><option value="0.5">0.5x</option><option value="1" selected>1x</option><option value="2">2x</option><option value="4">4x</option></select>
+</div>
+</div>
+<script id="cast" type="application/x-asciicast">
+{cast}
+</script>
+<script>
+{script}
+</script>
+<p><small>Generated with <a href="{repository}">ttyvid</a> v{version}</small></p>
+</body>
+</html>
+"#,
+        title = title,
+        cast = embedded_cast.trim_end(),
+        script = PLAYER_SCRIPT,
+        repository = env!("CARGO_PKG_REPOSITORY"),
+        version = env!("CARGO_PKG_VERSION"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_embeds_the_cast_and_the_player() {
+        let cast = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.5, \"o\", \"</script>hi\"]\n";
+        let page = html_player_page(cast, "a <demo>");
+
+        assert!(page.contains(r#"[0.5, "o", "<\/script>hi"]"#));
+        assert!(!page.contains("</script>hi"));
+        assert!(page.contains(PLAYER_SCRIPT));
+        assert!(page.contains("<title>a &lt;demo&gt;</title>"));
+    }
+}
@@ -1,8 +1,16 @@
 mod gif_encoder;
+mod html_player;
+mod parallel_encoder;
+#[cfg(feature = "svg")]
+mod svg_encoder;
 #[cfg(feature = "webm")]
 mod webm_encoder;
 
 pub use gif_encoder::GifEncoder;
+pub use html_player::generate_html_player;
+pub use parallel_encoder::ParallelEncoder;
+#[cfg(feature = "svg")]
+pub use svg_encoder::SvgEncoder;
 #[cfg(feature = "webm")]
 pub use webm_encoder::WebmEncoder;
 
@@ -22,6 +30,8 @@ pub enum OutputFormat {
     Gif,
     #[cfg(feature = "webm")]
     Webm,
+    #[cfg(feature = "svg")]
+    Svg,
 }
 
 impl OutputFormat {
@@ -32,19 +42,70 @@ impl OutputFormat {
                 "gif" => Some(OutputFormat::Gif),
                 #[cfg(feature = "webm")]
                 "webm" | "ivf" => Some(OutputFormat::Webm),
+                #[cfg(feature = "svg")]
+                "svg" => Some(OutputFormat::Svg),
                 _ => None,
             })
     }
 
+    /// Names accepted by `--format` in this build
+    pub fn names() -> &'static [&'static str] {
+        &[
+            "gif",
+            #[cfg(feature = "webm")]
+            "webm",
+            #[cfg(feature = "svg")]
+            "svg",
+        ]
+    }
+
     pub fn extension(&self) -> &'static str {
         match self {
             OutputFormat::Gif => "gif",
             #[cfg(feature = "webm")]
             OutputFormat::Webm => "webm",
+            #[cfg(feature = "svg")]
+            OutputFormat::Svg => "svg",
+        }
+    }
+
+    /// True for formats that play every frame for the same time and ignore per-frame delays
+    pub fn is_constant_rate(&self) -> bool {
+        #[cfg(feature = "webm")]
+        if *self == OutputFormat::Webm {
+            return true;
         }
+        false
+    }
+
+    /// True for formats encoded from rendered frames (SVG is written from the text grid)
+    pub fn is_raster(&self) -> bool {
+        #[cfg(feature = "svg")]
+        if *self == OutputFormat::Svg {
+            return false;
+        }
+        true
     }
 }
 
+/// Length of the silent audio track added to video output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SilentAudio {
+    /// As long as the video
+    MatchVideo,
+    Seconds(f64),
+}
+
+/// YUV matrix used when WebM frames are converted from RGB
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorSpace {
+    /// ITU-R BT.601 (standard definition)
+    Bt601,
+    /// ITU-R BT.709 (HD; what most players assume)
+    #[default]
+    Bt709,
+}
+
 /// Wrapper enum for different encoder types
 pub enum EncoderWrapper {
     Gif(GifEncoder),
@@ -63,14 +124,21 @@ impl EncoderWrapper {
         fps: u32,
         quality: u8,
         transparent_index: Option<u8>,
+        silent_audio: Option<SilentAudio>,
+        color_space: ColorSpace,
+        interlace: bool,
     ) -> Result<Self> {
         match format {
             OutputFormat::Gif => {
-                Ok(EncoderWrapper::Gif(GifEncoder::new(path, width, height, palette, loop_count, transparent_index)?))
+                Ok(EncoderWrapper::Gif(GifEncoder::new(path, width, height, palette, loop_count, transparent_index, interlace)?))
             }
             #[cfg(feature = "webm")]
             OutputFormat::Webm => {
-                Ok(EncoderWrapper::Webm(WebmEncoder::new(path, width, height, palette, fps, quality)?))
+                Ok(EncoderWrapper::Webm(WebmEncoder::new(path, width, height, palette, fps, quality, silent_audio, color_space)?))
+            }
+            #[cfg(feature = "svg")]
+            OutputFormat::Svg => {
+                anyhow::bail!("SVG output is written from the terminal grid; use SvgEncoder")
             }
         }
     }
@@ -83,6 +151,15 @@ impl EncoderWrapper {
         }
     }
 
+    /// Ask for the next frame to be a keyframe (WebM only; GIF has no inter-frame coding)
+    pub fn request_keyframe(&mut self) {
+        match self {
+            EncoderWrapper::Gif(_) => {}
+            #[cfg(feature = "webm")]
+            EncoderWrapper::Webm(encoder) => encoder.request_keyframe(),
+        }
+    }
+
     pub fn finish(self) -> Result<()> {
         match self {
             EncoderWrapper::Gif(encoder) => encoder.finish(),
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use super::EncoderWrapper;
+use crate::renderer::Canvas;
+
+/// Frames buffered per encoder before the renderer blocks
+const QUEUE_DEPTH: usize = 8;
+
+enum Message {
+    Frame(Arc<Canvas>, u16),
+    Keyframe,
+}
+
+struct Worker {
+    sender: SyncSender<Message>,
+    handle: JoinHandle<Result<()>>,
+}
+
+/// Feeds the same frame stream to several encoders, each on its own thread,
+/// so one render pass can produce every requested output format
+pub struct ParallelEncoder {
+    workers: Vec<Worker>,
+}
+
+impl ParallelEncoder {
+    pub fn new(encoders: Vec<EncoderWrapper>) -> Self {
+        let workers = encoders
+            .into_iter()
+            .map(|mut encoder| {
+                let (sender, receiver) = sync_channel::<Message>(QUEUE_DEPTH);
+                let handle = std::thread::spawn(move || {
+                    for message in receiver {
+                        match message {
+                            Message::Frame(canvas, delay) => encoder.add_frame(&canvas, delay)?,
+                            Message::Keyframe => encoder.request_keyframe(),
+                        }
+                    }
+                    encoder.finish()
+                });
+                Worker { sender, handle }
+            })
+            .collect();
+
+        Self { workers }
+    }
+
+    pub fn add_frame(&mut self, canvas: Canvas, delay_centiseconds: u16) -> Result<()> {
+        let canvas = Arc::new(canvas);
+        self.send(|| Message::Frame(Arc::clone(&canvas), delay_centiseconds))
+    }
+
+    /// Ask every encoder for a keyframe on the next frame
+    pub fn request_keyframe(&mut self) -> Result<()> {
+        self.send(|| Message::Keyframe)
+    }
+
+    /// Close the frame stream and wait for every encoder to flush
+    pub fn finish(self) -> Result<()> {
+        let mut first_error = None;
+        for worker in self.workers {
+            drop(worker.sender);
+            if let Err(e) = join(worker.handle) {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    fn send(&mut self, message: impl Fn() -> Message) -> Result<()> {
+        // A closed channel means that encoder thread already stopped with an error
+        if let Some(pos) = self.workers.iter().position(|w| w.sender.send(message()).is_err()) {
+            let worker = self.workers.remove(pos);
+            join(worker.handle)?;
+            anyhow::bail!("Encoder thread exited early");
+        }
+        Ok(())
+    }
+}
+
+fn join(handle: JoinHandle<Result<()>>) -> Result<()> {
+    handle
+        .join()
+        .map_err(|_| anyhow::anyhow!("Encoder thread panicked"))?
+}
+
+#[cfg(all(test, feature = "webm"))]
+mod tests {
+    use super::*;
+    use crate::encoder::{ColorSpace, OutputFormat};
+    use crate::renderer::Palette;
+
+    #[test]
+    fn every_encoder_gets_every_frame() {
+        let palette = Palette::default();
+        let path = |ext| std::env::temp_dir().join(format!("ttyvid-parallel-{}.{}", std::process::id(), ext));
+        let (gif_path, webm_path) = (path("gif"), path("webm"));
+        let encoders = [(&gif_path, OutputFormat::Gif), (&webm_path, OutputFormat::Webm)]
+            .into_iter()
+            .map(|(path, format)| {
+                EncoderWrapper::new(path, 32, 16, &palette, format, 0, 10, 30, None, None, ColorSpace::default(), false).unwrap()
+            })
+            .collect();
+
+        let mut encoder = ParallelEncoder::new(encoders);
+        for color in 1..=6 {
+            let mut canvas = Canvas::new(32, 16, &palette);
+            canvas.fill(color);
+            encoder.add_frame(canvas, 10).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(std::fs::File::open(&gif_path).unwrap()).unwrap();
+        let mut gif_frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            gif_frames += 1;
+        }
+        let webm_frames = crate::encoder::webm_encoder::tests::simple_blocks(&std::fs::read(&webm_path).unwrap()).len();
+        std::fs::remove_file(&gif_path).unwrap();
+        std::fs::remove_file(&webm_path).unwrap();
+
+        assert_eq!(gif_frames, 6);
+        assert_eq!(webm_frames, gif_frames);
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use crate::renderer::Palette;
+use crate::terminal::{Cell, CellFlags, Grid};
+
+/// Character cell width as a fraction of the font size (typical monospace advance)
+const CELL_WIDTH_RATIO: f64 = 0.6;
+/// Line height as a fraction of the font size
+const LINE_HEIGHT_RATIO: f64 = 1.2;
+/// Baseline offset within a line, as a fraction of the font size
+const BASELINE_RATIO: f64 = 0.9;
+
+/// Animated SVG writer: every distinct screen becomes a `<g>` of `<text>` runs,
+/// shown in turn by CSS visibility keyframes, so text stays crisp at any zoom.
+///
+/// Unlike the raster encoders this works from terminal grids rather than
+/// rendered canvases, so theme layers, titles and effects are not included.
+pub struct SvgEncoder {
+    path: PathBuf,
+    palette: Palette,
+    columns: usize,
+    rows: usize,
+    font_size: f64,
+    background: u8,
+    loop_count: u16,
+    /// Write concealed (SGR 8) text instead of leaving it out
+    reveal_concealed: bool,
+    /// Markup of each distinct frame and how long it stays up (centiseconds)
+    frames: Vec<(String, u32)>,
+}
+
+impl SvgEncoder {
+    pub fn new(
+        path: &Path,
+        columns: usize,
+        rows: usize,
+        palette: &Palette,
+        background: u8,
+        font_size: usize,
+        loop_count: u16,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            palette: palette.clone(),
+            columns,
+            rows,
+            font_size: font_size.max(1) as f64,
+            background,
+            loop_count,
+            reveal_concealed: false,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn set_reveal_concealed(&mut self, reveal: bool) {
+        self.reveal_concealed = reveal;
+    }
+
+    /// Add a screen; one identical to the previous frame only extends its duration
+    pub fn add_frame(&mut self, grid: &Grid, delay_centiseconds: u16) {
+        let markup = self.frame_markup(grid);
+        match self.frames.last_mut() {
+            Some((previous, duration)) if *previous == markup => *duration += delay_centiseconds as u32,
+            _ => self.frames.push((markup, delay_centiseconds as u32)),
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        std::fs::write(&self.path, self.document())
+            .with_context(|| format!("Failed to write SVG: {}", self.path.display()))
+    }
+
+    fn cell_width(&self) -> f64 {
+        self.font_size * CELL_WIDTH_RATIO
+    }
+
+    fn line_height(&self) -> f64 {
+        self.font_size * LINE_HEIGHT_RATIO
+    }
+
+    fn color(&self, index: u8) -> String {
+        let (r, g, b) = self.palette.get_rgb(index);
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    fn document(&self) -> String {
+        let width = self.columns as f64 * self.cell_width();
+        let height = self.rows as f64 * self.line_height();
+        let total: u32 = self.frames.iter().map(|(_, duration)| duration).sum::<u32>().max(1);
+        let iterations = if self.loop_count == 0 { "infinite".to_string() } else { self.loop_count.to_string() };
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.1}" height="{h:.1}" viewBox="0 0 {w:.1} {h:.1}" font-family="monospace" font-size="{fs}">"#,
+            w = width,
+            h = height,
+            fs = self.font_size,
+        );
+        let _ = writeln!(svg, "<style>");
+        let _ = writeln!(
+            svg,
+            ".f{{visibility:hidden;animation-duration:{:.2}s;animation-iteration-count:{};animation-timing-function:steps(1,end);animation-fill-mode:forwards}}",
+            total as f64 / 100.0,
+            iterations,
+        );
+        let _ = writeln!(svg, "text{{white-space:pre}}");
+
+        // Each frame is visible from its start until the next frame starts; the last
+        // one stays up through 100% so a finished (non-looping) animation keeps it
+        let mut start = 0u32;
+        for (index, (_, duration)) in self.frames.iter().enumerate() {
+            let from = start as f64 * 100.0 / total as f64;
+            let to = (start + duration) as f64 * 100.0 / total as f64;
+            let hidden_after = if index + 1 == self.frames.len() {
+                String::new()
+            } else {
+                format!("{:.3}%{{visibility:hidden}}", to)
+            };
+            let _ = writeln!(
+                svg,
+                "@keyframes k{i}{{0%{{visibility:hidden}}{from:.3}%{{visibility:visible}}{hidden}100%{{visibility:{last}}}}}.f{i}{{animation-name:k{i}}}",
+                i = index,
+                from = from,
+                hidden = hidden_after,
+                last = if index + 1 == self.frames.len() { "visible" } else { "hidden" },
+            );
+            start += duration;
+        }
+        let _ = writeln!(svg, "</style>");
+        let _ = writeln!(
+            svg,
+            r#"<rect width="100%" height="100%" fill="{}"/>"#,
+            self.color(self.background)
+        );
+
+        for (index, (markup, _)) in self.frames.iter().enumerate() {
+            let _ = writeln!(svg, r#"<g class="f f{}">"#, index);
+            svg.push_str(markup);
+            let _ = writeln!(svg, "</g>");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Background rects and text runs for one screen
+    fn frame_markup(&self, grid: &Grid) -> String {
+        let cell_width = self.cell_width();
+        let line_height = self.line_height();
+        let mut markup = String::new();
+
+        for y in 0..grid.height().min(self.rows) {
+            let row = &grid.row(y)[..grid.width().min(self.columns)];
+            let top = y as f64 * line_height;
+
+            // Cell colors after reverse video
+            let colors: Vec<(u8, u8)> = row.iter().map(|cell| {
+                if cell.flags.contains(CellFlags::REVERSE) {
+                    (cell.bg_color, cell.fg_color)
+                } else {
+                    (cell.fg_color, cell.bg_color)
+                }
+            }).collect();
+
+            // Backgrounds: one rect per run of the same non-default color
+            let mut x = 0;
+            while x < row.len() {
+                let bg = colors[x].1;
+                let run = colors[x..].iter().take_while(|(_, other)| *other == bg).count();
+                if bg != self.background {
+                    let _ = writeln!(
+                        markup,
+                        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+                        x as f64 * cell_width,
+                        top,
+                        run as f64 * cell_width,
+                        line_height,
+                        self.color(bg),
+                    );
+                }
+                x += run;
+            }
+
+            // Text: one span per run of identically styled, non-blank characters
+            let mut spans = String::new();
+            let mut x = 0;
+            // Concealed text is left out of the markup entirely, not just colored like the background
+            let blank = |cell: &Cell| {
+                cell.character == ' ' || (cell.flags.contains(CellFlags::CONCEAL) && !self.reveal_concealed)
+            };
+            while x < row.len() {
+                if blank(&row[x]) {
+                    x += 1;
+                    continue;
+                }
+                let style = (colors[x].0, row[x].flags);
+                let run = (x..row.len())
+                    .take_while(|&i| !blank(&row[i]) && (colors[i].0, row[i].flags) == style)
+                    .count();
+                let _ = write!(spans, r#"<tspan x="{:.1}" fill="{}""#, x as f64 * cell_width, self.color(style.0));
+                if style.1.contains(CellFlags::BOLD) {
+                    spans.push_str(r#" font-weight="bold""#);
+                }
+                if style.1.contains(CellFlags::ITALIC) {
+                    spans.push_str(r#" font-style="italic""#);
+                }
+                let decorations: Vec<&str> = [(CellFlags::UNDERLINE, "underline"), (CellFlags::OVERLINE, "overline")]
+                    .iter()
+                    .filter(|(flag, _)| style.1.contains(*flag))
+                    .map(|&(_, name)| name)
+                    .collect();
+                if !decorations.is_empty() {
+                    let _ = write!(spans, r#" text-decoration="{}""#, decorations.join(" "));
+                }
+                spans.push('>');
+                for cell in &row[x..x + run] {
+                    push_escaped(&mut spans, cell.character);
+                }
+                spans.push_str("</tspan>");
+                x += run;
+            }
+            if !spans.is_empty() {
+                let _ = writeln!(
+                    markup,
+                    r#"<text y="{:.1}">{}</text>"#,
+                    top + self.font_size * BASELINE_RATIO,
+                    spans
+                );
+            }
+        }
+
+        markup
+    }
+}
+
+fn push_escaped(out: &mut String, character: char) {
+    match character {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        // Control characters are not valid XML
+        c if (c as u32) < 0x20 => out.push(' '),
+        c => out.push(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::TerminalEmulator;
+
+    #[test]
+    fn each_distinct_screen_is_a_frame_group() {
+        let path = std::env::temp_dir().join(format!("ttyvid-svg-{}.svg", std::process::id()));
+        let mut terminal = TerminalEmulator::new(10, 2, true, 7, 0);
+        let mut encoder = SvgEncoder::new(&path, 10, 2, &Palette::default(), 0, 16, 0);
+
+        terminal.feed_bytes(b"hi");
+        encoder.add_frame(terminal.grid(), 10);
+        // An unchanged screen only extends the previous frame
+        encoder.add_frame(terminal.grid(), 10);
+        terminal.feed_bytes(b"\r\n<a&b>");
+        encoder.add_frame(terminal.grid(), 10);
+        encoder.finish().unwrap();
+
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(svg.matches(r#"<g class="f "#).count(), 2);
+        assert!(svg.contains(">hi</tspan>"));
+        assert!(svg.contains(">&lt;a&amp;b&gt;</tspan>"));
+        assert!(svg.contains("animation-duration:0.30s"));
+    }
+}
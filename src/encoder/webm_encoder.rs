@@ -5,6 +5,14 @@ use std::path::Path;
 use rav1e::prelude::*;
 
 use crate::renderer::{Canvas, Palette};
+use super::{ColorSpace, SilentAudio};
+
+const VIDEO_TRACK: u8 = 1;
+const AUDIO_TRACK: u8 = 2;
+
+/// One 20ms mono Opus (CELT) frame that decodes to silence
+const OPUS_SILENT_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+const OPUS_FRAME_MS: u64 = 20;
 
 pub struct WebmEncoder {
     writer: BufWriter<File>,
@@ -18,10 +26,23 @@ pub struct WebmEncoder {
     cluster_max_duration: u64, // Maximum duration for a cluster (in ms)
     duration_ms: u64,
     segment_data_start: u64, // Position where segment data starts (for seeking back)
+    force_keyframe: bool,    // Encode the next frame as a keyframe (set by request_keyframe)
+    silent_audio: Option<SilentAudio>,
+    audio_timestamp: u64,    // Start of the next silent audio frame (in ms)
+    color_space: ColorSpace,
 }
 
 impl WebmEncoder {
-    pub fn new(path: &Path, width: usize, height: usize, palette: &Palette, fps: u32, quality: u8) -> Result<Self> {
+    pub fn new(
+        path: &Path,
+        width: usize,
+        height: usize,
+        palette: &Palette,
+        fps: u32,
+        quality: u8,
+        silent_audio: Option<SilentAudio>,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
         let file = File::create(path)?;
         let writer = BufWriter::new(file);
 
@@ -56,6 +77,7 @@ impl WebmEncoder {
                 speed_settings: SpeedSettings::from_preset(speed),
                 quantizer,
                 min_quantizer,
+                color_description: Some(color_description(color_space)),
                 ..Default::default()
             });
 
@@ -79,6 +101,10 @@ impl WebmEncoder {
             cluster_max_duration: 5000, // 5 seconds per cluster
             duration_ms: 0,
             segment_data_start: 0,
+            force_keyframe: false,
+            silent_audio,
+            audio_timestamp: 0,
+            color_space,
         };
 
         temp_encoder.write_webm_header()?;
@@ -87,6 +113,11 @@ impl WebmEncoder {
         Ok(temp_encoder)
     }
 
+    /// Hint that the next frame starts a new scene (e.g. a screen clear) and should be a keyframe
+    pub fn request_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
     pub fn add_frame(&mut self, canvas: &Canvas, _delay_centiseconds: u16) -> Result<()> {
         let rgb_data = self.canvas_to_rgb(canvas);
 
@@ -107,9 +138,16 @@ impl WebmEncoder {
 
         self.rgb_to_yuv(&rgb_data, &mut frame);
 
+        let frame_type_override = if std::mem::take(&mut self.force_keyframe) {
+            FrameTypeOverride::Key
+        } else {
+            FrameTypeOverride::No
+        };
+
         // Try to send frame - handle LimitReached by draining packets first
         loop {
-            match self.encoder.send_frame(frame.clone()) {
+            let params = FrameParameters { frame_type_override, ..Default::default() };
+            match self.encoder.send_frame((frame.clone(), params)) {
                 Ok(_) => break,
                 Err(EncoderStatus::EnoughData) => {
                     // Encoder buffer full, drain some packets before retrying
@@ -137,6 +175,9 @@ impl WebmEncoder {
                     // Update duration
                     self.duration_ms = pts.max(self.duration_ms);
 
+                    // Keep audio interleaved ahead of the video it accompanies
+                    self.write_silence_until(pts)?;
+
                     // Start new cluster if needed (on keyframe and duration exceeded)
                     if is_key && pts > self.cluster_timestamp + self.cluster_max_duration {
                         self.end_cluster()?;
@@ -144,7 +185,7 @@ impl WebmEncoder {
                     }
 
                     // Write frame immediately
-                    self.write_simple_block(&packet.data, pts, is_key)?;
+                    self.write_simple_block(VIDEO_TRACK, &packet.data, pts, is_key)?;
                 }
                 Err(EncoderStatus::Encoded) => break,
                 Err(EncoderStatus::LimitReached) => break,
@@ -173,10 +214,13 @@ impl WebmEncoder {
                     // Update duration
                     self.duration_ms = pts.max(self.duration_ms);
 
+                    self.write_silence_until(pts)?;
+
                     // Write frame immediately
-                    self.write_simple_block(&packet.data, pts, is_key)?;
+                    self.write_simple_block(VIDEO_TRACK, &packet.data, pts, is_key)?;
                 }
-                Err(EncoderStatus::Encoded) => break,
+                // A frame was encoded without a packet ready yet; keep going until every frame is out
+                Err(EncoderStatus::Encoded) => continue,
                 Err(EncoderStatus::LimitReached) => break,
                 Err(e) => {
                     return Err(anyhow::anyhow!("Encoder finish error: {:?}", e));
@@ -184,6 +228,18 @@ impl WebmEncoder {
             }
         }
 
+        // Pad the audio out to its full length (the last video frame lasts one frame interval)
+        match self.silent_audio {
+            Some(SilentAudio::MatchVideo) => {
+                let video_end = self.duration_ms + 1000 / self.fps.max(1) as u64;
+                self.write_silence_until(video_end)?;
+            }
+            Some(SilentAudio::Seconds(seconds)) => {
+                self.write_silence_until((seconds * 1000.0).round() as u64)?;
+            }
+            None => {}
+        }
+
         // End final cluster
         self.end_cluster()?;
 
@@ -239,11 +295,43 @@ impl WebmEncoder {
                     let mut video_data = Vec::new();
                     self.write_ebml_uint_to_vec(&mut video_data, 0xB0, self.width as u64)?; // PixelWidth
                     self.write_ebml_uint_to_vec(&mut video_data, 0xBA, self.height as u64)?; // PixelHeight
+                    self.write_ebml_element_to_vec(&mut video_data, 0x55B0, &{
+                        let mut colour_data = Vec::new();
+                        let code = matroska_color_code(self.color_space);
+                        self.write_ebml_uint_to_vec(&mut colour_data, 0x55B1, code)?; // MatrixCoefficients
+                        self.write_ebml_uint_to_vec(&mut colour_data, 0x55B9, 1)?; // Range (broadcast)
+                        self.write_ebml_uint_to_vec(&mut colour_data, 0x55BA, code)?; // TransferCharacteristics
+                        self.write_ebml_uint_to_vec(&mut colour_data, 0x55BB, code)?; // Primaries
+                        colour_data
+                    })?; // Colour
                     video_data
                 })?;
 
                 track_data
             })?;
+
+            if self.silent_audio.is_some() {
+                self.write_ebml_element_to_vec(&mut data, 0xAE, &{
+                    let mut track_data = Vec::new();
+                    self.write_ebml_uint_to_vec(&mut track_data, 0xD7, AUDIO_TRACK as u64)?; // TrackNumber
+                    self.write_ebml_uint_to_vec(&mut track_data, 0x73C5, 2)?; // TrackUID
+                    self.write_ebml_uint_to_vec(&mut track_data, 0x83, 2)?; // TrackType (audio)
+                    self.write_ebml_string_to_vec(&mut track_data, 0x86, "A_OPUS")?; // CodecID
+                    self.write_ebml_element_to_vec(&mut track_data, 0x63A2, &opus_head())?; // CodecPrivate
+                    self.write_ebml_uint_to_vec(&mut track_data, 0x56AA, 0)?; // CodecDelay (ns)
+                    self.write_ebml_uint_to_vec(&mut track_data, 0x56BB, 80_000_000)?; // SeekPreRoll (ns)
+
+                    // Audio settings
+                    self.write_ebml_element_to_vec(&mut track_data, 0xE1, &{
+                        let mut audio_data = Vec::new();
+                        self.write_ebml_float_to_vec(&mut audio_data, 0xB5, 48000.0)?; // SamplingFrequency
+                        self.write_ebml_uint_to_vec(&mut audio_data, 0x9F, 1)?; // Channels
+                        audio_data
+                    })?;
+
+                    track_data
+                })?;
+            }
             data
         })?;
 
@@ -271,11 +359,31 @@ impl WebmEncoder {
         Ok(())
     }
 
-    fn write_simple_block(&mut self, data: &[u8], timestamp: u64, is_keyframe: bool) -> Result<()> {
+    /// Write silent Opus frames from the current audio position up to `until` (ms)
+    fn write_silence_until(&mut self, until: u64) -> Result<()> {
+        let until = match self.silent_audio {
+            None => return Ok(()),
+            Some(SilentAudio::MatchVideo) => until,
+            Some(SilentAudio::Seconds(seconds)) => until.min((seconds * 1000.0).round() as u64),
+        };
+        while self.audio_timestamp < until {
+            // Block timestamps are 16-bit offsets from the cluster; audio running past
+            // the last video frame needs clusters of its own
+            if self.audio_timestamp - self.cluster_timestamp > i16::MAX as u64 {
+                self.end_cluster()?;
+                self.start_cluster(self.audio_timestamp)?;
+            }
+            self.write_simple_block(AUDIO_TRACK, &OPUS_SILENT_FRAME, self.audio_timestamp, true)?;
+            self.audio_timestamp += OPUS_FRAME_MS;
+        }
+        Ok(())
+    }
+
+    fn write_simple_block(&mut self, track: u8, data: &[u8], timestamp: u64, is_keyframe: bool) -> Result<()> {
         let mut block_data = Vec::new();
 
         // Track number (varint encoded)
-        block_data.push(0x81); // Track 1
+        block_data.push(0x80 | track);
 
         // Timestamp relative to cluster
         let relative_ts = (timestamp - self.cluster_timestamp) as i16;
@@ -472,6 +580,9 @@ impl WebmEncoder {
         let v_xorigin = frame.planes[2].cfg.xorigin;
         let v_yorigin = frame.planes[2].cfg.yorigin;
 
+        // Limited-range RGB to YUV rows of the selected matrix, scaled by 256
+        let [ky, ku, kv] = yuv_matrix(self.color_space);
+
         // Copy row by row to handle stride properly WITH origin offset
         for y in 0..self.height {
             let rgb_row_start = y * self.width * 3;
@@ -483,9 +594,7 @@ impl WebmEncoder {
                 let g = rgb[rgb_idx + 1] as i32;
                 let b = rgb[rgb_idx + 2] as i32;
 
-                // RGB to YUV conversion (ITU-R BT.601)
-                let y_val = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
-                frame.planes[0].data[y_row_start + x] = y_val.clamp(0, 255) as u8;
+                frame.planes[0].data[y_row_start + x] = yuv_component(ky, [r, g, b], 16);
             }
         }
 
@@ -504,12 +613,179 @@ impl WebmEncoder {
                 let g = rgb[rgb_idx + 1] as i32;
                 let b = rgb[rgb_idx + 2] as i32;
 
-                let u_val = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
-                let v_val = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+                frame.planes[1].data[uv_row_start_u + uv_x] = yuv_component(ku, [r, g, b], 128);
+                frame.planes[2].data[uv_row_start_v + uv_x] = yuv_component(kv, [r, g, b], 128);
+            }
+        }
+    }
+}
+
+/// Y, U and V rows of the limited-range RGB to YUV matrix, in 1/256ths
+fn yuv_matrix(color_space: ColorSpace) -> [[i32; 3]; 3] {
+    match color_space {
+        ColorSpace::Bt601 => [[66, 129, 25], [-38, -74, 112], [112, -94, -18]],
+        ColorSpace::Bt709 => [[47, 157, 16], [-26, -86, 112], [112, -102, -10]],
+    }
+}
+
+/// One of Y, U or V for an RGB pixel: a matrix row applied, rounded, plus the channel's offset
+fn yuv_component(row: [i32; 3], [r, g, b]: [i32; 3], offset: i32) -> u8 {
+    (((row[0] * r + row[1] * g + row[2] * b + 128) >> 8) + offset).clamp(0, 255) as u8
+}
 
-                frame.planes[1].data[uv_row_start_u + uv_x] = u_val.clamp(0, 255) as u8;
-                frame.planes[2].data[uv_row_start_v + uv_x] = v_val.clamp(0, 255) as u8;
+/// Color description signalled in the AV1 sequence header, so players decode
+/// with the same matrix the frames were converted with
+fn color_description(color_space: ColorSpace) -> ColorDescription {
+    match color_space {
+        ColorSpace::Bt601 => ColorDescription {
+            color_primaries: ColorPrimaries::BT601,
+            transfer_characteristics: TransferCharacteristics::BT601,
+            matrix_coefficients: MatrixCoefficients::BT601,
+        },
+        ColorSpace::Bt709 => ColorDescription {
+            color_primaries: ColorPrimaries::BT709,
+            transfer_characteristics: TransferCharacteristics::BT709,
+            matrix_coefficients: MatrixCoefficients::BT709,
+        },
+    }
+}
+
+/// Matroska Colour code (ISO/IEC 23091-4) for the matrix, primaries and transfer
+fn matroska_color_code(color_space: ColorSpace) -> u64 {
+    match color_space {
+        ColorSpace::Bt601 => 6,
+        ColorSpace::Bt709 => 1,
+    }
+}
+
+/// Opus identification header (RFC 7845), stored as the audio track's CodecPrivate
+fn opus_head() -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // Version
+    head.push(1); // Channel count
+    head.extend_from_slice(&0u16.to_le_bytes()); // Pre-skip
+    head.extend_from_slice(&48000u32.to_le_bytes()); // Input sample rate
+    head.extend_from_slice(&0i16.to_le_bytes()); // Output gain
+    head.push(0); // Channel mapping family
+    head
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Read an EBML variable-length integer, returning (value without the length marker, bytes used)
+    fn read_vint(bytes: &[u8]) -> (u64, usize) {
+        let len = bytes[0].leading_zeros() as usize + 1;
+        let mut value = (bytes[0] as u64) & (0xFF >> len);
+        for &byte in &bytes[1..len] {
+            value = (value << 8) | byte as u64;
+        }
+        (value, len)
+    }
+
+    /// Visit the id and payload of every element, stepping into the containers
+    /// that hold SimpleBlocks and track entries
+    fn walk(bytes: &[u8], mut visit: impl FnMut(u64, &[u8])) {
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let id_len = bytes[pos].leading_zeros() as usize + 1;
+            let id = bytes[pos..pos + id_len].iter().fold(0u64, |id, &byte| (id << 8) | byte as u64);
+            let (size, size_len) = read_vint(&bytes[pos + id_len..]);
+            let body = pos + id_len + size_len;
+            // Segment, Cluster, Tracks, TrackEntry (Segment and Cluster are written with unknown sizes)
+            if matches!(id, 0x18538067 | 0x1F43B675 | 0x1654AE6B | 0xAE) {
+                pos = body;
+                continue;
+            }
+            visit(id, &bytes[body..body + size as usize]);
+            pos = body + size as usize;
+        }
+    }
+
+    /// (track, timestamp in ms, keyframe) of every SimpleBlock in a file written by WebmEncoder
+    pub(crate) fn simple_blocks(bytes: &[u8]) -> Vec<(u8, u64, bool)> {
+        let mut blocks = Vec::new();
+        let mut cluster_timestamp = 0;
+        walk(bytes, |id, body| match id {
+            0xE7 => cluster_timestamp = body.iter().fold(0, |ts, &byte| (ts << 8) | byte as u64),
+            0xA3 => {
+                let relative = i16::from_be_bytes([body[1], body[2]]);
+                let timestamp = (cluster_timestamp as i64 + relative as i64) as u64;
+                blocks.push((body[0] & 0x7F, timestamp, body[3] & 0x80 != 0));
             }
+            _ => {}
+        });
+        blocks
+    }
+
+    /// TrackType of each track entry (1 video, 2 audio)
+    fn track_types(bytes: &[u8]) -> Vec<u8> {
+        let mut types = Vec::new();
+        walk(bytes, |id, body| {
+            if id == 0x83 {
+                types.push(body[0]);
+            }
+        });
+        types
+    }
+
+    fn encode(name: &str, frames: usize, keyframe_at: Option<usize>) -> Vec<u8> {
+        encode_with_audio(name, frames, keyframe_at, None)
+    }
+
+    fn encode_with_audio(name: &str, frames: usize, keyframe_at: Option<usize>, silent_audio: Option<SilentAudio>) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("ttyvid-{}-{}.webm", name, std::process::id()));
+        let palette = Palette::default();
+        let mut encoder = WebmEncoder::new(&path, 32, 16, &palette, 10, 30, silent_audio, ColorSpace::Bt709).unwrap();
+        let mut canvas = Canvas::new(32, 16, &palette);
+        canvas.fill(4);
+        for frame in 0..frames {
+            if keyframe_at == Some(frame) {
+                encoder.request_keyframe();
+            }
+            encoder.add_frame(&canvas, 10).unwrap();
         }
+        encoder.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn requested_keyframe_lands_on_its_frame() {
+        let keyframes = |bytes: &[u8]| -> Vec<u64> {
+            simple_blocks(bytes).into_iter().filter(|&(track, _, key)| track == VIDEO_TRACK && key).map(|(_, ts, _)| ts).collect()
+        };
+        let plain = encode("no-hint", 8, None);
+        assert_eq!(simple_blocks(&plain).len(), 8, "every frame is written");
+        assert_eq!(keyframes(&plain), vec![0]);
+        assert_eq!(keyframes(&encode("hint", 8, Some(5))), vec![0, 500]);
+    }
+
+    #[test]
+    fn silent_audio_adds_an_audio_track_as_long_as_the_video() {
+        assert_eq!(track_types(&encode("video-only", 10, None)), vec![1]);
+
+        let bytes = encode_with_audio("silent-audio", 10, None, Some(SilentAudio::MatchVideo));
+        assert_eq!(track_types(&bytes), vec![1, 2]);
+        let audio: Vec<u64> = simple_blocks(&bytes).into_iter()
+            .filter(|&(track, _, _)| track == AUDIO_TRACK)
+            .map(|(_, timestamp, _)| timestamp)
+            .collect();
+        // Ten frames at 10 fps: one second of 20ms Opus frames
+        assert_eq!(audio.len(), 50);
+        assert_eq!(audio.last(), Some(&980));
+    }
+
+    #[test]
+    fn red_converts_differently_under_each_matrix() {
+        let yuv = |color_space| {
+            let [ky, ku, kv] = yuv_matrix(color_space);
+            let red = [255, 0, 0];
+            [yuv_component(ky, red, 16), yuv_component(ku, red, 128), yuv_component(kv, red, 128)]
+        };
+        assert_eq!(yuv(ColorSpace::Bt601), [82, 90, 240]);
+        assert_eq!(yuv(ColorSpace::Bt709), [63, 102, 240]);
     }
 }
@@ -232,6 +232,22 @@ fn map_utf8_to_cp437(ch: char) -> u8 {
     }
 }
 
+/// Repeat every pixel of a glyph `height` rows tall into a `factor` x `factor` block
+fn scale_glyph<T: Copy>(glyph: &[T], height: usize, factor: usize) -> Vec<T> {
+    let width = glyph.len() / height.max(1);
+    let mut scaled = Vec::with_capacity(glyph.len() * factor * factor);
+    for row in glyph.chunks_exact(width.max(1)) {
+        let start = scaled.len();
+        for &pixel in row {
+            scaled.extend(std::iter::repeat_n(pixel, factor));
+        }
+        for _ in 1..factor {
+            scaled.extend_from_within(start..start + width * factor);
+        }
+    }
+    scaled
+}
+
 impl Font {
     /// Load a TrueType font from the system by name with smart fallbacks
     ///
@@ -402,6 +418,32 @@ impl Font {
             .collect()
     }
 
+    /// The font at `factor` times its size (--retina): bitmap glyphs repeat each pixel,
+    /// TrueType glyphs are rasterized again at the larger size
+    pub fn scaled(&self, factor: usize) -> Font {
+        let factor = factor.max(1);
+        match self {
+            Font::Bitmap { width, height, glyphs } => Font::Bitmap {
+                width: width * factor,
+                height: height * factor,
+                glyphs: glyphs.iter().map(|glyph| scale_glyph(glyph, *height, factor)).collect(),
+            },
+            Font::BitmapIntensity { width, height, glyphs } => Font::BitmapIntensity {
+                width: width * factor,
+                height: height * factor,
+                glyphs: glyphs.iter().map(|(&ch, glyph)| (ch, scale_glyph(glyph, *height, factor))).collect(),
+            },
+            Font::TrueType(ttf) => Font::TrueType(ttf.scaled(factor)),
+        }
+    }
+
+    /// Stretch glyph coverage contrast (TrueType only; bitmap fonts are already pixel-aligned)
+    pub fn set_glyph_contrast(&mut self, on: bool) {
+        if let Font::TrueType(ttf) = self {
+            ttf.set_glyph_contrast(on);
+        }
+    }
+
     pub fn width(&self) -> usize {
         match self {
             Font::Bitmap { width, .. } => *width,
@@ -431,8 +473,43 @@ impl Font {
     }
 
     /// Get glyph for a character (CPU rendering)
-    /// Returns the pixel data for CPU-based rendering
+    /// Returns the pixel data for CPU-based rendering, laid out `width()` pixels wide
     pub fn get_glyph_utf8(&self, ch: char) -> Vec<u8> {
+        let (glyph, glyph_width) = self.get_glyph_proportional(ch);
+        let width = self.width();
+        if glyph_width == width {
+            return glyph;
+        }
+
+        // Variable-width glyph: crop or pad each row to the fixed cell width
+        let mut fixed = vec![0u8; width * self.height()];
+        for (src_row, dst_row) in glyph.chunks_exact(glyph_width).zip(fixed.chunks_exact_mut(width)) {
+            let n = glyph_width.min(width);
+            dst_row[..n].copy_from_slice(&src_row[..n]);
+        }
+        fixed
+    }
+
+    /// Get a glyph at its own advance width (`.fd` fonts may set a per-character `width`)
+    /// Returns the pixel data and that width
+    pub fn get_glyph_proportional(&self, ch: char) -> (Vec<u8>, usize) {
+        let glyph = self.get_glyph_raw(ch);
+        let glyph_width = (glyph.len() / self.height().max(1)).max(1);
+        (glyph, glyph_width)
+    }
+
+    /// Widest glyph advance in the font (the cell width for fixed-width fonts)
+    pub fn max_advance(&self) -> usize {
+        let height = self.height().max(1);
+        let widest = match self {
+            Font::Bitmap { glyphs, .. } => glyphs.iter().map(|g| g.len() / height).max(),
+            Font::BitmapIntensity { glyphs, .. } => glyphs.values().map(|g| g.len() / height).max(),
+            Font::TrueType(_) => None,
+        };
+        widest.unwrap_or(0).max(self.width())
+    }
+
+    fn get_glyph_raw(&self, ch: char) -> Vec<u8> {
         match self {
             Font::Bitmap { glyphs, .. } => {
                 // Legacy bitmap - convert bool to u8 (0 or 255)
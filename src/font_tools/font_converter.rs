@@ -3,6 +3,7 @@ use font_kit::family_name::FamilyName;
 use font_kit::properties::Properties;
 use font_kit::source::SystemSource;
 use fontdue::{Font as FontdueFont, FontSettings};
+use super::truetype_font::stretch_coverage;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
@@ -17,6 +18,7 @@ pub struct FontConverter {
     char_width: usize,
     char_height: usize,
     y_offset_adjustment: i32, // Offset to adjust all characters so they fit in the tight bounding box
+    glyph_contrast: bool,     // Stretch glyph coverage before quantizing (see stretch_coverage)
 }
 
 impl FontConverter {
@@ -120,9 +122,15 @@ impl FontConverter {
             char_width,
             char_height,
             y_offset_adjustment: min_top, // Store the min_top so we can adjust all glyphs
+            glyph_contrast: false,
         })
     }
 
+    /// Stretch the coverage contrast of rasterized glyphs
+    pub fn set_glyph_contrast(&mut self, on: bool) {
+        self.glyph_contrast = on;
+    }
+
     /// Load fallback fonts using fontconfig to find fonts that cover missing glyphs
     fn load_fallback_fonts(font_size: usize) -> Result<Vec<FontdueFont>> {
         let mut fallback_fonts = Vec::new();
@@ -312,7 +320,10 @@ impl FontConverter {
                 }
             } else {
                 // Normal character rendering - try primary font first, then fallbacks
-                let (metrics, bitmap) = self.rasterize_with_fallback(*ch);
+                let (metrics, mut bitmap) = self.rasterize_with_fallback(*ch);
+                if self.glyph_contrast {
+                    stretch_coverage(&mut bitmap);
+                }
 
                 // Use the font's natural horizontal offset (xmin)
                 let glyph_x_offset = metrics.xmin.max(0) as usize;
@@ -16,8 +16,11 @@ pub struct TrueTypeFont {
     descender: f32,
     units_per_em: f32,
     baseline_offset: f32,
-    /// Cache for rendered glyphs (using Mutex for interior mutability)
-    glyph_cache: Arc<Mutex<HashMap<char, Vec<bool>>>>,
+    /// Cache of rasterized cell bitmaps, so each glyph is rasterized once
+    /// (using Mutex for interior mutability)
+    glyph_cache: Arc<Mutex<HashMap<char, Vec<u8>>>>,
+    /// Stretch coverage contrast (see `stretch_coverage`)
+    glyph_contrast: bool,
 }
 
 impl TrueTypeFont {
@@ -123,8 +126,20 @@ impl TrueTypeFont {
         let descender = face.descender() as f32;
         let units_per_em = face.units_per_em() as f32;
 
+        Ok(Self::with_size(Arc::new(font), ascender, descender, units_per_em, font_size as f32))
+    }
+
+    /// The same face at `factor` times the size, rasterized afresh rather than resampled
+    pub fn scaled(&self, factor: usize) -> Self {
+        let mut font = Self::with_size(self.font.clone(), self.ascender, self.descender, self.units_per_em, self.font_size * factor as f32);
+        font.glyph_contrast = self.glyph_contrast;
+        font
+    }
+
+    /// Lay out the cell for a parsed face at `font_size` pixels
+    fn with_size(font: Arc<FontdueFont>, ascender: f32, descender: f32, units_per_em: f32, font_size: f32) -> Self {
         // Calculate scale to achieve desired font size
-        let scale = font_size as f32 / units_per_em;
+        let scale = font_size / units_per_em;
         let ascender_px = ascender * scale;
         let descender_px = descender * scale;
 
@@ -137,14 +152,14 @@ impl TrueTypeFont {
         let baseline_offset = padding_top + ascender_px;
 
         // Calculate character width
-        let char_width = Self::calculate_char_width(&font, font_size as f32);
+        let char_width = Self::calculate_char_width(&font, font_size);
 
         eprintln!("Font metrics: font_size={}px, ascender={:.1}px, descender={:.1}px, cell={}x{}, baseline={:.1}px",
             font_size, ascender_px, descender_px, char_width, char_height, baseline_offset);
 
-        Ok(Self {
-            font: Arc::new(font),
-            font_size: font_size as f32,
+        Self {
+            font,
+            font_size,
             char_width,
             char_height,
             ascender,
@@ -152,7 +167,8 @@ impl TrueTypeFont {
             units_per_em,
             baseline_offset,
             glyph_cache: Arc::new(Mutex::new(HashMap::new())),
-        })
+            glyph_contrast: false,
+        }
     }
 
     /// Calculate average character width for monospace rendering
@@ -162,6 +178,14 @@ impl TrueTypeFont {
         metrics.width.max(1)
     }
 
+    pub fn set_glyph_contrast(&mut self, on: bool) {
+        if self.glyph_contrast != on {
+            self.glyph_contrast = on;
+            // Cached bitmaps were rasterized with the old setting (and may be shared with clones)
+            self.glyph_cache = Arc::new(Mutex::new(HashMap::new()));
+        }
+    }
+
     pub fn width(&self) -> usize {
         self.char_width
     }
@@ -179,9 +203,15 @@ impl TrueTypeFont {
             .collect()
     }
 
-    /// Get glyph with intensity values (grayscale) for a character
+    /// Get glyph with intensity values (grayscale) for a character (cached)
     pub fn get_glyph_intensity(&self, ch: char) -> Vec<u8> {
-        self.rasterize_char_intensity(ch)
+        if let Some(bitmap) = self.glyph_cache.lock().unwrap().get(&ch) {
+            return bitmap.clone();
+        }
+        // Rasterize outside the lock so other threads aren't held up
+        let bitmap = self.rasterize_char_intensity(ch);
+        self.glyph_cache.lock().unwrap().insert(ch, bitmap.clone());
+        bitmap
     }
 
     /// Rasterize a character to a boolean bitmap (legacy, for compatibility)
@@ -230,10 +260,26 @@ impl TrueTypeFont {
             }
         }
 
+        if self.glyph_contrast {
+            stretch_coverage(&mut cell_bitmap);
+        }
+
         cell_bitmap
     }
 }
 
+/// Contrast stretch for an anti-aliased coverage bitmap: faint edge coverage is dropped
+/// and strong coverage made solid, leaving fewer half-lit pixels at small sizes. This is
+/// not hinting (fontdue has no hinter); outlines are not moved onto the pixel grid
+pub fn stretch_coverage(bitmap: &mut [u8]) {
+    const LOW: i32 = 64;
+    const HIGH: i32 = 192;
+    for pixel in bitmap.iter_mut() {
+        let value = *pixel as i32;
+        *pixel = ((value - LOW) * 255 / (HIGH - LOW)).clamp(0, 255) as u8;
+    }
+}
+
 /// Query terminal for its font name
 pub fn query_terminal_font() -> Option<String> {
     use std::io::{Write, Read};
@@ -315,3 +361,40 @@ pub fn query_terminal_font() -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_rasterized_once() {
+        let Ok(mut font) = TrueTypeFont::from_system("monospace", 16) else {
+            eprintln!("no system monospace font; skipping");
+            return;
+        };
+
+        let first = font.get_glyph_intensity('g');
+        assert_eq!(font.get_glyph_intensity('g'), first);
+        assert_eq!(font.glyph_cache.lock().unwrap().len(), 1);
+
+        // A later call is served from the cache rather than rasterized again
+        let marker = vec![7; first.len()];
+        font.glyph_cache.lock().unwrap().insert('g', marker.clone());
+        assert_eq!(font.get_glyph_intensity('g'), marker);
+
+        // Changing how glyphs are drawn drops the cached bitmaps
+        font.set_glyph_contrast(true);
+        assert!(font.glyph_cache.lock().unwrap().is_empty());
+        assert_ne!(font.get_glyph_intensity('g'), marker);
+    }
+
+    #[test]
+    fn glyph_contrast_changes_partial_coverage_only() {
+        let coverage: Vec<u8> = vec![0, 40, 64, 128, 192, 230, 255];
+        let mut stretched = coverage.clone();
+        stretch_coverage(&mut stretched);
+
+        assert_ne!(stretched, coverage);
+        assert_eq!(stretched, vec![0, 0, 0, 127, 255, 255, 255]);
+    }
+}
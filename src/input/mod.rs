@@ -1,5 +1,10 @@
+pub mod speed_curve;
+
+pub use speed_curve::SpeedCurve;
+
 use anyhow::Result;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read};
 use std::path::Path;
@@ -15,6 +20,24 @@ pub struct Event {
 pub enum EventType {
     Output,
     Input,
+    Resize,
+}
+
+impl Event {
+    /// Terminal size carried by a resize ("r") event, as (columns, rows)
+    pub fn resize_dimensions(&self) -> Option<(usize, usize)> {
+        if !matches!(self.event_type, EventType::Resize) {
+            return None;
+        }
+        let text = std::str::from_utf8(&self.data).ok()?;
+        let (cols, rows) = text.split_once('x')?;
+        let cols = cols.trim().parse().ok()?;
+        let rows = rows.trim().parse().ok()?;
+        if cols == 0 || rows == 0 {
+            return None;
+        }
+        Some((cols, rows))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +45,8 @@ pub struct Metadata {
     pub width: usize,
     pub height: usize,
     pub title: Option<String>,
+    /// Environment captured with the recording (usually TERM and SHELL)
+    pub env: HashMap<String, String>,
 }
 
 pub trait InputSource {
@@ -37,6 +62,17 @@ struct AsciicastHeader {
     height: usize,
     #[serde(default)]
     title: Option<String>,
+    #[serde(default)]
+    env: HashMap<String, Option<String>>,
+}
+
+impl AsciicastHeader {
+    /// Recorded environment variables, dropping any recorded as null
+    fn env(&self) -> HashMap<String, String> {
+        self.env.iter()
+            .filter_map(|(name, value)| Some((name.clone(), value.clone()?)))
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -73,6 +109,7 @@ impl AsciicastReader {
                 event_type: match event.1.as_str() {
                     "o" => EventType::Output,
                     "i" => EventType::Input,
+                    "r" => EventType::Resize,
                     _ => EventType::Output,
                 },
                 data: event.2.as_bytes().to_vec(),
@@ -93,6 +130,7 @@ impl InputSource for AsciicastReader {
             width: self.header.width,
             height: self.header.height,
             title: self.header.title.clone(),
+            env: self.header.env(),
         }
     }
 }
@@ -129,6 +167,7 @@ impl StdinReader {
                 width: self.width,
                 height: self.height,
                 title: None,
+                env: HashMap::new(),
             });
             self.events = Some(vec![Event {
                 timestamp: 0.0,
@@ -162,6 +201,7 @@ impl StdinReader {
                 event_type: match event.1.as_str() {
                     "o" => EventType::Output,
                     "i" => EventType::Input,
+                    "r" => EventType::Resize,
                     _ => EventType::Output,
                 },
                 data: event.2.as_bytes().to_vec(),
@@ -171,6 +211,7 @@ impl StdinReader {
         let metadata = Metadata {
             width: header.width,
             height: header.height,
+            env: header.env(),
             title: header.title,
         };
 
@@ -191,6 +232,7 @@ impl InputSource for StdinReader {
             width: self.width,
             height: self.height,
             title: None,
+            env: HashMap::new(),
         })
     }
 }
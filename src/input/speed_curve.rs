@@ -0,0 +1,126 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// One interval of recording time played at its own speed
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpeedSegment {
+    pub start: f64,
+    pub end: f64,
+    pub multiplier: f64,
+}
+
+/// Piecewise playback speed; time outside every segment plays at the default speed
+#[derive(Debug, Clone)]
+pub struct SpeedCurve {
+    segments: Vec<SpeedSegment>,
+    default_multiplier: f64,
+}
+
+impl SpeedCurve {
+    pub fn new(mut segments: Vec<SpeedSegment>, default_multiplier: f64) -> Result<Self> {
+        if default_multiplier <= 0.0 {
+            anyhow::bail!("Speed must be greater than 0");
+        }
+        for seg in &segments {
+            if seg.multiplier <= 0.0 {
+                anyhow::bail!("Speed curve multiplier must be greater than 0 (segment {}-{})", seg.start, seg.end);
+            }
+            if seg.end <= seg.start {
+                anyhow::bail!("Speed curve segment end must be after start ({}-{})", seg.start, seg.end);
+            }
+        }
+        segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+        Ok(Self { segments, default_multiplier })
+    }
+
+    /// Load a YAML (or JSON) list of `{start, end, multiplier}` segments
+    pub fn load(path: &Path, default_multiplier: f64) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read speed curve: {}", path.display()))?;
+        let segments: Vec<SpeedSegment> = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse speed curve: {}", path.display()))?;
+        Self::new(segments, default_multiplier)
+    }
+
+    /// Map a recording timestamp to its playback timestamp.
+    /// Overlapping segments are resolved in favour of the one that starts first.
+    pub fn retime(&self, t: f64) -> f64 {
+        let mut out = 0.0;
+        let mut pos = 0.0;
+
+        for seg in &self.segments {
+            if t <= pos {
+                break;
+            }
+            // Default-speed gap before this segment
+            let gap_end = seg.start.max(pos).min(t);
+            if gap_end > pos {
+                out += (gap_end - pos) / self.default_multiplier;
+                pos = gap_end;
+            }
+            // The segment itself
+            let seg_end = seg.end.min(t);
+            if seg_end > pos {
+                out += (seg_end - pos) / seg.multiplier;
+                pos = seg_end;
+            }
+        }
+
+        if t > pos {
+            out += (t - pos) / self.default_multiplier;
+        }
+        out
+    }
+
+    /// Map a playback timestamp back to the recording timestamp it shows (inverse of `retime`)
+    pub fn source_time(&self, t: f64) -> f64 {
+        let mut out = 0.0;
+        let mut pos = 0.0;
+
+        for seg in &self.segments {
+            // Default-speed gap before this segment
+            let gap_end = seg.start.max(pos);
+            let gap_out = (gap_end - pos) / self.default_multiplier;
+            if out + gap_out >= t {
+                return pos + (t - out) * self.default_multiplier;
+            }
+            out += gap_out;
+            pos = gap_end;
+
+            // The segment itself
+            if seg.end > pos {
+                let seg_out = (seg.end - pos) / seg.multiplier;
+                if out + seg_out >= t {
+                    return pos + (t - out) * seg.multiplier;
+                }
+                out += seg_out;
+                pos = seg.end;
+            }
+        }
+
+        pos + (t - out) * self.default_multiplier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64, multiplier: f64) -> SpeedSegment {
+        SpeedSegment { start, end, multiplier }
+    }
+
+    #[test]
+    fn each_segment_retimes_by_its_multiplier() {
+        // 0-2s at double speed, 2-4s at half speed
+        let curve = SpeedCurve::new(vec![segment(2.0, 4.0, 0.5), segment(0.0, 2.0, 2.0)], 1.0).unwrap();
+        assert_eq!(curve.retime(1.0), 0.5);
+        assert_eq!(curve.retime(2.0), 1.0);
+        assert_eq!(curve.retime(3.0), 3.0);
+        assert_eq!(curve.retime(4.0), 5.0);
+        // Past the last segment at the default speed
+        assert_eq!(curve.retime(5.0), 6.0);
+    }
+}
@@ -7,3 +7,4 @@ pub mod renderer;
 pub mod encoder;
 pub mod theme;
 pub mod assets;
+pub mod convert;
@@ -1,5 +1,5 @@
 use clap::Parser;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::PathBuf;
 
 mod cli;
@@ -13,17 +13,16 @@ mod recorder;
 mod mcp_server;
 mod font_tools;
 mod palette_tools;
+mod convert;
 
 use input::{InputSource, AsciicastReader, StdinReader};
-use terminal::TerminalEmulator;
-use renderer::{Palette, Canvas, Font, query_terminal_font, RenderBackend};
-#[cfg(feature = "gpu")]
-use renderer::GpuRenderer;
+use terminal::{row_ansi, row_text, Cell, Grid, TerminalEmulator};
+use renderer::{Palette, Canvas, Font};
 #[cfg(not(feature = "gpu"))]
 use renderer::Rasterizer;
 use encoder::{EncoderWrapper, OutputFormat};
 use theme::Theme;
-use theme::layers::{LayerRenderer, LayerImage};
+use convert::{clamp_dimension, configure_emulation, effective_fps, feed_event, frame_grid, frame_palette, load_theme, pixel_density};
 
 /// Query terminal default color (OSC 10 for fg, OSC 11 for bg)
 fn query_default_terminal_color(osc_number: u8) -> Option<u8> {
@@ -105,50 +104,6 @@ fn query_default_terminal_color(osc_number: u8) -> Option<u8> {
     None
 }
 
-/// Find layer file in theme directories
-fn find_layer_file(layer_file: &str) -> PathBuf {
-    // Try as absolute path first
-    let absolute_path = PathBuf::from(layer_file);
-    if absolute_path.exists() {
-        return absolute_path;
-    }
-
-    // Search in theme directories
-    let mut search_paths = vec![
-        PathBuf::from("themes"),
-    ];
-
-    // Add Unix-specific system paths
-    #[cfg(not(windows))]
-    {
-        search_paths.push(PathBuf::from("/usr/share/ttyvid/themes"));
-        search_paths.push(PathBuf::from("/usr/local/share/ttyvid/themes"));
-    }
-
-    // Add user directories
-    if let Some(proj_dirs) = directories::ProjectDirs::from("", "", "ttyvid") {
-        for base_path in [proj_dirs.data_dir(), proj_dirs.config_dir()] {
-            for search_path in &search_paths {
-                let layer_path = base_path.join("themes").join(layer_file);
-                if layer_path.exists() {
-                    return layer_path;
-                }
-            }
-        }
-    }
-
-    // Search in system and current directory
-    for base_path in &search_paths {
-        let layer_path = base_path.join(layer_file);
-        if layer_path.exists() {
-            return layer_path;
-        }
-    }
-
-    // Fall back to relative path (will fail later if not found)
-    PathBuf::from("themes").join(layer_file)
-}
-
 fn main() -> Result<()> {
     let args = cli::Args::parse();
 
@@ -161,11 +116,14 @@ fn main() -> Result<()> {
         });
     }
 
-    println!("ttyvid version {}\n", env!("CARGO_PKG_VERSION"));
+    // --grid-only output is meant for diffing and piping, so it gets stdout to itself
+    if args.grid_only.is_none() {
+        println!("ttyvid version {}\n", env!("CARGO_PKG_VERSION"));
+    }
 
     // Handle subcommands or legacy mode
     match args.command {
-        Some(cli::Command::Record { ref output, ref command, max_idle, no_pause, stats, verbose, unbuffered }) => {
+        Some(cli::Command::Record { ref output, ref command, max_idle, no_pause, stats, unbuffered, duration, append, ref tee, ref env }) => {
             // Determine output formats
             let output_formats = if !args.formats.is_empty() {
                 // Use --formats flag
@@ -196,7 +154,14 @@ fn main() -> Result<()> {
                     (args.columns.unwrap_or(80) as u16, args.rows.unwrap_or(24) as u16)
                 }
             } else {
-                (args.columns.unwrap_or(80) as u16, args.rows.unwrap_or(24) as u16)
+                // --append carries on at the recording's current size unless told otherwise
+                let appended_size = if append && cast_file.exists() {
+                    recorder::recording_end(&cast_file).ok().map(|(_, size)| size)
+                } else {
+                    None
+                };
+                let (default_cols, default_rows) = appended_size.unwrap_or((80, 24));
+                (args.columns.unwrap_or(default_cols) as u16, args.rows.unwrap_or(default_rows) as u16)
             };
 
             // Configure environment variables
@@ -204,6 +169,7 @@ fn main() -> Result<()> {
             if unbuffered {
                 env_vars.push(("PYTHONUNBUFFERED".to_string(), "1".to_string()));
             }
+            env_vars.extend(env.iter().cloned());
 
             // Configure and run recorder
             let config = recorder::RecordConfig {
@@ -214,8 +180,11 @@ fn main() -> Result<()> {
                 max_idle,
                 allow_pause: !no_pause,
                 show_stats: stats,  // Disabled by default, enable with --stats
-                verbose,
+                verbose: args.verbose,
                 env: env_vars,
+                duration,
+                append,
+                tee: tee.clone(),
             };
 
             let recorder = recorder::Recorder::new(config);
@@ -223,40 +192,7 @@ fn main() -> Result<()> {
 
             // Generate all requested formats
             let keep_cast = output_formats.contains(&"cast".to_string());
-            let mut generated_files = vec![];
-
-            for format in &output_formats {
-                match format.to_lowercase().as_str() {
-                    "cast" => {
-                        // Already generated
-                        generated_files.push(cast_file.clone());
-                    }
-                    "gif" => {
-                        let gif_file = base_output.with_extension("gif");
-                        eprintln!("\nConverting to GIF...");
-                        eprintln!("This may take a moment depending on recording length and frame rate.\n");
-                        convert_recording(&args, Some(cast_file.clone()), Some(gif_file.clone()))?;
-                        generated_files.push(gif_file);
-                    }
-                    #[cfg(feature = "webm")]
-                    "webm" => {
-                        let webm_file = base_output.with_extension("webm");
-                        eprintln!("\nConverting to WebM...");
-                        eprintln!("This may take a moment depending on recording length and frame rate.\n");
-                        convert_recording(&args, Some(cast_file.clone()), Some(webm_file.clone()))?;
-                        generated_files.push(webm_file);
-                    }
-                    "md" | "markdown" => {
-                        // Generate markdown file with embedded GIF/WebM
-                        let md_file = base_output.with_extension("md");
-                        generate_markdown(&base_output, &output_formats, &md_file)?;
-                        generated_files.push(md_file);
-                    }
-                    _ => {
-                        eprintln!("Warning: Unknown format '{}', skipping", format);
-                    }
-                }
-            }
+            generate_formats(&args, Some(&cast_file), output, &output_formats)?;
 
             // Clean up temporary .cast file if not requested
             if !keep_cast && cast_file.exists() {
@@ -264,58 +200,19 @@ fn main() -> Result<()> {
                     eprintln!("Warning: Failed to remove temporary file {}: {}", cast_file.display(), e);
                 }
             }
-
-            // Show summary
-            eprintln!("\nGenerated files:");
-            for file in &generated_files {
-                eprintln!("  ✓ {}", file.display());
-            }
+        }
+        Some(cli::Command::Convert { ref input, .. }) if args.check => {
+            check_recording(&args, Some(input.clone()))?;
+        }
+        Some(cli::Command::Convert { ref input, .. }) if args.grid_only.is_some() => {
+            print_final_grid(&args, Some(input.clone()))?;
         }
         Some(cli::Command::Convert { ref input, ref output }) => {
             if !args.formats.is_empty() {
-                // Multiple formats requested
-                let base_output = output.with_extension("");
-                let mut generated_files = vec![];
-
-                for format in &args.formats {
-                    match format.to_lowercase().as_str() {
-                        "cast" => {
-                            // Copy the input file
-                            let cast_file = base_output.with_extension("cast");
-                            std::fs::copy(input, &cast_file)?;
-                            generated_files.push(cast_file);
-                        }
-                        "gif" => {
-                            let gif_file = base_output.with_extension("gif");
-                            eprintln!("\nConverting to GIF...");
-                            convert_recording(&args, Some(input.clone()), Some(gif_file.clone()))?;
-                            generated_files.push(gif_file);
-                        }
-                        #[cfg(feature = "webm")]
-                        "webm" => {
-                            let webm_file = base_output.with_extension("webm");
-                            eprintln!("\nConverting to WebM...");
-                            convert_recording(&args, Some(input.clone()), Some(webm_file.clone()))?;
-                            generated_files.push(webm_file);
-                        }
-                        "md" | "markdown" => {
-                            let md_file = base_output.with_extension("md");
-                            generate_markdown(&base_output, &args.formats, &md_file)?;
-                            generated_files.push(md_file);
-                        }
-                        _ => {
-                            eprintln!("Warning: Unknown format '{}', skipping", format);
-                        }
-                    }
-                }
-
-                eprintln!("\nGenerated files:");
-                for file in &generated_files {
-                    eprintln!("  ✓ {}", file.display());
-                }
+                generate_formats(&args, Some(input), output, &args.formats)?;
             } else {
                 // Single format (legacy behavior)
-                convert_recording(&args, Some(input.clone()), Some(output.clone()))?;
+                convert_recording(&args, Some(input.clone()), std::slice::from_ref(output))?;
             }
         }
         Some(cli::Command::ListFonts { system, bitmap }) => {
@@ -362,6 +259,15 @@ fn main() -> Result<()> {
                 println!("\nUsage: ttyvid convert -i input.cast -o output.gif --font FontName");
             }
         }
+        Some(cli::Command::Diff { ref a, ref b, ref output }) => {
+            diff_recordings(&args, a, b, output)?;
+        }
+        Some(cli::Command::ContactSheet { ref input, ref output, ref frame_at, samples, columns }) => {
+            contact_sheet(&args, input, output, frame_at, samples, columns)?;
+        }
+        Some(cli::Command::Transcript { ref input, ref output, timestamps }) => {
+            transcript_recording(&args, input, output, timestamps)?;
+        }
         Some(cli::Command::ConvertFont { ref font, ref output, size, ref chars }) => {
             println!("Converting font to bitmap .fd format:");
             println!(" - font: {}", font);
@@ -388,7 +294,8 @@ fn main() -> Result<()> {
 
             // Load and convert the font
             println!("Loading font...");
-            let converter = font_tools::font_converter::FontConverter::load(font, size)?;
+            let mut converter = font_tools::font_converter::FontConverter::load(font, size)?;
+            converter.set_glyph_contrast(args.glyph_contrast);
 
             println!("Generating bitmap font...");
             converter.convert_to_fd(output, char_map)?;
@@ -423,6 +330,47 @@ fn main() -> Result<()> {
             // Generate palette card
             palette_tools::palette_card::generate_palette_card(&theme_obj.name, output, fg, bg)?;
         }
+        Some(cli::Command::AnsiCard { ref output }) => {
+            println!("Generating ANSI color card:");
+            println!(" - theme: {}", args.theme);
+            println!(" - output: {}", output.display());
+
+            let theme_path = std::path::Path::new(&args.theme);
+            let mut theme_obj = if theme_path.is_file() {
+                Theme::load(theme_path)?
+            } else {
+                Theme::load_by_name(&args.theme)?
+            };
+            for assignment in &args.theme_overrides {
+                theme_obj.apply_override(assignment)?;
+            }
+
+            palette_tools::ansi_card::generate_ansi_card(&theme_obj, output)?;
+        }
+        Some(cli::Command::ExportPalette { ref output }) => {
+            use palette_tools::export::PaletteFormat;
+
+            // --format names the palette format here; otherwise go by the extension
+            let format_name = match args.format {
+                Some(ref name) => name.clone(),
+                None => output.extension().and_then(|ext| ext.to_str()).unwrap_or("gpl").to_string(),
+            };
+            let format = PaletteFormat::from_name(&format_name).with_context(|| {
+                format!("Unknown palette format '{}': expected gpl, hex or json", format_name)
+            })?;
+
+            let theme_path = std::path::Path::new(&args.theme);
+            let mut theme_obj = if theme_path.is_file() {
+                Theme::load(theme_path)?
+            } else {
+                Theme::load_by_name(&args.theme)?
+            };
+            for assignment in &args.theme_overrides {
+                theme_obj.apply_override(assignment)?;
+            }
+
+            palette_tools::export::export_palette(&theme_obj, output, format)?;
+        }
         Some(cli::Command::FontCard { ref font, ref output, size: _ }) => {
             use std::path::Path;
 
@@ -445,54 +393,19 @@ fn main() -> Result<()> {
             // Generate font card
             font_tools::font_card::generate_font_card(&font_obj, output, font)?;
         }
+        None if args.check => {
+            check_recording(&args, args.input.clone())?;
+        }
+        None if args.grid_only.is_some() => {
+            print_final_grid(&args, args.input.clone())?;
+        }
         None => {
             // Legacy mode: no subcommand, behave like convert
-            if !args.formats.is_empty() && args.output.is_some() {
-                // Multiple formats in legacy mode
-                let output = args.output.as_ref().unwrap();
-                let base_output = output.with_extension("");
-                let mut generated_files = vec![];
-
-                for format in &args.formats {
-                    match format.to_lowercase().as_str() {
-                        "cast" => {
-                            if let Some(ref input) = args.input {
-                                let cast_file = base_output.with_extension("cast");
-                                std::fs::copy(input, &cast_file)?;
-                                generated_files.push(cast_file);
-                            }
-                        }
-                        "gif" => {
-                            let gif_file = base_output.with_extension("gif");
-                            eprintln!("\nConverting to GIF...");
-                            convert_recording(&args, args.input.clone(), Some(gif_file.clone()))?;
-                            generated_files.push(gif_file);
-                        }
-                        #[cfg(feature = "webm")]
-                        "webm" => {
-                            let webm_file = base_output.with_extension("webm");
-                            eprintln!("\nConverting to WebM...");
-                            convert_recording(&args, args.input.clone(), Some(webm_file.clone()))?;
-                            generated_files.push(webm_file);
-                        }
-                        "md" | "markdown" => {
-                            let md_file = base_output.with_extension("md");
-                            generate_markdown(&base_output, &args.formats, &md_file)?;
-                            generated_files.push(md_file);
-                        }
-                        _ => {
-                            eprintln!("Warning: Unknown format '{}', skipping", format);
-                        }
-                    }
-                }
-
-                eprintln!("\nGenerated files:");
-                for file in &generated_files {
-                    eprintln!("  ✓ {}", file.display());
-                }
+            if let Some(output) = args.output.as_ref().filter(|_| !args.formats.is_empty()) {
+                generate_formats(&args, args.input.as_deref(), output, &args.formats)?;
             } else {
                 // Single format legacy mode
-                convert_recording(&args, args.input.clone(), args.output.clone())?;
+                convert_recording(&args, args.input.clone(), args.output.as_slice())?;
             }
         }
     }
@@ -500,582 +413,740 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn convert_recording(args: &cli::Args, input: Option<PathBuf>, output: Option<PathBuf>) -> Result<()> {
-    // Query terminal size if requested
-    let (term_cols, term_rows) = if args.clone || args.terminal_size {
-        use crossterm::terminal;
-        if let Ok((cols, rows)) = terminal::size() {
-            eprintln!("Using terminal size: {}x{}", cols, rows);
-            (Some(cols as usize), Some(rows as usize))
-        } else {
-            eprintln!("Warning: Could not query terminal size, using defaults");
-            (None, None)
-        }
-    } else {
-        (None, None)
-    };
-
-    // Determine output format
-    let output_format = if let Some(ref fmt_str) = args.format {
-        // Explicit format specified
-        let explicit_format = match fmt_str.to_lowercase().as_str() {
-            "gif" => OutputFormat::Gif,
-            #[cfg(feature = "webm")]
-            "webm" => OutputFormat::Webm,
-            _ => {
-                #[cfg(feature = "webm")]
-                {
-                    anyhow::bail!("Unknown format: {}. Supported formats: gif, webm", fmt_str)
-                }
-                #[cfg(not(feature = "webm"))]
-                {
-                    anyhow::bail!("Unknown format: {}. Supported formats: gif (compile with --features webm for WebM support)", fmt_str)
+/// Write every format in `formats` next to `output` (its extension is replaced per format).
+/// Video formats share one render pass; cast and html need the recording on disk.
+fn generate_formats(args: &cli::Args, input: Option<&std::path::Path>, output: &std::path::Path, formats: &[String]) -> Result<()> {
+    let base_output = output.with_extension("");
+    let mut generated_files = vec![];
+    let mut video_outputs = vec![];
+
+    for format in formats {
+        match format.to_lowercase().as_str() {
+            "cast" => {
+                if let Some(input) = input {
+                    let cast_file = base_output.with_extension("cast");
+                    // A fresh recording is already written here
+                    if cast_file != input {
+                        std::fs::copy(input, &cast_file)?;
+                    }
+                    generated_files.push(cast_file);
+                } else {
+                    eprintln!("Warning: cast output needs an input file, skipping");
                 }
             }
-        };
-
-        // Warn if explicit format doesn't match output file extension
-        if let Some(ref path) = output {
-            if let Some(detected_format) = OutputFormat::from_path(path) {
-                if detected_format != explicit_format {
-                    eprintln!("Warning: Specified format '{:?}' doesn't match output file extension '.{}'",
-                        explicit_format, path.extension().and_then(|e| e.to_str()).unwrap_or("?"));
-                    eprintln!("         Using specified format: {:?}", explicit_format);
+            "html" => {
+                if let Some(input) = input {
+                    let html_file = base_output.with_extension("html");
+                    encoder::generate_html_player(input, &html_file, &recording_title(&base_output))?;
+                    generated_files.push(html_file);
+                } else {
+                    eprintln!("Warning: html output needs an input file (the cast is embedded), skipping");
                 }
             }
-        }
-
-        explicit_format
-    } else if let Some(ref path) = output {
-        // Auto-detect from output file extension
-        OutputFormat::from_path(path).unwrap_or(OutputFormat::Gif)
-    } else {
-        // Default to GIF
-        OutputFormat::Gif
-    };
-
-    // Determine output path
-    let output_path = if let Some(path) = output {
-        path
-    } else {
-        // Auto-generate filename with proper extension
-        let ext = output_format.extension();
-        let mut index = 0;
-        loop {
-            let filename = format!("ttyvid-{:04}.{}", index, ext);
-            if !std::path::Path::new(&filename).exists() {
-                break PathBuf::from(filename);
+            "gif" => {
+                video_outputs.push(base_output.with_extension("gif"));
             }
-            index += 1;
-            if index >= 10000 {
-                anyhow::bail!("No available output filenames (ttyvid-0000.{} to ttyvid-9999.{} all exist)", ext, ext);
+            #[cfg(feature = "webm")]
+            "webm" => {
+                video_outputs.push(base_output.with_extension("webm"));
+            }
+            #[cfg(feature = "svg")]
+            "svg" => {
+                video_outputs.push(base_output.with_extension("svg"));
+            }
+            "md" | "markdown" => {
+                let md_file = base_output.with_extension("md");
+                generate_markdown(&base_output, formats, &md_file, args.md_template.as_deref())?;
+                generated_files.push(md_file);
+            }
+            _ => {
+                eprintln!("Warning: Unknown format '{}', skipping", format);
             }
         }
-    };
+    }
 
-    // Read input events
-    let mut input_source: Box<dyn InputSource> = if let Some(ref path) = input {
-        Box::new(AsciicastReader::new(path)?)
-    } else {
-        Box::new(StdinReader::new(
-            args.columns.unwrap_or(80),
-            args.rows.unwrap_or(25),
-        ))
-    };
+    // Render once and encode every video format in parallel
+    if !video_outputs.is_empty() {
+        eprintln!("\nConverting to {}...", describe_outputs(&video_outputs));
+        eprintln!("This may take a moment depending on recording length and frame rate.\n");
+        convert_recording(args, input.map(|p| p.to_path_buf()), &video_outputs)?;
+        generated_files.extend(video_outputs);
+    }
 
-    let mut events = input_source.read_events()?;
-    let metadata = input_source.metadata();
+    eprintln!("\nGenerated files:");
+    for file in &generated_files {
+        eprintln!("  ✓ {}", file.display());
+    }
+    Ok(())
+}
 
-    // Determine dimensions: terminal size > explicit args > metadata
-    let width = term_cols.or(args.columns).unwrap_or(metadata.width);
-    let height = term_rows.or(args.rows).unwrap_or(metadata.height);
+/// Human-readable list of output formats, e.g. "GIF, WEBM"
+fn describe_outputs(outputs: &[PathBuf]) -> String {
+    outputs.iter()
+        .filter_map(|p| p.extension().and_then(|e| e.to_str()))
+        .map(|e| e.to_uppercase())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-    // Load theme
-    let theme = {
-        let theme_path = std::path::Path::new(&args.theme);
-        if theme_path.exists() && theme_path.is_file() {
-            // Direct path to theme file
-            Theme::load(theme_path)?
-        } else {
-            // Load by name (searches filesystem + embedded)
-            Theme::load_by_name(&args.theme)?
+/// Background color index used to tint cells that differ between two recordings
+const DIFF_TINT_COLOR: u8 = 1;
+
+/// Play two recordings on the same clock and encode recording A with cells that differ from B tinted
+fn diff_recordings(args: &cli::Args, path_a: &std::path::Path, path_b: &std::path::Path, output: &std::path::Path) -> Result<()> {
+    let mut reader_a = AsciicastReader::new(path_a)?;
+    let mut reader_b = AsciicastReader::new(path_b)?;
+    let events_a = reader_a.read_events()?;
+    let events_b = reader_b.read_events()?;
+    let (meta_a, meta_b) = (reader_a.metadata(), reader_b.metadata());
+
+    // Compare on a grid large enough for both, including any recorded resizes
+    let (cols, rows) = events_a.iter().chain(&events_b)
+        .filter_map(|event| event.resize_dimensions())
+        .fold((meta_a.width.max(meta_b.width), meta_a.height.max(meta_b.height)),
+            |(w, h), (c, r)| (w.max(c), h.max(r)));
+    let cols = clamp_dimension("columns", cols, args.max_columns)?;
+    let rows = clamp_dimension("rows", rows, args.max_rows)?;
+
+    let frame_rate = effective_fps(args.fps);
+    let frame_duration = 1.0 / frame_rate as f64;
+    let delay_centiseconds = (100.0 / frame_rate as f64).round() as u16;
+    let duration = events_a.iter().chain(&events_b)
+        .map(|event| event.timestamp)
+        .fold(0.0, f64::max);
+    let frame_count = ((duration * frame_rate as f64).ceil() as usize + 1).max(1);
+
+    println!("Comparing recordings:");
+    println!(" - a: {}", path_a.display());
+    println!(" - b: {}", path_b.display());
+    println!(" - output: {}", output.display());
+    println!(" - character dimensions: {}x{}", cols, rows);
+    println!(" - frames: {}", frame_count);
+
+    let theme = load_theme(args)?;
+    let (palette, term_default_fg, term_default_bg) = frame_palette(args, &theme)?;
+    let default_fg = term_default_fg.unwrap_or(theme.default_foreground);
+    let default_bg = term_default_bg.unwrap_or(theme.default_background);
+    let renderer = renderer::create_renderer_auto(Font::load(args.font.as_deref()).scaled(pixel_density(args)), palette.clone());
+    let (pixel_width, pixel_height) = renderer.canvas_size(cols, rows);
+    let format = OutputFormat::from_path(output).unwrap_or(OutputFormat::Gif);
+    let mut encoder = EncoderWrapper::new(
+        output, pixel_width, pixel_height, &palette, format,
+        args.r#loop, frame_rate, args.quality.clamp(0, 100), None, None, args.color_space, args.interlace,
+    )?;
+
+    let mut terminal_a = TerminalEmulator::new(meta_a.width, meta_a.height, !args.no_autowrap, default_fg, default_bg);
+    let mut terminal_b = TerminalEmulator::new(meta_b.width, meta_b.height, !args.no_autowrap, default_fg, default_bg);
+    for (terminal, meta) in [(&mut terminal_a, &meta_a), (&mut terminal_b, &meta_b)] {
+        configure_emulation(terminal, args, meta);
+        terminal.set_palette(palette.clone());
+    }
+    let (mut idx_a, mut idx_b) = (0, 0);
+    let mut differing_frames = 0;
+
+    for frame_num in 0..frame_count {
+        let current_time = frame_num as f64 * frame_duration;
+        while idx_a < events_a.len() && events_a[idx_a].timestamp <= current_time {
+            feed_event(&mut terminal_a, &events_a[idx_a], (cols, rows));
+            idx_a += 1;
+        }
+        while idx_b < events_b.len() && events_b[idx_b].timestamp <= current_time {
+            feed_event(&mut terminal_b, &events_b[idx_b], (cols, rows));
+            idx_b += 1;
         }
-    };
 
-    println!(" - input: {}", input.as_deref().unwrap_or(std::path::Path::new("stdin")).display());
-    println!(" - output: {}", output_path.display());
-    println!(" - format: {:?}", output_format);
-    println!(" - theme: {}", theme.name);
-    println!(" - speed: {}", args.speed);
-    println!(" - events: {}", events.len());
-    println!(" - character dimensions: {}x{}", width, height);
-
-    // Apply speed multiplier to timestamps
-    if args.speed != 1.0 {
-        for event in &mut events {
-            event.timestamp /= args.speed;
+        let mut overlay = frame_grid(&terminal_a, cols, rows).into_owned();
+        if tint_differences(&mut overlay, &frame_grid(&terminal_b, cols, rows)) {
+            differing_frames += 1;
         }
-    }
 
-    // Remove gaps if requested
-    if args.no_gaps {
-        remove_gaps(&mut events);
+        encoder.add_frame(&renderer.render_grid(&overlay), delay_centiseconds)?;
     }
 
-    // Calculate total duration and frame count
-    let duration = if !events.is_empty() {
-        events.last().unwrap().timestamp
-    } else {
-        0.0
-    };
-    let frame_rate = args.fps.clamp(1, 100);
-    // Add 1 frame to ensure all events are processed (last event needs a frame AFTER it to be visible)
-    let frame_count = ((duration * frame_rate as f64).ceil() as usize + 1).max(1);
-
-    // Add trailer frames if requested (1.5 seconds holding the final state)
-    let trailer_frame_count = if args.trailer {
-        (frame_rate as f64 * 1.5).round() as usize
-    } else {
-        0
-    };
-    let total_frame_count = frame_count + trailer_frame_count;
+    encoder.finish()?;
+    println!("\n✓ {} of {} frames differ", differing_frames, frame_count);
+    Ok(())
+}
 
-    println!(" - frame rate: {}", frame_rate);
-    println!(" - frames: {}", frame_count);
-    println!(" - seconds: {:.2}", duration);
-    if args.trailer {
-        println!(" - trailer: {} frames (1.5s)", trailer_frame_count);
+/// Tint the background of every cell of `overlay` that differs from `other`; true if any did
+fn tint_differences(overlay: &mut Grid, other: &Grid) -> bool {
+    let mut differs = false;
+    for y in 0..overlay.height() {
+        for x in 0..overlay.width() {
+            if let (Some(&cell), Some(other_cell)) = (overlay.get_cell(x, y), other.get_cell(x, y)) {
+                if cell != *other_cell {
+                    overlay.write_cell(x, y, Cell { bg_color: DIFF_TINT_COLOR, ..cell });
+                    differs = true;
+                }
+            }
+        }
     }
+    differs
+}
 
-    // Query terminal colors early if needed (gets palette + default colors in one go)
-    let (palette, term_default_fg, term_default_bg) = if args.clone || args.terminal_colors {
-        eprintln!("Querying terminal for colors...");
-        // This queries the full palette AND default fg/bg colors
-        let (pal, fg, bg) = Palette::from_terminal();
-        eprintln!("Terminal colors detected: fg={:?}, bg={:?}", fg, bg);
-        (Some(pal), fg, bg)
-    } else if let Some(ref theme_palette) = theme.palette {
-        // Use theme's custom palette
-        (Some(Palette::from_theme(theme_palette)), None, None)
+/// Pixels between contact sheet tiles and around the sheet edge
+const CONTACT_SHEET_GAP: usize = 8;
+
+/// Render a recording at several times and tile the frames, each labeled with its time, into a PNG
+fn contact_sheet(args: &cli::Args, input: &std::path::Path, output: &std::path::Path,
+    frame_at: &[f64], samples: Option<usize>, columns: Option<usize>) -> Result<()> {
+    let mut reader = AsciicastReader::new(input)?;
+    let events = reader.read_events()?;
+    let meta = reader.metadata();
+
+    let (cols, rows) = events.iter()
+        .filter_map(|event| event.resize_dimensions())
+        .fold((meta.width, meta.height), |(w, h), (c, r)| (w.max(c), h.max(r)));
+    let cols = clamp_dimension("columns", cols, args.max_columns)?;
+    let rows = clamp_dimension("rows", rows, args.max_rows)?;
+
+    // Explicit times, or N samples spread evenly so the last one shows the end of the recording
+    let duration = events.last().map_or(0.0, |event| event.timestamp);
+    let mut times: Vec<f64> = if frame_at.is_empty() {
+        let samples = samples.unwrap_or(4).max(1);
+        (1..=samples).map(|i| duration * i as f64 / samples as f64).collect()
     } else {
-        // Fall back to default
-        (Some(Palette::default()), None, None)
+        frame_at.to_vec()
     };
+    times.sort_by(f64::total_cmp);
+
+    let tiles_per_row = columns
+        .unwrap_or_else(|| (times.len() as f64).sqrt().ceil() as usize)
+        .clamp(1, times.len());
+    let tile_rows = times.len().div_ceil(tiles_per_row);
 
-    // Create terminal emulator with colors (terminal colors override theme)
+    let theme = load_theme(args)?;
+    let (palette, term_default_fg, term_default_bg) = frame_palette(args, &theme)?;
     let default_fg = term_default_fg.unwrap_or(theme.default_foreground);
     let default_bg = term_default_bg.unwrap_or(theme.default_background);
-    eprintln!("Using colors: fg={}, bg={}", default_fg, default_bg);
-    let mut terminal = TerminalEmulator::new(width, height, !args.no_autowrap, default_fg, default_bg);
-
-    // Create rasterizer with font (GPU-accelerated if compiled with --features gpu)
-    #[cfg(feature = "gpu")]
-    let rasterizer = {
-        let font = if let Some(ref font_file) = args.font_file {
-            eprintln!("Loading bitmap font from file: {}", font_file.display());
-            match Font::load_from_file(font_file) {
-                Ok(bitmap_font) => {
-                    eprintln!("Successfully loaded bitmap font (cell size: {}x{})", bitmap_font.width(), bitmap_font.height());
-                    bitmap_font
-                }
-                Err(e) => {
-                    eprintln!("Failed to load font file: {}", e);
-                    eprintln!("Falling back to embedded bitmap font");
-                    Font::load(args.font.as_deref())
-                }
-            }
-        } else if let Some(ref system_font) = args.system_font {
-            eprintln!("Loading system font: {} at size {}", system_font, args.font_size);
-            if let Some(ttf_font) = Font::from_system_font(system_font, args.font_size) {
-                eprintln!("Successfully loaded system font (cell size: {}x{})", ttf_font.width(), ttf_font.height());
-                ttf_font
-            } else {
-                eprintln!("Failed to load system font, falling back to embedded bitmap font");
-                Font::load(args.font.as_deref())
-            }
-        } else if args.clone {
-            if let Some(font_name) = query_terminal_font() {
-                eprintln!("Terminal font detected: {}", font_name);
-                if let Some(ttf_font) = Font::from_system_font(&font_name, args.font_size) {
-                    eprintln!("Loaded TrueType font: {} (cell size: {}x{})", font_name, ttf_font.width(), ttf_font.height());
-                    ttf_font
-                } else {
-                    eprintln!("Could not load font '{}', falling back to embedded font", font_name);
-                    Font::load(args.font.as_deref())
-                }
-            } else {
-                eprintln!("Could not detect terminal font, using embedded font");
-                Font::load(args.font.as_deref())
-            }
+    let density = pixel_density(args);
+    let renderer = renderer::create_renderer_auto(Font::load(args.font.as_deref()).scaled(density), palette.clone());
+    let (frame_width, frame_height) = renderer.canvas_size(cols, rows);
+    let (_, label_height) = renderer.canvas_size(1, 1);
+    let tile_height = frame_height + label_height;
+
+    let gap = CONTACT_SHEET_GAP * density;
+    let sheet_width = tiles_per_row * (frame_width + gap) + gap;
+    let sheet_height = tile_rows * (tile_height + gap) + gap;
+    let mut sheet = Canvas::new(sheet_width, sheet_height, &palette);
+    sheet.fill(term_default_bg.unwrap_or(theme.background));
+
+    println!("Contact sheet:");
+    println!(" - input: {}", input.display());
+    println!(" - output: {}", output.display());
+    println!(" - tiles: {} ({}x{} grid)", times.len(), tiles_per_row, tile_rows);
+
+    let mut terminal = TerminalEmulator::new(meta.width.min(cols), meta.height.min(rows), !args.no_autowrap, default_fg, default_bg);
+    configure_emulation(&mut terminal, args, &meta);
+    terminal.set_palette(palette.clone());
+    let mut event_idx = 0;
+    for (i, &time) in times.iter().enumerate() {
+        while event_idx < events.len() && events[event_idx].timestamp <= time {
+            feed_event(&mut terminal, &events[event_idx], (cols, rows));
+            event_idx += 1;
+        }
+
+        let grid = frame_grid(&terminal, cols, rows);
+        let frame = if !args.no_cursor && terminal.state().display_cursor {
+            let (cursor_x, cursor_y) = terminal.state().cursor_get_position();
+            renderer.render_grid_with_cursor(&grid, cursor_x as usize, cursor_y as usize)
         } else {
-            Font::load(args.font.as_deref())
+            renderer.render_grid(&grid)
         };
-        GpuRenderer::new(font, palette.as_ref().unwrap().clone())
-    };
 
-    #[cfg(not(feature = "gpu"))]
-    let rasterizer = if let Some(ref font_file) = args.font_file {
-        eprintln!("Loading bitmap font from file: {}", font_file.display());
-        match Font::load_from_file(font_file) {
-            Ok(bitmap_font) => {
-                eprintln!("Successfully loaded bitmap font (cell size: {}x{})", bitmap_font.width(), bitmap_font.height());
-                Rasterizer::with_font(bitmap_font)
-            }
-            Err(e) => {
-                eprintln!("Failed to load font file: {}", e);
-                eprintln!("Falling back to embedded bitmap font");
-                Rasterizer::new(args.font.as_deref())
-            }
+        let x = gap + (i % tiles_per_row) * (frame_width + gap);
+        let y = gap + (i / tiles_per_row) * (tile_height + gap);
+        sheet.blit(&frame, x, y);
+        renderer.render_title(&mut sheet, x as i32, (y + frame_height) as i32, &format!("{:.2}s", time), default_fg, default_bg, 1.0);
+    }
+
+    save_png(&sheet.to_rgba_image(&palette), output, density)
+        .with_context(|| format!("Failed to write contact sheet: {}", output.display()))?;
+    println!("✓ Contact sheet created: {} ({}x{})", output.display(), sheet_width, sheet_height);
+
+    Ok(())
+}
+
+/// Write a PNG; above 1x density a pHYs chunk records the DPI (72 per unit of density),
+/// so HiDPI-aware viewers show a --retina image at its 1x size
+fn save_png(image: &image::RgbaImage, path: &std::path::Path, density: usize) -> Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width(), image.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if density > 1 {
+        let pixels_per_meter = (72.0 * density as f64 / 0.0254).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: pixels_per_meter, yppu: pixels_per_meter, unit: png::Unit::Meter }));
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Substitute {basename}, {date}, {theme} and {index} tokens in an output filename template
+fn expand_output_template(template: &str, basename: &str, theme: &str, index: usize) -> String {
+    template
+        .replace("{basename}", basename)
+        .replace("{date}", &chrono::Local::now().format("%Y%m%d-%H%M%S").to_string())
+        .replace("{theme}", theme)
+        .replace("{index}", &format!("{:04}", index))
+}
+
+/// Replay a recording and write every line that reached the main screen, oldest first
+fn transcript_recording(args: &cli::Args, input: &std::path::Path, output: &std::path::Path, timestamps: bool) -> Result<()> {
+    let mut reader = AsciicastReader::new(input)?;
+    let events = reader.read_events()?;
+    let meta = reader.metadata();
+
+    let (cols, rows) = events.iter()
+        .filter_map(|event| event.resize_dimensions())
+        .fold((meta.width, meta.height), |(w, h), (c, r)| (w.max(c), h.max(r)));
+    let cols = clamp_dimension("columns", cols, args.max_columns)?;
+    let rows = clamp_dimension("rows", rows, args.max_rows)?;
+
+    let mut terminal = TerminalEmulator::new(meta.width.min(cols), meta.height.min(rows), !args.no_autowrap, 7, 0);
+    configure_emulation(&mut terminal, args, &meta);
+    terminal.enable_scrollback(usize::MAX);
+
+    // Time each line first held text, following rows as they scroll off into scrollback
+    let mut scrolled_times: Vec<Option<f64>> = Vec::new();
+    let mut row_times: Vec<Option<f64>> = Vec::new();
+
+    for event in &events {
+        feed_event(&mut terminal, event, (cols, rows));
+
+        let scrolled = terminal.scrollback_len() - scrolled_times.len();
+        for _ in 0..scrolled {
+            let time = if row_times.is_empty() { None } else { row_times.remove(0) };
+            scrolled_times.push(time.or(Some(event.timestamp)));
         }
-    } else if let Some(ref system_font) = args.system_font {
-        eprintln!("Loading system font: {} at size {}", system_font, args.font_size);
-        if let Some(ttf_font) = Font::from_system_font(system_font, args.font_size) {
-            eprintln!("Successfully loaded system font (cell size: {}x{})", ttf_font.width(), ttf_font.height());
-            Rasterizer::with_font(ttf_font)
-        } else {
-            eprintln!("Failed to load system font, falling back to embedded bitmap font");
-            Rasterizer::new(args.font.as_deref())
+
+        let screen = terminal.main_grid();
+        row_times.resize(screen.height(), None);
+        for (y, time) in row_times.iter_mut().enumerate() {
+            if row_text(screen.row(y)).is_empty() {
+                *time = None;
+            } else if time.is_none() {
+                *time = Some(event.timestamp);
+            }
         }
-    } else if args.clone {
-        if let Some(font_name) = query_terminal_font() {
-            eprintln!("Terminal font detected: {}", font_name);
-            if let Some(ttf_font) = Font::from_system_font(&font_name, args.font_size) {
-                eprintln!("Loaded TrueType font: {} (cell size: {}x{})", font_name, ttf_font.width(), ttf_font.height());
-                Rasterizer::with_font(ttf_font)
-            } else {
-                eprintln!("Could not load font '{}', falling back to embedded font", font_name);
-                Rasterizer::new(args.font.as_deref())
+    }
+
+    let screen = terminal.main_grid();
+    let mut lines: Vec<(Option<f64>, String)> = terminal.scrollback().map(row_text)
+        .zip(scrolled_times)
+        .map(|(text, time)| (time, text))
+        .chain((0..screen.height()).map(|y| (row_times.get(y).copied().flatten(), row_text(screen.row(y)))))
+        .collect();
+    while lines.last().is_some_and(|(_, text)| text.is_empty()) {
+        lines.pop();
+    }
+
+    let mut content = String::new();
+    for (time, text) in &lines {
+        if timestamps {
+            match time {
+                Some(t) => content.push_str(&format!("[{:>8.2}] ", t)),
+                None => content.push_str(&" ".repeat(11)),
             }
-        } else {
-            eprintln!("Could not detect terminal font, using embedded font");
-            Rasterizer::new(args.font.as_deref())
         }
-    } else {
-        Rasterizer::new(args.font.as_deref())
-    };
+        content.push_str(text);
+        content.push('\n');
+    }
 
-    let (term_pixel_width, term_pixel_height) = rasterizer.canvas_size(width, height);
+    std::fs::write(output, content)
+        .with_context(|| format!("Failed to write transcript: {}", output.display()))?;
+    println!("✓ Transcript created: {} ({} lines)", output.display(), lines.len());
 
-    // Apply theme padding
-    let (padding_left, padding_top, padding_right, padding_bottom) = if let Some(ref padding) = theme.padding {
-        (padding.left as usize, padding.top as usize, padding.right as usize, padding.bottom as usize)
+    Ok(())
+}
+
+/// Replay a recording through the emulator only and report anything it could not handle
+fn check_recording(args: &cli::Args, input: Option<PathBuf>) -> Result<()> {
+    let mut input_source: Box<dyn InputSource> = if let Some(ref path) = input {
+        Box::new(AsciicastReader::new(path)?)
     } else {
-        (0, 0, 0, 0)
+        Box::new(StdinReader::new(
+            args.columns.unwrap_or(80),
+            args.rows.unwrap_or(25),
+        ))
     };
+    let events = input_source.read_events()?;
+    let metadata = input_source.metadata();
 
-    let pixel_width = term_pixel_width + padding_left + padding_right;
-    let pixel_height = term_pixel_height + padding_top + padding_bottom;
-
-    println!(" - terminal pixel dimensions: {}x{}", term_pixel_width, term_pixel_height);
-    if padding_left > 0 || padding_top > 0 || padding_right > 0 || padding_bottom > 0 {
-        println!(" - padding: L:{} T:{} R:{} B:{}", padding_left, padding_top, padding_right, padding_bottom);
-    }
-    println!(" - final pixel dimensions: {}x{}", pixel_width, pixel_height);
-
-    // Load theme layers (without pre-processing - render per mode in frame loop)
-    let mut layer_renderer = LayerRenderer::new();
-    for layer in &theme.layers {
-        // If theme is embedded, load ONLY from embedded layers (skip filesystem search)
-        // If theme is from filesystem, search filesystem first then fallback to embedded
-        let layer_path = if theme.is_embedded {
-            // Embedded theme: use layer path as-is (LayerImage::load will check embedded layers)
-            PathBuf::from(&layer.file)
-        } else {
-            // External theme: search filesystem locations
-            find_layer_file(&layer.file)
-        };
+    let width = clamp_dimension("columns", args.columns.unwrap_or(metadata.width), args.max_columns)?;
+    let height = clamp_dimension("rows", args.rows.unwrap_or(metadata.height), args.max_rows)?;
+    let max_size = events.iter()
+        .filter_map(|event| event.resize_dimensions())
+        .fold((width, height), |(w, h), (cols, rows)| {
+            (w.max(cols.min(args.max_columns)), h.max(rows.min(args.max_rows)))
+        });
 
-        match LayerImage::load(&layer_path) {
-            Ok(layer_image) => {
-                let anim_info = if layer_image.is_animated {
-                    format!(" [{} frames, animated]", layer_image.frame_count())
-                } else {
-                    String::new()
-                };
-                println!(" - loaded layer: {} ({}x{}) mode={:?} depth={}{}",
-                    layer.file, layer_image.width, layer_image.height, layer.mode, layer.depth, anim_info);
-                layer_renderer.add_layer(layer_image, layer.clone());
-            }
-            Err(e) => {
-                eprintln!("Warning: Failed to load layer image {}: {}", layer.file, e);
-            }
+    println!("Checking {}: {} events, {}x{}",
+        input.as_deref().unwrap_or(std::path::Path::new("stdin")).display(), events.len(), width, height);
+
+    let mut terminal = TerminalEmulator::new(width, height, !args.no_autowrap, 7, 0);
+    terminal.collect_unhandled(true);
+
+    let mut problems = 0;
+    for (i, event) in events.iter().enumerate() {
+        let fed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            feed_event(&mut terminal, event, max_size);
+        }));
+        if fed.is_err() {
+            println!("  ✗ emulator panicked on event {} at {:.3}s", i, event.timestamp);
+            problems += 1;
+            break;
         }
     }
 
-    // Use the palette we already queried earlier (don't query again!)
-    let palette = palette.unwrap(); // Safe because we always set Some() above
+    for (sequence, count) in terminal.unhandled() {
+        println!("  ✗ unsupported sequence {} (seen {} times)", sequence, count);
+        problems += 1;
+    }
 
-    // Use terminal background for canvas fill (overrides theme background)
-    let background_color = term_default_bg.unwrap_or(theme.background);
-    eprintln!("Canvas background color index: {}", background_color);
+    if let Some(column) = terminal.clipped_column() {
+        println!("  ✗ cursor moved to column {} but the terminal is {} columns wide; pass --columns {} to avoid clipping",
+            column, width, column);
+        problems += 1;
+    }
 
-    // Don't use transparency in final GIF - transparency is handled during layer compositing
-    // The final output should be fully opaque with the theme background color
-    let transparent_index = None;
+    if problems > 0 {
+        anyhow::bail!("Check failed: {} problem(s) found", problems);
+    }
+    println!("✓ Recording parsed and emulated cleanly");
+    Ok(())
+}
 
-    let mut encoder = EncoderWrapper::new(
-        &output_path,
-        pixel_width,
-        pixel_height,
-        &palette,
-        output_format,
-        args.r#loop,
-        frame_rate,
-        args.quality.clamp(0, 100),
-        transparent_index,
-    )?;
+/// Replay a recording through the emulator only and print the screen it ends on,
+/// one line per row, for debugging emulation without rendering
+fn print_final_grid(args: &cli::Args, input: Option<PathBuf>) -> Result<()> {
+    for line in final_grid_lines(args, input)? {
+        println!("{}", line);
+    }
+    Ok(())
+}
 
-    // GPU BATCH MODE: Process frames in two passes
-    // Pass 1: Collect all Grid snapshots (~14MB for 282 frames)
-    // Pass 2: Batch render ALL grids at once (ONE GPU sync!)
+/// The rows of the screen a recording ends on, as plain text or (--grid-only ansi) with SGR codes
+fn final_grid_lines(args: &cli::Args, input: Option<PathBuf>) -> Result<Vec<String>> {
+    let mut input_source: Box<dyn InputSource> = if let Some(ref path) = input {
+        Box::new(AsciicastReader::new(path)?)
+    } else {
+        Box::new(StdinReader::new(
+            args.columns.unwrap_or(80),
+            args.rows.unwrap_or(25),
+        ))
+    };
+    let events = input_source.read_events()?;
+    let metadata = input_source.metadata();
 
-    let frame_duration = 1.0 / frame_rate as f64;
-    let delay_centiseconds = (100.0 / frame_rate as f64).round() as u16;
+    let width = clamp_dimension("columns", args.columns.unwrap_or(metadata.width), args.max_columns)?;
+    let height = clamp_dimension("rows", args.rows.unwrap_or(metadata.height), args.max_rows)?;
+    let max_size = (args.max_columns, args.max_rows);
 
-    #[cfg(feature = "gpu")]
-    let use_batch_rendering = rasterizer.is_gpu_available();
-    #[cfg(not(feature = "gpu"))]
-    let use_batch_rendering = false;
+    let mut terminal = TerminalEmulator::new(width, height, !args.no_autowrap, 7, 0);
+    configure_emulation(&mut terminal, args, &metadata);
+    for event in &events {
+        feed_event(&mut terminal, event, max_size);
+    }
 
-    // PASS 1: Collect all grid snapshots
-    let term_canvases: Vec<Canvas> = if use_batch_rendering {
-        let mut grids = Vec::with_capacity(total_frame_count);
-        let mut event_idx = 0;
+    let ansi = args.grid_only.as_deref() == Some("ansi");
+    let default_bg = terminal.state().default_background as u8;
+    let grid = terminal.grid();
+    let lines = (0..grid.height())
+        .map(|y| if ansi { row_ansi(grid.row(y), default_bg) } else { row_text(grid.row(y)) })
+        .collect();
+    Ok(lines)
+}
 
-        for frame_num in 0..total_frame_count {
-            let current_time = frame_num as f64 * frame_duration;
+/// Convert a recording into every path in `outputs` from a single render pass.
+/// The first output honours --format; the rest are typed by extension.
+/// With no outputs a filename is generated.
+fn convert_recording(args: &cli::Args, input: Option<PathBuf>, outputs: &[PathBuf]) -> Result<()> {
+    let output = outputs.first().cloned();
+    let output_format = convert::output_format(args, output.as_deref())?;
 
-            // Process all events up to current time (only for non-trailer frames)
-            if frame_num < frame_count {
-                while event_idx < events.len() && events[event_idx].timestamp <= current_time {
-                    terminal.feed_bytes(&events[event_idx].data);
-                    event_idx += 1;
-                }
+    // Determine output path
+    let output_path = if let Some(path) = output {
+        path
+    } else if let Some(ref template) = args.output_template {
+        // Expand user-supplied naming template
+        let basename = input.as_deref()
+            .and_then(|p| p.file_stem())
+            .and_then(|s| s.to_str())
+            .unwrap_or("stdin");
+        let theme_name = std::path::Path::new(&args.theme)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(&args.theme);
+        let ext = output_format.extension();
+        let mut index = 0;
+        loop {
+            let mut filename = expand_output_template(template, basename, theme_name, index);
+            if std::path::Path::new(&filename).extension().is_none() {
+                filename = format!("{}.{}", filename, ext);
             }
-
-            // Clone the grid snapshot (Grid is cheap to clone - just Vec<Cell> where Cell is Copy)
-            grids.push(terminal.grid().clone());
-        }
-
-        // PASS 2: GPU BATCH RENDER (ONE sync for ALL frames!)
-        #[cfg(feature = "gpu")]
-        {
-            let batch_result = rasterizer.render_grids_batch(&grids);
-            match batch_result {
-                Ok(canvases) => {
-                    canvases
-                }
-                Err(e) => {
-                    eprintln!("GPU batch render failed: {}, falling back to frame-by-frame", e);
-                    // Fallback: render each grid individually
-                    grids.iter().map(|grid| {
-                        if !args.no_cursor && terminal.state().display_cursor {
-                            let (cursor_x, cursor_y) = terminal.state().cursor_get_position();
-                            rasterizer.render_grid_with_cursor(grid, cursor_x as usize, cursor_y as usize)
-                        } else {
-                            rasterizer.render_grid(grid)
-                        }
-                    }).collect()
-                }
+            if !template.contains("{index}") || !std::path::Path::new(&filename).exists() {
+                break PathBuf::from(filename);
+            }
+            index += 1;
+            if index >= 10000 {
+                anyhow::bail!("No available output filenames for template '{}'", template);
             }
-        }
-        #[cfg(not(feature = "gpu"))]
-        {
-            vec![] // Won't be used
         }
     } else {
-        // CPU MODE: Render frame-by-frame as before
-        vec![] // We'll render in the loop below
+        // Auto-generate filename with proper extension
+        let ext = output_format.extension();
+        let mut index = 0;
+        loop {
+            let filename = format!("ttyvid-{:04}.{}", index, ext);
+            if !std::path::Path::new(&filename).exists() {
+                break PathBuf::from(filename);
+            }
+            index += 1;
+            if index >= 10000 {
+                anyhow::bail!("No available output filenames (ttyvid-0000.{} to ttyvid-9999.{} all exist)", ext, ext);
+            }
+        }
     };
 
-    // PASS 3: Composite with layers and encode
-    let start_time = std::time::Instant::now();
-    let mut event_idx = 0;
-    for frame_num in 0..total_frame_count {
-        let current_time = frame_num as f64 * frame_duration;
-
-        // Get or render terminal canvas
-        let term_canvas = if use_batch_rendering {
-            // Use pre-rendered canvas from batch
-            term_canvases[frame_num].clone()
-        } else {
-            // CPU path: process events and render frame-by-frame
-            if frame_num < frame_count {
-                while event_idx < events.len() && events[event_idx].timestamp <= current_time {
-                    terminal.feed_bytes(&events[event_idx].data);
-                    event_idx += 1;
-                }
-            }
+    let mut targets = vec![(output_path, output_format)];
+    for path in outputs.iter().skip(1) {
+        let format = OutputFormat::from_path(path)
+            .ok_or_else(|| anyhow::anyhow!("Unknown output format for {}", path.display()))?;
+        targets.push((path.clone(), format));
+    }
 
-            if !args.no_cursor && terminal.state().display_cursor {
-                let (cursor_x, cursor_y) = terminal.state().cursor_get_position();
-                rasterizer.render_grid_with_cursor(
-                    terminal.grid(),
-                    cursor_x as usize,
-                    cursor_y as usize
-                )
-            } else {
-                rasterizer.render_grid(terminal.grid())
-            }
-        };
+    // Read input events
+    let mut input_source: Box<dyn InputSource> = if let Some(ref path) = input {
+        Box::new(AsciicastReader::new(path)?)
+    } else {
+        Box::new(StdinReader::new(
+            args.columns.unwrap_or(80),
+            args.rows.unwrap_or(25),
+        ))
+    };
 
-        // Create final canvas with padding
-        let mut canvas = Canvas::new(pixel_width, pixel_height, &palette);
+    let events = input_source.read_events()?;
+    let metadata = input_source.metadata();
+    let input_label = input.as_deref().map_or_else(|| "stdin".to_string(), |path| path.display().to_string());
 
-        // Fill with background color (terminal bg overrides theme bg)
-        for y in 0..pixel_height {
-            for x in 0..pixel_width {
-                canvas.set_pixel(x, y, background_color);
-            }
-        }
+    convert::convert_to_targets(args, &input_label, events, metadata, &targets)
+}
 
-        // Convert current time to milliseconds for animation
-        let current_time_ms = current_time * 1000.0;
+/// Page title for generated documents: the output's file name
+fn recording_title(base_path: &std::path::Path) -> String {
+    base_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("Terminal Recording")
+        .to_string()
+}
 
-        // Render underlay layers (depth < 0)
-        layer_renderer.render_underlays(&mut canvas, palette.colors(), current_time_ms);
+/// Markdown page for a recording: the built-in layout, or `template` with {title},
+/// {gif}, {webm}, {cast}, {version} and {repository} filled in (a format that was
+/// not generated leaves its token empty)
+fn generate_markdown(base_path: &std::path::Path, formats: &[String], md_file: &std::path::Path, template: Option<&std::path::Path>) -> Result<()> {
+    let title = recording_title(base_path);
+    let file_name = |format: &str| -> String {
+        if formats.iter().any(|f| f == format) {
+            base_path.with_extension(format).file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string()
+        } else {
+            String::new()
+        }
+    };
+    let (gif, webm, cast) = (file_name("gif"), file_name("webm"), file_name("cast"));
+
+    let content = if let Some(template_path) = template {
+        std::fs::read_to_string(template_path)
+            .with_context(|| format!("Failed to read markdown template: {}", template_path.display()))?
+            .replace("{title}", &title)
+            .replace("{gif}", &gif)
+            .replace("{webm}", &webm)
+            .replace("{cast}", &cast)
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+            .replace("{repository}", env!("CARGO_PKG_REPOSITORY"))
+    } else {
+        let mut content = format!("# {}\n\n", title);
 
-        // Composite terminal output onto canvas with padding offset
-        for y in 0..term_pixel_height {
-            for x in 0..term_pixel_width {
-                if let Some(color) = term_canvas.get_pixel(x, y) {
-                    canvas.set_pixel(x + padding_left, y + padding_top, color);
-                }
-            }
+        // Embed the GIF or WebM if available
+        if !gif.is_empty() {
+            content.push_str(&format!("![{}]({})\n\n", title, gif));
+        } else if !webm.is_empty() {
+            content.push_str(&format!("<video src=\"{}\" controls></video>\n\n", webm));
         }
 
-        // Render title text if provided
-        if let Some(ref title_text) = args.title {
-            if !title_text.is_empty() {
-                if let Some(ref title_config) = theme.title {
-                    rasterizer.render_title(
-                        &mut canvas,
-                        title_config.x,
-                        title_config.y,
-                        title_text,
-                        title_config.foreground,
-                        title_config.background,
-                        title_config.font_size,
-                    );
-                }
-            }
+        // Add link to .cast file if available
+        if !cast.is_empty() {
+            content.push_str("## Files\n\n");
+            content.push_str(&format!("- [Asciinema recording]({})\n", cast));
         }
 
-        // Render overlay layers (depth >= 0)
-        layer_renderer.render_overlays(&mut canvas, palette.colors(), current_time_ms);
+        content.push_str(&format!("\n---\n\nGenerated with [ttyvid]({}) v{}\n",
+            env!("CARGO_PKG_REPOSITORY"), env!("CARGO_PKG_VERSION")));
+        content
+    };
 
-        // Add frame to GIF
-        encoder.add_frame(&canvas, delay_centiseconds)?;
+    std::fs::write(md_file, content)
+        .with_context(|| format!("Failed to write markdown: {}", md_file.display()))
+}
 
-        // Progress indicator with ETA
-        let percent = ((frame_num + 1) as f64 / total_frame_count as f64 * 100.0) as usize;
-        let status = if frame_num >= frame_count {
-            format!("[TRAILER {}/{}]", frame_num - frame_count + 1, trailer_frame_count)
-        } else {
-            format!("{} of {:.2}s", (current_time as f32).min(duration as f32), duration)
-        };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Calculate ETA
-        let elapsed = start_time.elapsed().as_secs_f64();
-        let eta_str = if frame_num > 0 {
-            let frames_remaining = total_frame_count - (frame_num + 1);
-            let seconds_per_frame = elapsed / (frame_num + 1) as f64;
-            let eta_seconds = frames_remaining as f64 * seconds_per_frame;
-            if eta_seconds < 60.0 {
-                format!("ETA: {:.1}s", eta_seconds)
-            } else {
-                format!("ETA: {:.1}m", eta_seconds / 60.0)
-            }
-        } else {
-            "ETA: --".to_string()
-        };
+    #[test]
+    fn output_template_substitutes_tokens() {
+        assert_eq!(expand_output_template("demo-{theme}.gif", "session", "mac", 0), "demo-mac.gif");
+        assert_eq!(expand_output_template("{basename}-{index}", "session", "mac", 7), "session-0007");
+    }
 
-        print!("\r  {} {}% Frame: {}/{} {} FPS {}       ",
-            status,
-            percent,
-            frame_num + 1,
-            total_frame_count,
-            frame_rate,
-            eta_str
-        );
-        use std::io::Write;
-        std::io::stdout().flush()?;
+    #[test]
+    fn transcript_lists_lines_in_order() {
+        let dir = std::env::temp_dir();
+        let cast = dir.join(format!("ttyvid-transcript-{}.cast", std::process::id()));
+        let text = dir.join(format!("ttyvid-transcript-{}.txt", std::process::id()));
+        std::fs::write(&cast, concat!(
+            "{\"version\": 2, \"width\": 10, \"height\": 3}\n",
+            "[0.5, \"o\", \"hello\\r\\n\"]\n",
+            "[1.0, \"o\", \"world\\r\\n\"]\n",
+        )).unwrap();
+
+        transcript_recording(&cli::Args::default(), &cast, &text, false).unwrap();
+        let transcript = std::fs::read_to_string(&text).unwrap();
+        std::fs::remove_file(&cast).unwrap();
+        std::fs::remove_file(&text).unwrap();
+        assert_eq!(transcript, "hello\nworld\n");
     }
 
-    println!();
+    #[test]
+    fn check_reports_unsupported_sequences() {
+        let dir = std::env::temp_dir();
+        let cast = |name: &str, output: &str| {
+            let path = dir.join(format!("ttyvid-check-{}-{}.cast", name, std::process::id()));
+            let event = serde_json::to_string(&(0.5, "o", output)).unwrap();
+            std::fs::write(&path, format!("{{\"version\": 2, \"width\": 10, \"height\": 3}}\n{}\n", event)).unwrap();
+            path
+        };
+        let clean = cast("clean", "hello\r\n");
+        let unsupported = cast("unsupported", "\x1b[1xhello");
 
-    // Finish encoding
-    encoder.finish()?;
+        let clean_result = check_recording(&cli::Args::default(), Some(clean.clone()));
+        let unsupported_result = check_recording(&cli::Args::default(), Some(unsupported.clone()));
+        std::fs::remove_file(&clean).unwrap();
+        std::fs::remove_file(&unsupported).unwrap();
 
-    let total_time = start_time.elapsed();
-    let time_str = if total_time.as_secs() < 60 {
-        format!("{:.1}s", total_time.as_secs_f64())
-    } else {
-        let minutes = total_time.as_secs() / 60;
-        let seconds = total_time.as_secs() % 60;
-        format!("{}m {}s", minutes, seconds)
-    };
+        assert!(clean_result.is_ok());
+        assert!(unsupported_result.unwrap_err().to_string().contains("1 problem"));
+    }
 
-    println!("\n✓ {:?} created: {} (total time: {})", output_format, output_path.display(), time_str);
+    #[test]
+    fn check_reports_content_past_the_header_width() {
+        let path = std::env::temp_dir().join(format!("ttyvid-check-wide-{}.cast", std::process::id()));
+        let event = serde_json::to_string(&(0.5, "o", "\x1b[1;120Hx")).unwrap();
+        std::fs::write(&path, format!("{{\"version\": 2, \"width\": 80, \"height\": 3}}\n{}\n", event)).unwrap();
+        let result = check_recording(&cli::Args::default(), Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("1 problem"));
+        // An explicit width that fits clears it
+        std::fs::write(&path, format!("{{\"version\": 2, \"width\": 80, \"height\": 3}}\n{}\n", event)).unwrap();
+        let args = cli::Args { columns: Some(120), ..cli::Args::default() };
+        let result = check_recording(&args, Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok(), "{:?}", result);
+    }
 
-    Ok(())
-}
+    #[test]
+    fn markdown_template_tokens_are_filled_in() {
+        let dir = std::env::temp_dir();
+        let base = dir.join(format!("ttyvid-md-{}", std::process::id()));
+        let template = dir.join(format!("ttyvid-md-{}.template", std::process::id()));
+        let (md, default_md) = (base.with_extension("md"), base.with_extension("default.md"));
+        std::fs::write(&template, "# {title}\n![demo]({gif})\n[{webm}]({cast})\n{repository}\n").unwrap();
+        let formats = ["gif".to_string(), "cast".to_string()];
+
+        generate_markdown(&base, &formats, &md, Some(&template)).unwrap();
+        generate_markdown(&base, &formats, &default_md, None).unwrap();
+        let (page, default_page) = (std::fs::read_to_string(&md).unwrap(), std::fs::read_to_string(&default_md).unwrap());
+        for path in [&template, &md, &default_md] {
+            std::fs::remove_file(path).unwrap();
+        }
 
-fn generate_markdown(base_path: &PathBuf, formats: &[String], md_file: &PathBuf) -> Result<()> {
-    use std::fs::File;
-    use std::io::Write;
+        let name = base.file_name().unwrap().to_str().unwrap();
+        assert_eq!(
+            page,
+            format!("# {name}\n![demo]({name}.gif)\n[]({name}.cast)\nhttps://github.com/watkinslabs/ttyvid\n")
+        );
+        assert!(default_page.contains("https://github.com/watkinslabs/ttyvid"));
+        assert!(!default_page.contains("ndonald2"));
+    }
 
-    let mut content = String::new();
+    #[test]
+    fn grid_only_lists_the_final_screen() {
+        let cast = std::env::temp_dir().join(format!("ttyvid-grid-only-{}.cast", std::process::id()));
+        std::fs::write(&cast, concat!(
+            "{\"version\": 2, \"width\": 20, \"height\": 4}\n",
+            "[0.5, \"o\", \"hello\\u001b[1;11Hcol10\\r\\n\"]\n",
+            "[1.0, \"o\", \"  \\u001b[31mred\\u001b[0m x\"]\n",
+        )).unwrap();
+
+        let lines = |mode: &str| {
+            let args = cli::Args { grid_only: Some(mode.to_string()), ..cli::Args::default() };
+            final_grid_lines(&args, Some(cast.clone())).unwrap()
+        };
+        let (text, ansi) = (lines("plain"), lines("ansi"));
+        std::fs::remove_file(&cast).unwrap();
 
-    // Get the base filename for the title
-    let title = base_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("Terminal Recording");
-
-    content.push_str(&format!("# {}\n\n", title));
-
-    // Embed the GIF or WebM if available
-    if formats.contains(&"gif".to_string()) {
-        let gif_name = base_path.with_extension("gif");
-        let gif_filename = gif_name.file_name().and_then(|n| n.to_str()).unwrap_or("output.gif");
-        content.push_str(&format!("![{}]({})\n\n", title, gif_filename));
-    } else if formats.contains(&"webm".to_string()) {
-        let webm_name = base_path.with_extension("webm");
-        let webm_filename = webm_name.file_name().and_then(|n| n.to_str()).unwrap_or("output.webm");
-        content.push_str(&format!("<video src=\"{}\" controls></video>\n\n", webm_filename));
+        assert_eq!(text, ["hello     col10", "  red x", "", ""]);
+        assert!(ansi[1].contains("\x1b[") && ansi[1].contains("red"));
+        assert_eq!(ansi[2..], ["", ""]);
     }
 
-    // Add link to .cast file if available
-    if formats.contains(&"cast".to_string()) {
-        let cast_name = base_path.with_extension("cast");
-        let cast_filename = cast_name.file_name().and_then(|n| n.to_str()).unwrap_or("output.cast");
-        content.push_str(&format!("## Files\n\n"));
-        content.push_str(&format!("- [Asciinema recording]({})\n", cast_filename));
+    fn screen(text: &str) -> Grid {
+        let mut terminal = TerminalEmulator::new(8, 2, true, 7, 0);
+        terminal.feed_bytes(text.as_bytes());
+        terminal.grid().clone()
     }
 
-    content.push_str(&format!("\n---\n\nGenerated with [ttyvid](https://github.com/ndonald2/ttyvid) v{}\n", env!("CARGO_PKG_VERSION")));
-
-    let mut file = File::create(md_file)?;
-    file.write_all(content.as_bytes())?;
-
-    Ok(())
-}
-
-fn remove_gaps(events: &mut [input::Event]) {
-    if events.is_empty() {
-        return;
+    #[test]
+    fn diff_tints_only_the_differing_cell() {
+        let mut overlay = screen("cargo ok\r\ndone");
+        let other = screen("cargo ok\r\nDone");
+
+        assert!(tint_differences(&mut overlay, &other));
+        let tinted: Vec<(usize, usize)> = (0..2)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| overlay.get_cell(x, y).unwrap().bg_color == DIFF_TINT_COLOR)
+            .collect();
+        assert_eq!(tinted, vec![(0, 1)]);
+        assert_eq!(overlay.get_cell(0, 1).unwrap().character, 'd');
+
+        let mut same = screen("cargo ok");
+        assert!(!tint_differences(&mut same, &screen("cargo ok")));
     }
 
-    let mut prev_time = 0.0;
-    let mut gap_offset = 0.0;
-
-    for event in events.iter_mut() {
-        let gap = event.timestamp - prev_time;
-        if gap > 1.0 {
-            gap_offset += gap - 1.0;
-        }
-        event.timestamp -= gap_offset;
-        prev_time = event.timestamp;
+    #[test]
+    fn contact_sheet_tiles_frames_in_a_grid() {
+        let dir = std::env::temp_dir();
+        let cast = dir.join(format!("ttyvid-sheet-{}.cast", std::process::id()));
+        let png = dir.join(format!("ttyvid-sheet-{}.png", std::process::id()));
+        std::fs::write(&cast, concat!(
+            "{\"version\": 2, \"width\": 10, \"height\": 3}\n",
+            "[0.5, \"o\", \"one\\r\\n\"]\n",
+            "[1.5, \"o\", \"two\\r\\n\"]\n",
+            "[2.5, \"o\", \"three\"]\n",
+        )).unwrap();
+
+        contact_sheet(&cli::Args::default(), &cast, &png, &[1.0, 2.0, 3.0, 4.0], None, Some(2)).unwrap();
+        let (width, height) = image::image_dimensions(&png).unwrap();
+        std::fs::remove_file(&cast).unwrap();
+        std::fs::remove_file(&png).unwrap();
+
+        let font = Font::load(None);
+        let (tile_width, tile_height) = (10 * font.width(), 4 * font.height());
+        assert_eq!(width as usize, 2 * (tile_width + CONTACT_SHEET_GAP) + CONTACT_SHEET_GAP);
+        assert_eq!(height as usize, 2 * (tile_height + CONTACT_SHEET_GAP) + CONTACT_SHEET_GAP);
     }
 }
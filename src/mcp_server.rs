@@ -294,6 +294,11 @@ impl ServerHandler for TtyvidServerHandler {
                         "type": "string",
                         "description": "Theme to use for all conversions",
                         "default": "default"
+                    },
+                    "output_template": {
+                        "type": "string",
+                        "description": "Output filename template with {basename}, {date}, {theme}, {index} tokens",
+                        "default": "{basename}"
                     }
                 }),
             ),
@@ -785,6 +790,7 @@ async fn handle_batch_convert(args: JsonValue) -> Result<CallToolResult, CallToo
 
     let format = args["format"].as_str().unwrap_or("gif");
     let theme = args["theme"].as_str().unwrap_or("default");
+    let output_template = args["output_template"].as_str().unwrap_or("{basename}");
 
     // Create output directory if it doesn't exist
     use tokio::fs;
@@ -798,13 +804,14 @@ async fn handle_batch_convert(args: JsonValue) -> Result<CallToolResult, CallToo
     let mut success_count = 0;
     let mut error_count = 0;
 
-    for input_val in inputs {
+    for (index, input_val) in inputs.iter().enumerate() {
         if let Some(input) = input_val.as_str() {
             let input_path = Path::new(input);
-            let filename = input_path
+            let basename = input_path
                 .file_stem()
                 .and_then(|s| s.to_str())
                 .unwrap_or("output");
+            let filename = crate::expand_output_template(output_template, basename, theme, index);
 
             let output = Path::new(output_dir)
                 .join(format!("{}.{}", filename, format));
@@ -0,0 +1,153 @@
+use anyhow::Result;
+use std::path::Path;
+use image::{ImageBuffer, Rgb, RgbImage};
+use crate::theme::Theme;
+use crate::font_tools::Font;
+use super::Palette;
+
+// Swatch geometry: the top block shows the color as a background, the strip
+// underneath shows the index drawn in the color as a foreground
+const SWATCH_WIDTH: usize = 40;
+const BG_HEIGHT: usize = 22;
+const FG_HEIGHT: usize = 18;
+const SWATCH_HEIGHT: usize = BG_HEIGHT + FG_HEIGHT;
+const GAP: usize = 2;
+const PADDING: usize = 10;
+const HEADER_HEIGHT: usize = 40;
+const SECTION_GAP: usize = 16;
+
+// Columns in the widest section: three 6x6 blocks of the color cube side by side
+const GRID_COLUMNS: usize = 18;
+
+const CARD_BACKGROUND: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// Render the 256-color palette in the standard ANSI layout: the 16 base colors
+/// (normal over bright), the 6x6x6 color cube as six 6x6 blocks, and the
+/// 24-step grayscale ramp
+pub fn generate_ansi_card(theme: &Theme, output: &Path) -> Result<()> {
+    let palette = if let Some(ref theme_palette) = theme.palette {
+        Palette::from_theme(theme_palette)
+    } else {
+        Palette::default()
+    };
+    let font = Font::load(None);
+
+    let width = PADDING * 2 + GRID_COLUMNS * (SWATCH_WIDTH + GAP);
+    let height = row_y(16) + PADDING;
+    let mut img = ImageBuffer::from_pixel(width as u32, height as u32, CARD_BACKGROUND);
+
+    draw_text(&mut img, &font, PADDING, 12, &format!("ANSI 256: {}", theme.name), Rgb([255, 255, 255]));
+
+    let (dr, dg, db) = palette.get_rgb(theme.default_background);
+    let strip_background = Rgb([dr, dg, db]);
+
+    for (index, x, y) in swatch_origins() {
+        let (r, g, b) = palette.get_rgb(index as u8);
+        let color = Rgb([r, g, b]);
+        let label = index.to_string();
+        let label_x = x + SWATCH_WIDTH.saturating_sub(label.len() * font.width()) / 2;
+
+        fill_rect(&mut img, x, y, SWATCH_WIDTH, BG_HEIGHT, color);
+        let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        let label_color = if luminance > 128.0 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) };
+        draw_text(&mut img, &font, label_x, y + BG_HEIGHT.saturating_sub(font.height()) / 2, &label, label_color);
+
+        fill_rect(&mut img, x, y + BG_HEIGHT, SWATCH_WIDTH, FG_HEIGHT, strip_background);
+        draw_text(&mut img, &font, label_x, y + BG_HEIGHT + FG_HEIGHT.saturating_sub(font.height()) / 2, &label, color);
+    }
+
+    img.save(output)?;
+
+    println!("\n✓ ANSI color card created: {}", output.display());
+    println!("  Theme: {}", theme.name);
+    println!("  Layout: 16 base colors | 6x6x6 cube | 24 grays");
+
+    Ok(())
+}
+
+/// (color index, x, y) of each swatch's top-left corner
+fn swatch_origins() -> Vec<(usize, usize, usize)> {
+    // (color index, column, row) for each swatch, rows counted across all sections
+    let mut swatches = Vec::with_capacity(256);
+    for i in 0..16 {
+        swatches.push((i, i % 8, i / 8));
+    }
+    for i in 0..216 {
+        let (red, green, blue) = (i / 36, (i / 6) % 6, i % 6);
+        swatches.push((16 + i, (red % 3) * 6 + blue, 2 + (red / 3) * 6 + green));
+    }
+    for i in 0..24 {
+        swatches.push((232 + i, i % 12, 14 + i / 12));
+    }
+
+    swatches
+        .into_iter()
+        .map(|(index, column, row)| (index, PADDING + column * (SWATCH_WIDTH + GAP), row_y(row)))
+        .collect()
+}
+
+/// Top of a swatch row; section boundaries (the first row of each) get extra spacing
+fn row_y(row: usize) -> usize {
+    let sections_before = [2, 14].iter().filter(|&&start| row >= start).count();
+    HEADER_HEIGHT + row * (SWATCH_HEIGHT + GAP) + sections_before * SECTION_GAP
+}
+
+fn fill_rect(img: &mut RgbImage, x: usize, y: usize, width: usize, height: usize, color: Rgb<u8>) {
+    for py in y..(y + height).min(img.height() as usize) {
+        for px in x..(x + width).min(img.width() as usize) {
+            img.put_pixel(px as u32, py as u32, color);
+        }
+    }
+}
+
+fn draw_text(img: &mut RgbImage, font: &Font, x: usize, y: usize, text: &str, color: Rgb<u8>) {
+    for (char_idx, c) in text.chars().enumerate() {
+        let char_x = x + char_idx * font.width();
+        let glyph = font.get_glyph_utf8(c);
+
+        for py in 0..font.height() {
+            for px in 0..font.width() {
+                let glyph_idx = py * font.width() + px;
+                if glyph_idx < glyph.len() && glyph[glyph_idx] > 25 {
+                    let img_x = (char_x + px) as u32;
+                    let img_y = (y + py) as u32;
+                    if img_x < img.width() && img_y < img.height() {
+                        img.put_pixel(img_x, img_y, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_has_a_swatch_for_every_color() {
+        let theme = Theme::default();
+        let palette = Palette::default();
+        let path = std::env::temp_dir().join(format!("ttyvid-ansi-card-{}.png", std::process::id()));
+        generate_ansi_card(&theme, &path).unwrap();
+        let img = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).ok();
+
+        let origins = swatch_origins();
+        assert_eq!(origins.len(), 256);
+
+        // Swatches cover disjoint regions
+        for (i, &(_, ax, ay)) in origins.iter().enumerate() {
+            for &(_, bx, by) in &origins[i + 1..] {
+                let apart = ax.abs_diff(bx) >= SWATCH_WIDTH || ay.abs_diff(by) >= SWATCH_HEIGHT;
+                assert!(apart, "swatches at ({ax},{ay}) and ({bx},{by}) overlap");
+            }
+        }
+
+        // The corner of each swatch, clear of its label, shows that swatch's color
+        for (index, x, y) in origins {
+            let (r, g, b) = palette.get_rgb(index as u8);
+            assert_eq!(*img.get_pixel(x as u32, y as u32), Rgb([r, g, b]), "swatch {index}");
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{bail, Context, Result};
+use std::fmt::Write as _;
+use std::path::Path;
+use crate::theme::Theme;
+use super::Palette;
+
+/// File formats a theme palette can be exported to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteFormat {
+    /// GIMP palette (.gpl), also read by Inkscape and Krita
+    Gpl,
+    /// One #rrggbb code per line
+    Hex,
+    /// {"name": ..., "colors": ["#rrggbb", ...]}
+    Json,
+}
+
+impl PaletteFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "gpl" | "gimp" => Some(Self::Gpl),
+            "hex" | "txt" => Some(Self::Hex),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Write the theme's palette (the 256-color default when it has none) in the given format
+pub fn export_palette(theme: &Theme, output: &Path, format: PaletteFormat) -> Result<()> {
+    std::fs::write(output, palette_text(theme, format))
+        .with_context(|| format!("Failed to write palette: {}", output.display()))?;
+
+    println!("\n✓ Palette exported: {}", output.display());
+    println!("  Theme: {}", theme.name);
+    println!("  Format: {:?}", format);
+
+    Ok(())
+}
+
+pub fn palette_text(theme: &Theme, format: PaletteFormat) -> String {
+    // Themes with reduced palettes export only the colors they define
+    let (palette, count) = match theme.palette {
+        Some(ref theme_palette) => (Palette::from_theme(theme_palette), theme_palette.rgb.len().clamp(1, 256)),
+        None => (Palette::default(), 256),
+    };
+    let colors = &palette.rgb_colors()[..count];
+    let hex = |[r, g, b]: [u8; 3]| format!("#{:02x}{:02x}{:02x}", r, g, b);
+
+    let mut text = String::new();
+    match format {
+        PaletteFormat::Gpl => {
+            let _ = writeln!(text, "GIMP Palette");
+            let _ = writeln!(text, "Name: {}", theme.name);
+            let _ = writeln!(text, "Columns: 16");
+            let _ = writeln!(text, "#");
+            for (index, &[r, g, b]) in colors.iter().enumerate() {
+                let _ = writeln!(text, "{:3} {:3} {:3}\tIndex {}", r, g, b, index);
+            }
+        }
+        PaletteFormat::Hex => {
+            for &color in colors {
+                let _ = writeln!(text, "{}", hex(color));
+            }
+        }
+        PaletteFormat::Json => {
+            let document = serde_json::json!({
+                "name": theme.name,
+                "colors": colors.iter().map(|&color| hex(color)).collect::<Vec<_>>(),
+            });
+            let _ = writeln!(text, "{:#}", document);
+        }
+    }
+    text
+}
+
+/// Read a palette written by export-palette or another tool: GIMP .gpl, JSON
+/// (an object with a "colors" array, or a bare array) or one hex code per line
+pub fn load_palette_file(path: &Path) -> Result<Palette> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read palette: {}", path.display()))?;
+    let colors = parse_palette(&text)
+        .with_context(|| format!("Invalid palette file: {}", path.display()))?;
+    Ok(Palette::from_rgb(&colors))
+}
+
+pub fn parse_palette(text: &str) -> Result<Vec<[u8; 3]>> {
+    let trimmed = text.trim_start();
+    let colors = if trimmed.starts_with("GIMP Palette") {
+        parse_gpl(trimmed)?
+    } else if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        parse_json(trimmed)?
+    } else {
+        trimmed
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with("//"))
+            .map(parse_hex)
+            .collect::<Result<Vec<_>>>()?
+    };
+    if colors.is_empty() {
+        bail!("no colors found");
+    }
+    Ok(colors)
+}
+
+/// GIMP palette: header lines, then "R G B [name]" rows; '#' starts a comment
+fn parse_gpl(text: &str) -> Result<Vec<[u8; 3]>> {
+    let mut colors = Vec::new();
+    for (number, line) in text.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+        let channels: Vec<u8> = line
+            .split_whitespace()
+            .take(3)
+            .map(|value| value.parse::<u8>())
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("line {}: expected \"R G B\" values 0-255", number + 1))?;
+        if channels.len() != 3 {
+            bail!("line {}: expected \"R G B\" values 0-255", number + 1);
+        }
+        colors.push([channels[0], channels[1], channels[2]]);
+    }
+    Ok(colors)
+}
+
+fn parse_json(text: &str) -> Result<Vec<[u8; 3]>> {
+    let document: serde_json::Value = serde_json::from_str(text)?;
+    let list = match document.get("colors") {
+        Some(colors) => colors,
+        None => &document,
+    };
+    let entries = list.as_array().context("expected a \"colors\" array of hex strings")?;
+    entries
+        .iter()
+        .map(|entry| entry.as_str().context("colors must be hex strings").and_then(parse_hex))
+        .collect()
+}
+
+/// "#rrggbb" or "rrggbb"
+fn parse_hex(code: &str) -> Result<[u8; 3]> {
+    let digits = code.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(digits, 16).ok().filter(|_| digits.len() == 6)
+        .with_context(|| format!("'{}' is not a #rrggbb color", code))?;
+    Ok([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_theme_exports_a_gimp_palette() {
+        let theme = Theme::load_by_name("default").unwrap();
+        let path = std::env::temp_dir().join(format!("ttyvid-export-{}.gpl", std::process::id()));
+        export_palette(&theme, &path, PaletteFormat::Gpl).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = text.lines();
+        assert_eq!(lines.next(), Some("GIMP Palette"));
+        assert_eq!(lines.next(), Some("Name: default"));
+        assert_eq!(lines.next(), Some("Columns: 16"));
+        assert_eq!(lines.next(), Some("#"));
+
+        let colors: Vec<&str> = lines.collect();
+        assert_eq!(colors.len(), 256);
+        for line in colors {
+            let channels: Vec<&str> = line.split_whitespace().take(3).collect();
+            assert!(channels.iter().all(|c| c.parse::<u8>().is_ok()), "bad color line {:?}", line);
+        }
+        assert_eq!(parse_palette(&text).unwrap().len(), 256);
+    }
+}
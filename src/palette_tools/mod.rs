@@ -1,4 +1,6 @@
 mod palette;
 pub mod palette_card;
+pub mod ansi_card;
+pub mod export;
 
 pub use palette::Palette;
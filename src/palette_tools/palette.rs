@@ -16,6 +16,16 @@ impl Palette {
         Self { colors }
     }
 
+    /// Replace the leading default colors with the given ones; indices past the
+    /// end of a short list (e.g. a 16-color scheme) keep their default colors
+    pub fn from_rgb(rgb: &[[u8; 3]]) -> Self {
+        let mut palette = Self::default();
+        for (index, color) in rgb.iter().take(256).enumerate() {
+            palette.colors[index * 3..index * 3 + 3].copy_from_slice(color);
+        }
+        palette
+    }
+
     /// Query the current terminal for its actual color palette
     /// Falls back to default palette for any colors that can't be queried
     /// Returns (palette, default_fg_index, default_bg_index)
@@ -135,6 +145,108 @@ impl Palette {
         rgb
     }
 
+    /// Build a 256-entry lookup mapping each index to the nearest color of its RGB inverse
+    pub fn invert_map(&self) -> Vec<u8> {
+        (0..=255u8)
+            .map(|idx| {
+                let (r, g, b) = self.get_rgb(idx);
+                self.match_color_index(255 - r as i32, 255 - g as i32, 255 - b as i32)
+            })
+            .collect()
+    }
+
+    /// Build a 256-entry lookup mapping each index to the nearest color scaled by `factor`
+    pub fn darken_map(&self, factor: f32) -> Vec<u8> {
+        (0..=255u8)
+            .map(|idx| {
+                let (r, g, b) = self.get_rgb(idx);
+                self.match_color_index(
+                    (r as f32 * factor) as i32,
+                    (g as f32 * factor) as i32,
+                    (b as f32 * factor) as i32,
+                )
+            })
+            .collect()
+    }
+
+    /// Relative luminance (0.0-1.0) of a palette entry, per WCAG
+    pub fn luminance(&self, index: u8) -> f32 {
+        let channel = |c: u8| {
+            let c = c as f32 / 255.0;
+            if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        };
+        let (r, g, b) = self.get_rgb(index);
+        0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+    }
+
+    /// Contrast ratio (1.0-21.0) between two palette entries, per WCAG
+    pub fn contrast_ratio(&self, a: u8, b: u8) -> f32 {
+        let (la, lb) = (self.luminance(a), self.luminance(b));
+        (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+    }
+
+    /// Colors (glyph, block) for a reverse-video cursor over a cell drawn `fg` on `bg`.
+    /// The colors normally just swap; when the block would not stand out from the
+    /// cell background by `min_contrast`, it becomes black or white instead.
+    pub fn cursor_colors(&self, fg: u8, bg: u8, min_contrast: f32) -> (u8, u8) {
+        if self.contrast_ratio(fg, bg) >= min_contrast {
+            return (bg, fg);
+        }
+        let black = self.match_color_index(0, 0, 0);
+        let white = self.match_color_index(255, 255, 255);
+        let block = if self.contrast_ratio(white, bg) >= self.contrast_ratio(black, bg) { white } else { black };
+        (bg, block)
+    }
+
+    /// Reduce to at most `max_colors` entries taken from this palette.
+    /// The `keep` indices are chosen first, then the 16 system colors, then whichever
+    /// remaining color is farthest from everything chosen so far.
+    /// Returns the reduced palette and a 256-entry map from old to new indices.
+    pub fn reduce(&self, max_colors: usize, keep: &[u8]) -> (Palette, Vec<u8>) {
+        let distance = |a: (u8, u8, u8), b: (u8, u8, u8)| {
+            let dr = a.0 as i32 - b.0 as i32;
+            let dg = a.1 as i32 - b.1 as i32;
+            let db = a.2 as i32 - b.2 as i32;
+            dr * dr + dg * dg + db * db
+        };
+        let max_colors = max_colors.max(1);
+        let palette_len = (self.colors.len() / 3).min(256);
+
+        let mut selected: Vec<(u8, u8, u8)> = Vec::with_capacity(max_colors);
+        for &idx in keep {
+            let rgb = self.get_rgb(idx);
+            if selected.len() < max_colors && !selected.contains(&rgb) {
+                selected.push(rgb);
+            }
+        }
+
+        for range in [0..palette_len.min(16), 0..palette_len] {
+            while selected.len() < max_colors {
+                let farthest = range.clone()
+                    .map(|idx| self.get_rgb(idx as u8))
+                    .map(|rgb| (rgb, selected.iter().map(|&s| distance(rgb, s)).min().unwrap_or(i32::MAX)))
+                    .filter(|&(_, d)| d > 0)
+                    .max_by_key(|&(_, d)| d);
+                match farthest {
+                    Some((rgb, _)) => selected.push(rgb),
+                    None => break,
+                }
+            }
+        }
+
+        let map = (0..=255u8)
+            .map(|idx| {
+                let rgb = self.get_rgb(idx);
+                (0..selected.len())
+                    .min_by_key(|&i| distance(rgb, selected[i]))
+                    .unwrap_or(0) as u8
+            })
+            .collect();
+        let colors = selected.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+
+        (Palette { colors }, map)
+    }
+
     // Exact translation of graphics.pyx match_color_index lines 99-120
     pub fn match_color_index(&self, r: i32, g: i32, b: i32) -> u8 {
         let mut last_distance: i32 = -1;
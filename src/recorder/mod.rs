@@ -4,11 +4,14 @@ mod writer;
 
 pub use pty::PtyRecorder;
 pub use capture::CaptureSession;
-pub use writer::CastWriter;
+pub use writer::{recording_end, CastWriter};
 
 use anyhow::Result;
 use std::path::PathBuf;
 
+/// TERM given to the recorded program unless --env sets another
+const DEFAULT_TERM: &str = "xterm-256color";
+
 /// Configuration for recording sessions
 #[derive(Debug, Clone)]
 pub struct RecordConfig {
@@ -36,8 +39,17 @@ pub struct RecordConfig {
     /// Verbose output
     pub verbose: bool,
 
-    /// Environment variables to set
+    /// Environment variables to set (also written to the cast header)
     pub env: Vec<(String, String)>,
+
+    /// Stop after this many seconds, ending the program if it is still running
+    pub duration: Option<f64>,
+
+    /// Add to an existing recording at `output` instead of replacing it
+    pub append: bool,
+
+    /// Also write the raw output bytes to this file
+    pub tee: Option<PathBuf>,
 }
 
 impl Default for RecordConfig {
@@ -52,6 +64,9 @@ impl Default for RecordConfig {
             show_stats: false,  // Disabled by default - interferes with terminal display
             verbose: false,
             env: Vec::new(),
+            duration: None,
+            append: false,
+            tee: None,
         }
     }
 }
@@ -75,11 +90,19 @@ impl Recorder {
                 eprintln!("Press Ctrl+\\ to pause/resume");
             }
 
-            eprintln!("Press Ctrl+D or type 'exit' to stop recording");
+            if self.config.command.is_some() {
+                eprintln!("Recording stops when the command exits");
+            } else {
+                eprintln!("Press Ctrl+D or type 'exit' to stop recording");
+            }
+            if let Some(seconds) = self.config.duration {
+                eprintln!("Recording stops automatically after {}s", seconds);
+            }
             eprintln!();
         } else {
             // Minimal output - just show we're recording
-            eprintln!("● Recording to {} (Ctrl+D to stop)", self.config.output.display());
+            let stop_hint = if self.config.command.is_some() { "stops when the command exits" } else { "Ctrl+D to stop" };
+            eprintln!("● Recording to {} ({})", self.config.output.display(), stop_hint);
         }
 
         // Create PTY recorder
@@ -88,11 +111,18 @@ impl Recorder {
         // Create capture session
         let mut capture = CaptureSession::new(&self.config);
 
-        // Create output writer
-        let mut writer = CastWriter::new(&self.config.output)?;
+        // Create output writer, continuing an existing recording for --append
+        let mut writer = if self.config.append && self.config.output.exists() {
+            CastWriter::append(&self.config.output, self.config.columns, self.config.rows)?
+        } else {
+            CastWriter::new(&self.config.output)?
+        };
+        if let Some(ref tee) = self.config.tee {
+            writer.tee_to(tee, self.config.append)?;
+        }
 
-        // Write header
-        writer.write_header(self.config.columns, self.config.rows)?;
+        // Write header (already present when appending)
+        writer.write_header(self.config.columns, self.config.rows, &self.config.env)?;
 
         // Main recording loop
         pty.run(&mut capture, &mut writer)?;
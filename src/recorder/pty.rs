@@ -1,5 +1,5 @@
 use anyhow::{Context, Result};
-use portable_pty::{CommandBuilder, MasterPty, PtySize, native_pty_system};
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize, native_pty_system};
 use std::io::{Read, Write};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::thread;
@@ -8,9 +8,14 @@ use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 
 use super::{CaptureSession, CastWriter, RecordConfig};
 
+/// After the child exits, output still arriving is recorded until the PTY
+/// has been quiet this long
+const EXIT_DRAIN: Duration = Duration::from_millis(100);
+
 /// PTY-based recorder that handles all terminal I/O
 pub struct PtyRecorder {
     master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
     config: RecordConfig,
 }
 
@@ -60,16 +65,16 @@ impl PtyRecorder {
             cmd.cwd(cwd);
         }
 
+        // Set TERM environment variable (--env TERM=... replaces it)
+        cmd.env("TERM", super::DEFAULT_TERM);
+
         // Set environment variables
         for (key, value) in &config.env {
             cmd.env(key, value);
         }
 
-        // Set TERM environment variable
-        cmd.env("TERM", "xterm-256color");
-
         // Spawn the child process in the PTY slave
-        let _child = pair
+        let child = pair
             .slave
             .spawn_command(cmd)
             .context("Failed to spawn command in PTY")?;
@@ -77,6 +82,7 @@ impl PtyRecorder {
         // We only need the master side - slave is owned by child process
         Ok(Self {
             master: pair.master,
+            child,
             config: config.clone(),
         })
     }
@@ -137,8 +143,26 @@ impl PtyRecorder {
         });
 
         let mut last_stats_update = std::time::Instant::now();
+        let deadline = self.config.duration.map(|seconds| std::time::Instant::now() + Duration::from_secs_f64(seconds));
+        let mut timed_out = false;
+        // Set once the child has exited: the last time PTY output arrived
+        let mut exited_quiet_since: Option<std::time::Instant> = None;
 
         loop {
+            if deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+                timed_out = true;
+                break;
+            }
+
+            // The recording ends with the child, even if a background process it
+            // started still holds the PTY open
+            if exited_quiet_since.is_none() && matches!(self.child.try_wait(), Ok(Some(_))) {
+                exited_quiet_since = Some(std::time::Instant::now());
+            }
+            if exited_quiet_since.is_some_and(|since| since.elapsed() >= EXIT_DRAIN) {
+                break;
+            }
+
             // Check for PTY output
             match pty_rx.try_recv() {
                 Ok(data) => {
@@ -152,6 +176,10 @@ impl PtyRecorder {
 
                     // Write to .cast file
                     writer.write_event(&event)?;
+
+                    if exited_quiet_since.is_some() {
+                        exited_quiet_since = Some(std::time::Instant::now());
+                    }
                 }
                 Err(std::sync::mpsc::TryRecvError::Empty) => {
                     // No data available yet
@@ -185,8 +213,17 @@ impl PtyRecorder {
             thread::sleep(Duration::from_millis(10));
         }
 
-        // Wait for PTY reader thread
-        let _ = pty_thread.join();
+        if timed_out {
+            // SIGHUP first, then a hard kill if the program ignores it
+            let _ = self.child.kill();
+        }
+        if timed_out || exited_quiet_since.is_some() {
+            // The reader thread is left behind: a grandchild may still hold the PTY open
+            drop(pty_thread);
+        } else {
+            // Wait for PTY reader thread
+            let _ = pty_thread.join();
+        }
 
         if self.config.show_stats {
             eprint!("\r\x1b[K"); // Clear stats line
@@ -250,3 +287,64 @@ impl Drop for PtyRecorder {
         let _ = disable_raw_mode();
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Record `command` into a cast in the temp dir without a terminal on stdin,
+    /// returning how long the capture ran and the cast text
+    fn record(name: &str, command: &[&str], duration: Option<f64>, tee: Option<&std::path::Path>) -> (Duration, String) {
+        let path = std::env::temp_dir().join(format!("ttyvid-{}-{}.cast", name, std::process::id()));
+        let config = RecordConfig {
+            output: path.clone(),
+            command: Some(command.iter().map(|arg| arg.to_string()).collect()),
+            duration,
+            ..RecordConfig::default()
+        };
+        let mut recorder = PtyRecorder::new(&config).unwrap();
+        let mut capture = CaptureSession::new(&config);
+        let mut writer = CastWriter::new(&path).unwrap();
+        if let Some(tee) = tee {
+            writer.tee_to(tee, false).unwrap();
+        }
+        writer.write_header(config.columns, config.rows, &config.env).unwrap();
+
+        let (_stdin_tx, stdin_rx) = channel();
+        let started = Instant::now();
+        recorder.io_loop(&mut capture, &mut writer, stdin_rx, false).unwrap();
+        let elapsed = started.elapsed();
+        writer.close().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (elapsed, text)
+    }
+
+    #[test]
+    fn duration_stops_a_running_command() {
+        let (elapsed, text) = record("duration", &["sh", "-c", "echo started; sleep 30"], Some(1.0), None);
+        assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(5), "stopped after {:?}", elapsed);
+        assert!(text.contains("started"));
+    }
+
+    #[test]
+    fn recording_ends_when_the_command_exits() {
+        let (elapsed, text) = record("exit", &["echo", "hi"], None, None);
+        assert!(elapsed < Duration::from_secs(3), "stopped after {:?}", elapsed);
+        assert!(text.lines().skip(1).any(|line| line.contains("hi")));
+    }
+
+    #[test]
+    fn tee_file_gets_the_raw_output_bytes() {
+        let tee = std::env::temp_dir().join(format!("ttyvid-tee-{}.log", std::process::id()));
+        let (_, text) = record("tee", &["printf", "\\033[1mbold\\033[0m\\377\\n"], None, Some(&tee));
+        let raw = std::fs::read(&tee).unwrap();
+        std::fs::remove_file(&tee).unwrap();
+
+        // The PTY turns the newline into CRLF; the invalid UTF-8 byte survives only in the tee
+        assert_eq!(raw, b"\x1b[1mbold\x1b[0m\xff\r\n");
+        assert!(text.contains("\\u001b[1mbold"));
+    }
+}
@@ -1,15 +1,23 @@
 use anyhow::{Context, Result};
 use serde_json::json;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
 use super::capture::{CaptureSession, OutputEvent};
+use crate::input::{AsciicastReader, InputSource};
+
+/// Variables stored in the cast header's `env`, for emulation of the recording later
+const HEADER_ENV_VARS: &[&str] = &["SHELL", "TERM"];
 
 /// Writes asciicast v2 format (.cast files)
 pub struct CastWriter {
     writer: BufWriter<File>,
     header_written: bool,
+    /// Added to every event time: the end of the recording being appended to
+    time_offset: f64,
+    /// --tee: plain log receiving the raw bytes of every output event
+    tee: Option<BufWriter<File>>,
 }
 
 impl CastWriter {
@@ -20,18 +28,71 @@ impl CastWriter {
         Ok(Self {
             writer: BufWriter::new(file),
             header_written: false,
+            time_offset: 0.0,
+            tee: None,
+        })
+    }
+
+    /// Continue an existing recording: new events are timed from its last event,
+    /// and a resize event is written first if the terminal size has changed
+    pub fn append(path: &Path, width: u16, height: u16) -> Result<Self> {
+        let (last_time, size) = recording_end(path)?;
+
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open output file for appending: {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        // Start on a fresh line if the file was cut off mid-line
+        let ends_with_newline = std::fs::read(path).map_or(true, |bytes| bytes.ends_with(b"\n"));
+        if !ends_with_newline {
+            writeln!(writer)?;
+        }
+        if size != (width as usize, height as usize) {
+            writeln!(writer, "{}", json!([last_time, "r", format!("{}x{}", width, height)]))?;
+        }
+
+        Ok(Self {
+            writer,
+            header_written: true,
+            time_offset: last_time,
+            tee: None,
         })
     }
 
-    /// Write asciicast v2 header
-    pub fn write_header(&mut self, width: u16, height: u16) -> Result<()> {
+    /// Also write the raw bytes of every output event to `path`, added to the
+    /// end of it when `append` is set
+    pub fn tee_to(&mut self, path: &Path, append: bool) -> Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .with_context(|| format!("Failed to create tee file: {}", path.display()))?;
+        self.tee = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Write asciicast v2 header, recording the child's SHELL and TERM (as
+    /// overridden by --env, if it sets them)
+    pub fn write_header(&mut self, width: u16, height: u16, env: &[(String, String)]) -> Result<()> {
         if self.header_written {
             return Ok(());
         }
 
-        // Get environment info
+        // Environment as the recorded program sees it; other --env values can hold
+        // tokens and stay out of a file meant to be shared
+        let mut header_env = serde_json::Map::new();
         let shell = std::env::var("SHELL").unwrap_or_else(|_| "unknown".to_string());
-        let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+        header_env.insert("SHELL".to_string(), shell.into());
+        header_env.insert("TERM".to_string(), super::DEFAULT_TERM.into());
+        for (key, value) in env {
+            if HEADER_ENV_VARS.contains(&key.as_str()) {
+                header_env.insert(key.clone(), value.clone().into());
+            }
+        }
 
         // Build header (asciicast v2 format)
         let header = json!({
@@ -39,10 +100,7 @@ impl CastWriter {
             "width": width,
             "height": height,
             "timestamp": CaptureSession::get_start_timestamp(),
-            "env": {
-                "SHELL": shell,
-                "TERM": term,
-            },
+            "env": header_env,
             "title": "ttyvid recording",
         });
 
@@ -67,15 +125,96 @@ impl CastWriter {
         let data_str = String::from_utf8_lossy(&event.data);
 
         // Write event line: [time, "o", data]
-        let event_json = json!([event.timestamp, "o", data_str]);
+        let event_json = json!([self.time_offset + event.timestamp, "o", data_str]);
         writeln!(self.writer, "{}", event_json)?;
 
+        if let Some(ref mut tee) = self.tee {
+            tee.write_all(&event.data)?;
+        }
+
         Ok(())
     }
 
     /// Flush and close the writer
     pub fn close(&mut self) -> Result<()> {
         self.writer.flush()?;
+        if let Some(ref mut tee) = self.tee {
+            tee.flush()?;
+        }
         Ok(())
     }
 }
+
+/// Time of a recording's last event and the terminal size in effect at that
+/// point (the header's, updated by any resize events)
+pub fn recording_end(path: &Path) -> Result<(f64, (usize, usize))> {
+    let mut reader = AsciicastReader::new(path)
+        .with_context(|| format!("Failed to read recording to append to: {}", path.display()))?;
+    let metadata = reader.metadata();
+    let events = reader.read_events()?;
+
+    let last_time = events.iter().map(|event| event.timestamp).fold(0.0, f64::max);
+    let size = events
+        .iter()
+        .rev()
+        .find_map(|event| event.resize_dimensions())
+        .unwrap_or((metadata.width, metadata.height));
+    Ok((last_time, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(timestamp: f64, text: &str) -> OutputEvent {
+        OutputEvent { timestamp, data: text.as_bytes().to_vec() }
+    }
+
+    #[test]
+    fn appended_events_continue_after_the_last_one() {
+        let path = std::env::temp_dir().join(format!("ttyvid-append-{}.cast", std::process::id()));
+        let mut writer = CastWriter::new(&path).unwrap();
+        writer.write_header(80, 24, &[]).unwrap();
+        writer.write_event(&output(0.5, "first ")).unwrap();
+        writer.write_event(&output(2.0, "session\r\n")).unwrap();
+        writer.close().unwrap();
+
+        let mut writer = CastWriter::append(&path, 80, 24).unwrap();
+        writer.write_header(80, 24, &[]).unwrap();
+        writer.write_event(&output(0.25, "second ")).unwrap();
+        writer.write_event(&output(1.0, "session")).unwrap();
+        writer.close().unwrap();
+
+        let mut reader = AsciicastReader::new(&path).unwrap();
+        let events = reader.read_events().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let end = recording_end(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(text.lines().filter(|line| line.starts_with('{')).count(), 1);
+        let times: Vec<f64> = events.iter().map(|event| event.timestamp).collect();
+        assert_eq!(times, [0.5, 2.0, 2.25, 3.0]);
+        assert_eq!(end, (3.0, (80, 24)));
+    }
+
+    #[test]
+    fn header_env_keeps_term_and_shell_only() {
+        let path = std::env::temp_dir().join(format!("ttyvid-header-{}.cast", std::process::id()));
+        let env = [
+            ("TERM".to_string(), "screen-256color".to_string()),
+            ("API_TOKEN".to_string(), "secret".to_string()),
+            ("PYTHONUNBUFFERED".to_string(), "1".to_string()),
+        ];
+        let mut writer = CastWriter::new(&path).unwrap();
+        writer.write_header(100, 30, &env).unwrap();
+        writer.close().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let header: serde_json::Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        assert_eq!(header["width"], 100);
+        assert_eq!(header["env"]["TERM"], "screen-256color");
+        assert!(header["env"]["SHELL"].is_string());
+        assert_eq!(header["env"].as_object().unwrap().len(), 2);
+    }
+}
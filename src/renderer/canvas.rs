@@ -1,4 +1,8 @@
 use super::Palette;
+use image::RgbaImage;
+
+/// Pixels converted per batch in the chunked RGBA fast path
+const RGBA_CHUNK: usize = 16;
 
 #[derive(Clone)]
 pub struct Canvas {
@@ -43,4 +47,181 @@ impl Canvas {
             None
         }
     }
+
+    /// Set every pixel to one color
+    pub fn fill(&mut self, color_index: u8) {
+        self.data.fill(color_index);
+    }
+
+    /// Fill a rectangle, clipped to the canvas bounds
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color_index: u8) {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+
+        for row in y..y + height {
+            let start = row * self.width + x;
+            self.data[start..start + width].fill(color_index);
+        }
+    }
+
+    /// Copy out a rectangle, clipped to the canvas bounds
+    pub fn subcanvas(&self, x: usize, y: usize, width: usize, height: usize) -> Canvas {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+
+        let mut data = Vec::with_capacity(width * height);
+        for row in y..y + height {
+            let start = row * self.width + x;
+            data.extend_from_slice(&self.data[start..start + width]);
+        }
+        Canvas { data, width, height }
+    }
+
+    /// Copy another canvas onto this one at (x, y); pixels falling outside are dropped
+    pub fn blit(&mut self, src: &Canvas, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let copy_width = src.width.min(self.width - x);
+        let copy_height = src.height.min(self.height - y);
+
+        for row in 0..copy_height {
+            let src_start = row * src.width;
+            let dst_start = (y + row) * self.width + x;
+            self.data[dst_start..dst_start + copy_width]
+                .copy_from_slice(&src.data[src_start..src_start + copy_width]);
+        }
+    }
+
+    /// Replace every pixel's color index through a 256-entry lookup table
+    pub fn remap_colors(&mut self, map: &[u8]) {
+        for pixel in &mut self.data {
+            *pixel = map[*pixel as usize];
+        }
+    }
+
+    /// Convert to an RGBA image through the palette
+    pub fn to_rgba_image(&self, palette: &Palette) -> RgbaImage {
+        RgbaImage::from_raw(self.width as u32, self.height as u32, self.to_rgba(palette))
+            .expect("RGBA buffer matches canvas dimensions")
+    }
+
+    /// Convert to packed RGBA bytes, batching pixels through a precomputed palette LUT
+    pub fn to_rgba(&self, palette: &Palette) -> Vec<u8> {
+        let lut = rgba_lut(palette);
+        let mut out = vec![0u8; self.data.len() * 4];
+
+        let mut src = self.data.chunks_exact(RGBA_CHUNK);
+        let mut dst = out.chunks_exact_mut(RGBA_CHUNK * 4);
+        for (indices, pixels) in (&mut src).zip(&mut dst) {
+            for (i, &index) in indices.iter().enumerate() {
+                pixels[i * 4..i * 4 + 4].copy_from_slice(&lut[index as usize]);
+            }
+        }
+
+        // Scalar tail for the pixels that don't fill a whole chunk
+        let tail = src.remainder();
+        let tail_start = out.len() - tail.len() * 4;
+        for (i, &index) in tail.iter().enumerate() {
+            out[tail_start + i * 4..tail_start + i * 4 + 4].copy_from_slice(&lut[index as usize]);
+        }
+
+        out
+    }
+
+    /// Convert to packed RGBA bytes one pixel at a time (reference implementation)
+    pub fn to_rgba_scalar(&self, palette: &Palette) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.data.len() * 4);
+        for &index in &self.data {
+            let (r, g, b) = palette.get_rgb(index);
+            out.extend_from_slice(&[r, g, b, 255]);
+        }
+        out
+    }
+}
+
+/// Precompute opaque RGBA for every palette index
+fn rgba_lut(palette: &Palette) -> [[u8; 4]; 256] {
+    let mut lut = [[0, 0, 0, 255]; 256];
+    for (index, entry) in lut.iter_mut().enumerate() {
+        let (r, g, b) = palette.get_rgb(index as u8);
+        *entry = [r, g, b, 255];
+    }
+    lut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunked_rgba_matches_scalar() {
+        // An odd size leaves a partial chunk for the scalar tail
+        let mut canvas = Canvas::new(37, 11, &Palette::default());
+        let mut seed: u32 = 12345;
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                canvas.set_pixel(x, y, (seed >> 16) as u8);
+            }
+        }
+
+        let palette = Palette::default();
+        assert_eq!(canvas.to_rgba(&palette), canvas.to_rgba_scalar(&palette));
+    }
+
+    /// Canvas whose pixels count up from 0 in row-major order
+    fn numbered(width: usize, height: usize) -> Canvas {
+        let data = (0..width * height).map(|i| i as u8).collect();
+        Canvas { data, width, height }
+    }
+
+    #[test]
+    fn subcanvas_is_clipped_to_the_canvas() {
+        let canvas = numbered(4, 3);
+        let inner = canvas.subcanvas(1, 1, 2, 2);
+        assert_eq!((inner.width(), inner.height()), (2, 2));
+        assert_eq!(inner.data(), &[5, 6, 9, 10]);
+
+        let edge = canvas.subcanvas(3, 2, 5, 5);
+        assert_eq!((edge.width(), edge.height()), (1, 1));
+        assert_eq!(edge.data(), &[11]);
+
+        let outside = canvas.subcanvas(9, 9, 2, 2);
+        assert_eq!((outside.width(), outside.height()), (0, 0));
+    }
+
+    #[test]
+    fn blit_drops_pixels_past_the_edge() {
+        let mut canvas = Canvas::new(4, 3, &Palette::default());
+        canvas.blit(&numbered(3, 3), 2, 1);
+        assert_eq!(canvas.data(), &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 3, 4]);
+
+        let before = canvas.data().to_vec();
+        canvas.blit(&numbered(2, 2), 4, 0);
+        assert_eq!(canvas.data(), &before[..]);
+    }
+
+    #[test]
+    fn fill_matches_a_set_pixel_loop_and_fill_rect_is_clipped() {
+        let mut filled = numbered(5, 3);
+        filled.fill(7);
+        let mut looped = numbered(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                looped.set_pixel(x, y, 7);
+            }
+        }
+        assert_eq!(filled.data(), looped.data());
+
+        let mut canvas = Canvas { data: vec![0; 4 * 3], width: 4, height: 3 };
+        canvas.fill_rect(2, 1, 10, 10, 1);
+        assert_eq!(canvas.data(), &[0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1]);
+        canvas.fill_rect(9, 9, 2, 2, 2);
+        assert!(!canvas.data().contains(&2));
+    }
 }
@@ -0,0 +1,122 @@
+use super::{Canvas, Palette};
+
+/// A post-compositing effect applied to every frame before it reaches the encoder
+pub trait FrameEffect {
+    fn apply(&self, canvas: &mut Canvas);
+}
+
+/// CRT-style scanlines: darkens every other pixel row
+pub struct Scanlines {
+    darken_map: Vec<u8>,
+}
+
+impl Scanlines {
+    pub fn new(palette: &Palette) -> Self {
+        Self {
+            darken_map: palette.darken_map(0.6),
+        }
+    }
+}
+
+impl FrameEffect for Scanlines {
+    fn apply(&self, canvas: &mut Canvas) {
+        for y in (1..canvas.height()).step_by(2) {
+            for x in 0..canvas.width() {
+                if let Some(color) = canvas.get_pixel(x, y) {
+                    canvas.set_pixel(x, y, self.darken_map[color as usize]);
+                }
+            }
+        }
+    }
+}
+
+/// Number of precomputed brightness steps for the vignette falloff
+const VIGNETTE_LEVELS: usize = 32;
+
+/// Radial darkening toward the corners; `strength` 0.0 = none, 1.0 = black corners
+pub struct Vignette {
+    strength: f32,
+    level_maps: Vec<Vec<u8>>,
+}
+
+impl Vignette {
+    pub fn new(palette: &Palette, strength: f32) -> Self {
+        let level_maps = (0..=VIGNETTE_LEVELS)
+            .map(|level| palette.darken_map(level as f32 / VIGNETTE_LEVELS as f32))
+            .collect();
+        Self {
+            strength: strength.clamp(0.0, 1.0),
+            level_maps,
+        }
+    }
+}
+
+impl FrameEffect for Vignette {
+    fn apply(&self, canvas: &mut Canvas) {
+        let cx = canvas.width() as f32 / 2.0;
+        let cy = canvas.height() as f32 / 2.0;
+        let max_dist_sq = cx * cx + cy * cy;
+        if max_dist_sq == 0.0 {
+            return;
+        }
+
+        for y in 0..canvas.height() {
+            for x in 0..canvas.width() {
+                let dx = x as f32 + 0.5 - cx;
+                let dy = y as f32 + 0.5 - cy;
+                let falloff = 1.0 - self.strength * ((dx * dx + dy * dy) / max_dist_sq);
+                let level = (falloff.clamp(0.0, 1.0) * VIGNETTE_LEVELS as f32).round() as usize;
+                if level == VIGNETTE_LEVELS {
+                    continue;
+                }
+                if let Some(color) = canvas.get_pixel(x, y) {
+                    canvas.set_pixel(x, y, self.level_maps[level][color as usize]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brightness(palette: &Palette, color: u8) -> u32 {
+        let (r, g, b) = palette.get_rgb(color);
+        r as u32 + g as u32 + b as u32
+    }
+
+    fn white_canvas(palette: &Palette) -> Canvas {
+        let mut canvas = Canvas::new(16, 8, palette);
+        canvas.fill(15);
+        canvas
+    }
+
+    #[test]
+    fn scanlines_darken_alternate_rows() {
+        let palette = Palette::default();
+        let mut canvas = white_canvas(&palette);
+        Scanlines::new(&palette).apply(&mut canvas);
+
+        for y in 0..canvas.height() {
+            let color = canvas.get_pixel(3, y).unwrap();
+            if y % 2 == 0 {
+                assert_eq!(color, 15, "row {} is left alone", y);
+            } else {
+                assert!(brightness(&palette, color) < brightness(&palette, 15), "row {} is darker", y);
+            }
+        }
+    }
+
+    #[test]
+    fn vignette_darkens_corners_more_than_center() {
+        let palette = Palette::default();
+        let mut canvas = white_canvas(&palette);
+        Vignette::new(&palette, 1.0).apply(&mut canvas);
+
+        let center = brightness(&palette, canvas.get_pixel(8, 4).unwrap());
+        for (x, y) in [(0, 0), (15, 0), (0, 7), (15, 7)] {
+            assert!(brightness(&palette, canvas.get_pixel(x, y).unwrap()) < center, "corner {},{}", x, y);
+        }
+    }
+}
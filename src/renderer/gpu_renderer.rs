@@ -10,7 +10,7 @@ use wgpu::util::DeviceExt;
 #[cfg(feature = "gpu")]
 use bytemuck;
 
-use crate::renderer::{Canvas, Palette, Font};
+use crate::renderer::{Canvas, Palette, Font, RenderOptions};
 use crate::terminal::{Cell, CellFlags, Grid};
 use anyhow::{Result, Context};
 
@@ -22,6 +22,7 @@ pub struct GpuRenderer {
     palette: Palette,
     fallback_to_cpu: bool,
     has_warned_fallback: std::sync::atomic::AtomicBool,
+    options: RenderOptions,
 }
 
 #[cfg(feature = "gpu")]
@@ -50,12 +51,57 @@ struct GpuContext {
     render_state: std::cell::RefCell<Option<RenderState>>,
 }
 
+/// Which GPU to render on (--gpu-power, --gpu-adapter)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuSelection {
+    /// Prefer the integrated, power-saving GPU over a discrete one
+    pub low_power: bool,
+    /// Use the first adapter whose name contains this (case-insensitive)
+    pub adapter: Option<String>,
+}
+
+impl GpuSelection {
+    /// The adapter request made when no adapter name is given
+    #[cfg(feature = "gpu")]
+    pub fn adapter_options(&self) -> wgpu::RequestAdapterOptions<'static, 'static> {
+        wgpu::RequestAdapterOptions {
+            power_preference: if self.low_power {
+                wgpu::PowerPreference::LowPower
+            } else {
+                wgpu::PowerPreference::HighPerformance
+            },
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }
+    }
+
+    /// Find the adapter to render on
+    #[cfg(feature = "gpu")]
+    fn request_adapter(&self, instance: &wgpu::Instance) -> Result<wgpu::Adapter> {
+        let Some(ref wanted) = self.adapter else {
+            return pollster::block_on(instance.request_adapter(&self.adapter_options()))
+                .context("Failed to find GPU adapter");
+        };
+        let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+        let names: Vec<String> = adapters.iter().map(|adapter| adapter.get_info().name).collect();
+        adapters
+            .into_iter()
+            .find(|adapter| adapter.get_info().name.to_lowercase().contains(&wanted.to_lowercase()))
+            .with_context(|| format!("No GPU adapter matches '{}'; available: {}", wanted, names.join(", ")))
+    }
+}
+
 impl GpuRenderer {
     /// Create a new GPU renderer with automatic fallback
     pub fn new(font: Font, palette: Palette) -> Self {
+        Self::with_gpu_selection(font, palette, &GpuSelection::default())
+    }
+
+    /// Create a GPU renderer on the chosen adapter, falling back to the CPU if it is unavailable
+    pub fn with_gpu_selection(font: Font, palette: Palette, selection: &GpuSelection) -> Self {
         #[cfg(feature = "gpu")]
         {
-            match Self::init_gpu(&font, &palette) {
+            match Self::init_gpu(&font, &palette, selection) {
                 Ok(gpu_context) => {
                     eprintln!("GPU acceleration enabled (wgpu)");
                     Self {
@@ -64,6 +110,7 @@ impl GpuRenderer {
                         palette,
                         fallback_to_cpu: false,
                         has_warned_fallback: std::sync::atomic::AtomicBool::new(false),
+                        options: RenderOptions::default(),
                     }
                 }
                 Err(e) => {
@@ -75,6 +122,7 @@ impl GpuRenderer {
                         palette,
                         fallback_to_cpu: true,
                         has_warned_fallback: std::sync::atomic::AtomicBool::new(true),
+                        options: RenderOptions::default(),
                     }
                 }
             }
@@ -82,30 +130,29 @@ impl GpuRenderer {
 
         #[cfg(not(feature = "gpu"))]
         {
+            let _ = selection;
             Self {
                 font,
                 palette,
                 fallback_to_cpu: true,
                 has_warned_fallback: std::sync::atomic::AtomicBool::new(true),
+                options: RenderOptions::default(),
             }
         }
     }
 
     /// Initialize GPU context with font and palette uploaded once
     #[cfg(feature = "gpu")]
-    fn init_gpu(font: &Font, palette: &Palette) -> Result<GpuContext> {
+    fn init_gpu(font: &Font, palette: &Palette, selection: &GpuSelection) -> Result<GpuContext> {
         // Request GPU adapter
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
         });
 
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: None,
-            force_fallback_adapter: false,
-        }))
-        .context("Failed to find GPU adapter")?;
+        let adapter = selection.request_adapter(&instance)?;
+        let info = adapter.get_info();
+        eprintln!("GPU adapter: {} ({:?}, {:?})", info.name, info.device_type, info.backend);
 
         // Create device and queue
         let (device, queue) = pollster::block_on(adapter.request_device(
@@ -240,11 +287,16 @@ impl GpuRenderer {
         })
     }
 
+    /// Proportional layout is CPU only; the GPU shader assumes a fixed grid
+    pub fn set_options(&mut self, options: RenderOptions) {
+        self.options = options;
+    }
+
     /// Check if GPU is available
     pub fn is_gpu_available(&self) -> bool {
         #[cfg(feature = "gpu")]
         {
-            self.gpu_context.is_some()
+            self.gpu_context.is_some() && !self.options.proportional
         }
         #[cfg(not(feature = "gpu"))]
         {
@@ -254,6 +306,10 @@ impl GpuRenderer {
 
     /// Render grid to canvas (GPU or CPU fallback)
     pub fn render_grid(&self, grid: &Grid) -> Canvas {
+        if self.options.proportional {
+            return self.render_grid_proportional_cpu(grid, None);
+        }
+
         #[cfg(feature = "gpu")]
         {
             if let Some(ref ctx) = self.gpu_context {
@@ -280,6 +336,9 @@ impl GpuRenderer {
         if grids.is_empty() {
             return Ok(Vec::new());
         }
+        if self.options.proportional {
+            return Ok(grids.iter().map(|grid| self.render_grid_proportional_cpu(grid, None)).collect());
+        }
 
         // GPU buffer binding limit: 128MB (max_storage_buffer_binding_size)
         // Calculate max frames per batch to stay under limit
@@ -342,9 +401,11 @@ impl GpuRenderer {
                 for col in 0..grid_width {
                     if let Some(cell) = grid.get_cell(col, row) {
                         let char_code = cell.character as u32;
-                        let flags = if cell.flags.contains(CellFlags::REVERSE) { 1u32 } else { 0u32 };
+                        let flags = gpu_cell_flags(cell);
                         all_cell_data.push(char_code);
-                        all_cell_data.push(cell.fg_color as u32);
+                        // Concealed cells go to the shader with the glyph in the background color
+                        let fg_color = if self.options.is_hidden(cell) { cell.bg_color } else { cell.fg_color };
+                        all_cell_data.push(fg_color as u32);
                         all_cell_data.push(cell.bg_color as u32);
                         all_cell_data.push(flags);
                     } else {
@@ -445,6 +506,9 @@ impl GpuRenderer {
                 mapped_at_creation: false,
             });
 
+            let cell_binding_size = std::num::NonZeroU64::new((cells_per_frame * 4) as u64)
+                .context("Cannot batch render an empty grid")?;
+
             // Create bind group for this frame (different cell data offset, different output buffer)
             let frame_bind_group = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some(&format!("Frame {} Bind Group", frame_idx)),
@@ -456,7 +520,7 @@ impl GpuRenderer {
                         resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
                             buffer: &cell_buffer,
                             offset: (frame_idx * aligned_bytes_per_frame) as u64,
-                            size: Some(std::num::NonZeroU64::new((cells_per_frame * 4) as u64).unwrap()),
+                            size: Some(cell_binding_size),
                         }),
                     },
                     wgpu::BindGroupEntry {
@@ -523,7 +587,8 @@ impl GpuRenderer {
         let buffer_slice = staging_buffer.slice(..);
         let (sender, receiver) = std::sync::mpsc::channel();
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-            sender.send(result).unwrap();
+            // The receiver only goes away if rendering was already abandoned
+            let _ = sender.send(result);
         });
 
         ctx.device.poll(wgpu::Maintain::Wait);
@@ -569,9 +634,11 @@ impl GpuRenderer {
             for col in 0..grid_width {
                 if let Some(cell) = grid.get_cell(col, row) {
                     let char_code = cell.character as u32;
-                    let flags = if cell.flags.contains(CellFlags::REVERSE) { 1u32 } else { 0u32 };
+                    let flags = gpu_cell_flags(cell);
                     cell_data.push(char_code);
-                    cell_data.push(cell.fg_color as u32);
+                    // Concealed cells go to the shader with the glyph in the background color
+                    let fg_color = if self.options.is_hidden(cell) { cell.bg_color } else { cell.fg_color };
+                    cell_data.push(fg_color as u32);
                     cell_data.push(cell.bg_color as u32);
                     cell_data.push(flags);
                 } else {
@@ -718,7 +785,8 @@ impl GpuRenderer {
         let buffer_slice = state.staging_buffer.slice(..);
         let (sender, receiver) = std::sync::mpsc::channel();
         buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-            sender.send(result).unwrap();
+            // The receiver only goes away if rendering was already abandoned
+            let _ = sender.send(result);
         });
 
         ctx.device.poll(wgpu::Maintain::Wait);
@@ -763,6 +831,9 @@ impl GpuRenderer {
     pub fn render_grid_with_cursor(&self, grid: &Grid, cursor_x: usize, cursor_y: usize) -> Canvas {
         // For now, always use CPU for cursor rendering
         // GPU path can be added later
+        if self.options.proportional {
+            return self.render_grid_proportional_cpu(grid, Some((cursor_x, cursor_y)));
+        }
         let (width, height) = self.canvas_size(grid.width(), grid.height());
         let mut canvas = Canvas::new(width, height, &self.palette);
 
@@ -783,19 +854,67 @@ impl GpuRenderer {
 
     /// Calculate canvas size
     fn canvas_size(&self, cols: usize, rows: usize) -> (usize, usize) {
+        if self.options.proportional {
+            return (cols * self.font.max_advance(), rows * self.font.height());
+        }
         (cols * self.font.width(), rows * self.font.height())
     }
 
+    /// Render with each glyph advancing the pen by its own width (CPU implementation)
+    fn render_grid_proportional_cpu(&self, grid: &Grid, cursor: Option<(usize, usize)>) -> Canvas {
+        let (width, height) = self.canvas_size(grid.width(), grid.height());
+        let mut canvas = Canvas::new(width, height, &self.palette);
+        let glyph_height = self.font.height();
+
+        for y in 0..grid.height() {
+            let mut pen_x = 0;
+            for x in 0..grid.width() {
+                let Some(cell) = grid.get_cell(x, y) else { continue };
+                let (mut fg, mut bg) = self.options.cell_colors(cell);
+                if cursor == Some((x, y)) {
+                    (fg, bg) = self.palette.cursor_colors(fg, bg, self.options.cursor_contrast);
+                }
+
+                let (glyph, advance) = self.font.get_glyph_proportional(cell.character);
+                for gy in 0..glyph_height {
+                    for gx in 0..advance {
+                        let intensity = glyph[gy * advance + gx];
+                        canvas.set_pixel(pen_x + gx, y * glyph_height + gy, self.blend_intensity(intensity, fg, bg));
+                    }
+                }
+                if cell.flags.contains(CellFlags::OVERLINE) {
+                    canvas.fill_rect(pen_x, y * glyph_height, advance, 1, fg);
+                }
+                pen_x += advance;
+            }
+        }
+
+        canvas
+    }
+
+    /// Intensity 0 = bg, intensity 10 = fg, blend for in-between
+    fn blend_intensity(&self, intensity: u8, fg: u8, bg: u8) -> u8 {
+        if intensity == 0 {
+            bg
+        } else if intensity >= 10 {
+            fg
+        } else {
+            let (bg_r, bg_g, bg_b) = self.palette.get_rgb(bg);
+            let (fg_r, fg_g, fg_b) = self.palette.get_rgb(fg);
+            let blend_factor = intensity as f32 / 10.0;
+            let r = bg_r as f32 + (fg_r as i16 - bg_r as i16) as f32 * blend_factor;
+            let g = bg_g as f32 + (fg_g as i16 - bg_g as i16) as f32 * blend_factor;
+            let b = bg_b as f32 + (fg_b as i16 - bg_b as i16) as f32 * blend_factor;
+            self.palette.match_color_index(r as i32, g as i32, b as i32)
+        }
+    }
+
     /// Render single cell (CPU implementation)
     fn render_cell_cpu(&self, cell: &Cell, col: usize, row: usize, canvas: &mut Canvas) {
         let x = col * self.font.width();
         let y = row * self.font.height();
 
-        let (fg, bg) = if cell.flags.contains(CellFlags::REVERSE) {
-            (cell.bg_color, cell.fg_color)
-        } else {
-            (cell.fg_color, cell.bg_color)
-        };
+        let (fg, bg) = self.options.cell_colors(cell);
 
         let glyph = self.font.get_glyph_utf8(cell.character);
 
@@ -825,6 +944,10 @@ impl GpuRenderer {
                 }
             }
         }
+
+        if cell.flags.contains(CellFlags::OVERLINE) {
+            canvas.fill_rect(x, y, self.font.width(), 1, fg);
+        }
     }
 
     /// Render cell with inverted colors (CPU implementation)
@@ -832,11 +955,9 @@ impl GpuRenderer {
         let x = col * self.font.width();
         let y = row * self.font.height();
 
-        let (fg, bg) = if cell.flags.contains(CellFlags::REVERSE) {
-            (cell.fg_color, cell.bg_color)
-        } else {
-            (cell.bg_color, cell.fg_color)
-        };
+        let (fg, bg) = self.options.cell_colors(cell);
+        // Swap for the cursor, keeping the block visible against the cell background
+        let (fg, bg) = self.palette.cursor_colors(fg, bg, self.options.cursor_contrast);
 
         let glyph = self.font.get_glyph_utf8(cell.character);
 
@@ -866,6 +987,10 @@ impl GpuRenderer {
                 }
             }
         }
+
+        if cell.flags.contains(CellFlags::OVERLINE) {
+            canvas.fill_rect(x, y, self.font.width(), 1, fg);
+        }
     }
 
     /// Render title text
@@ -891,4 +1016,49 @@ impl super::RenderBackend for GpuRenderer {
     fn render_title(&self, canvas: &mut Canvas, x: i32, y: i32, text: &str, fg_color: u8, bg_color: u8, size: f32) {
         self.render_title(canvas, x, y, text, fg_color, bg_color, size)
     }
+
+    fn is_gpu_available(&self) -> bool {
+        self.is_gpu_available()
+    }
+
+    fn render_grids_batch(&self, grids: &[Grid]) -> Result<Vec<Canvas>> {
+        self.render_grids_batch(grids)
+    }
+
+    fn default_background_mask(&self, grid: &Grid, canvas: &Canvas, default_bg: u8, cursor: Option<(usize, usize)>) -> Vec<bool> {
+        self.options.default_background_mask(&self.font, grid, canvas, default_bg, cursor)
+    }
+}
+
+/// Cell flag bits understood by the shader (REVERSE_FLAG and OVERLINE_FLAG in render.wgsl)
+fn gpu_cell_flags(cell: &Cell) -> u32 {
+    let mut flags = 0;
+    if cell.flags.contains(CellFlags::REVERSE) {
+        flags |= 1;
+    }
+    if cell.flags.contains(CellFlags::OVERLINE) {
+        flags |= 2;
+    }
+    flags
+}
+
+#[cfg(all(test, feature = "gpu"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_power_picks_the_adapter_power_preference() {
+        let low = GpuSelection { low_power: true, adapter: None };
+        assert_eq!(low.adapter_options().power_preference, wgpu::PowerPreference::LowPower);
+        let high = GpuSelection::default();
+        assert_eq!(high.adapter_options().power_preference, wgpu::PowerPreference::HighPerformance);
+    }
+
+    #[test]
+    fn unknown_adapter_name_is_an_error() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor { backends: wgpu::Backends::all(), ..Default::default() });
+        let selection = GpuSelection { low_power: false, adapter: Some("no such gpu".to_string()) };
+        let error = selection.request_adapter(&instance).unwrap_err();
+        assert!(error.to_string().contains("No GPU adapter matches 'no such gpu'"));
+    }
 }
@@ -1,4 +1,5 @@
 mod canvas;
+pub mod effects;
 
 #[cfg(feature = "gpu")]
 mod gpu_renderer;
@@ -8,14 +9,76 @@ pub use crate::palette_tools::Palette;
 pub use canvas::Canvas;
 
 #[cfg(feature = "gpu")]
-pub use gpu_renderer::GpuRenderer;
+pub use gpu_renderer::{GpuRenderer, GpuSelection};
 
 use crate::terminal::{Cell, CellFlags, Grid};
 use rayon::prelude::*;
 
+/// Minimum contrast between the cursor block and the cell background unless configured
+pub const DEFAULT_CURSOR_CONTRAST: f32 = 1.5;
+
+/// Rendering behavior shared by the CPU and GPU backends, built from the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /// Lay out each row by per-glyph advance widths instead of a fixed cell grid
+    pub proportional: bool,
+    /// Minimum contrast ratio kept between the cursor and the cell under it (1.0 disables)
+    pub cursor_contrast: f32,
+    /// Draw concealed (SGR 8) text instead of hiding it
+    pub reveal_concealed: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self { proportional: false, cursor_contrast: DEFAULT_CURSOR_CONTRAST, reveal_concealed: false }
+    }
+}
+
+impl RenderOptions {
+    /// The foreground and background a cell is drawn with, after reverse video and concealment
+    pub fn cell_colors(&self, cell: &Cell) -> (u8, u8) {
+        let (fg, bg) = if cell.flags.contains(CellFlags::REVERSE) {
+            (cell.bg_color, cell.fg_color)
+        } else {
+            (cell.fg_color, cell.bg_color)
+        };
+        if self.is_hidden(cell) { (bg, bg) } else { (fg, bg) }
+    }
+
+    /// Pixels of a rendered `grid` that show their cell's default background: the cell is drawn
+    /// on `default_bg` (after reverse video) and the pixel was left in that color. The cursor cell
+    /// is never included. Used by transparent-background themes to let underlays show through
+    pub fn default_background_mask(&self, font: &Font, grid: &Grid, canvas: &Canvas, default_bg: u8, cursor: Option<(usize, usize)>) -> Vec<bool> {
+        let mut mask = vec![false; canvas.width() * canvas.height()];
+        let glyph_height = font.height();
+        for y in 0..grid.height() {
+            let mut pen_x = 0;
+            for x in 0..grid.width() {
+                let Some(cell) = grid.get_cell(x, y) else { continue };
+                let advance = if self.proportional { font.get_glyph_proportional(cell.character).1 } else { font.width() };
+                if cursor != Some((x, y)) && self.cell_colors(cell).1 == default_bg {
+                    for py in y * glyph_height..((y + 1) * glyph_height).min(canvas.height()) {
+                        for px in pen_x..(pen_x + advance).min(canvas.width()) {
+                            mask[py * canvas.width() + px] = canvas.get_pixel(px, py) == Some(default_bg);
+                        }
+                    }
+                }
+                pen_x += advance;
+            }
+        }
+        mask
+    }
+
+    /// True when the cell's glyph should not be drawn
+    pub fn is_hidden(&self, cell: &Cell) -> bool {
+        cell.flags.contains(CellFlags::CONCEAL) && !self.reveal_concealed
+    }
+}
+
 pub struct Rasterizer {
     font: Font,
     palette: Palette,
+    options: RenderOptions,
 }
 
 impl Rasterizer {
@@ -23,20 +86,40 @@ impl Rasterizer {
         let font = Font::load(font_name);
         let palette = Palette::default();
 
-        Self { font, palette }
+        Self { font, palette, options: RenderOptions::default() }
     }
 
     /// Create a rasterizer with a custom font (for TrueType support)
     pub fn with_font(font: Font) -> Self {
         let palette = Palette::default();
-        Self { font, palette }
+        Self { font, palette, options: RenderOptions::default() }
+    }
+
+    /// Palette used for color decisions such as cursor contrast
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Layout, cursor and concealed-text behavior used for every grid drawn after this call
+    pub fn set_options(&mut self, options: RenderOptions) {
+        self.options = options;
+    }
+
+    pub fn set_glyph_contrast(&mut self, on: bool) {
+        self.font.set_glyph_contrast(on);
     }
 
     pub fn canvas_size(&self, cols: usize, rows: usize) -> (usize, usize) {
+        if self.options.proportional {
+            return (cols * self.font.max_advance(), rows * self.font.height());
+        }
         (cols * self.font.width(), rows * self.font.height())
     }
 
     pub fn render_grid(&self, grid: &Grid) -> Canvas {
+        if self.options.proportional {
+            return self.render_grid_proportional(grid, None);
+        }
         let (width, height) = self.canvas_size(grid.width(), grid.height());
         let mut canvas = Canvas::new(width, height, &self.palette);
 
@@ -54,6 +137,9 @@ impl Rasterizer {
 
     /// Render grid with cursor visible at specified position
     pub fn render_grid_with_cursor(&self, grid: &Grid, cursor_x: usize, cursor_y: usize) -> Canvas {
+        if self.options.proportional {
+            return self.render_grid_proportional(grid, Some((cursor_x, cursor_y)));
+        }
         let (width, height) = self.canvas_size(grid.width(), grid.height());
         let mut canvas = Canvas::new(width, height, &self.palette);
 
@@ -74,15 +160,44 @@ impl Rasterizer {
         canvas
     }
 
+    /// Render with each glyph advancing the pen by its own width
+    fn render_grid_proportional(&self, grid: &Grid, cursor: Option<(usize, usize)>) -> Canvas {
+        let (width, height) = self.canvas_size(grid.width(), grid.height());
+        let mut canvas = Canvas::new(width, height, &self.palette);
+        let glyph_height = self.font.height();
+
+        for y in 0..grid.height() {
+            let mut pen_x = 0;
+            for x in 0..grid.width() {
+                let Some(cell) = grid.get_cell(x, y) else { continue };
+                let (mut fg, mut bg) = self.options.cell_colors(cell);
+                if cursor == Some((x, y)) {
+                    (fg, bg) = self.palette.cursor_colors(fg, bg, self.options.cursor_contrast);
+                }
+
+                let (glyph, advance) = self.font.get_glyph_proportional(cell.character);
+                for gy in 0..glyph_height {
+                    for gx in 0..advance {
+                        let intensity = glyph[gy * advance + gx];
+                        let color = if intensity > 127 { fg } else { bg };
+                        canvas.set_pixel(pen_x + gx, y * glyph_height + gy, color);
+                    }
+                }
+                if cell.flags.contains(CellFlags::OVERLINE) {
+                    canvas.fill_rect(pen_x, y * glyph_height, advance, 1, fg);
+                }
+                pen_x += advance;
+            }
+        }
+
+        canvas
+    }
+
     fn render_cell(&self, cell: &Cell, col: usize, row: usize, canvas: &mut Canvas) {
         let x = col * self.font.width();
         let y = row * self.font.height();
 
-        let (fg, bg) = if cell.flags.contains(CellFlags::REVERSE) {
-            (cell.bg_color, cell.fg_color)
-        } else {
-            (cell.fg_color, cell.bg_color)
-        };
+        let (fg, bg) = self.options.cell_colors(cell);
 
         // Get character bitmap with UTF-8 mapping (supports both FD and TrueType fonts)
         let glyph = self.font.get_glyph_utf8(cell.character);
@@ -101,6 +216,10 @@ impl Rasterizer {
                 }
             }
         }
+
+        if cell.flags.contains(CellFlags::OVERLINE) {
+            canvas.fill_rect(x, y, self.font.width(), 1, fg);
+        }
     }
 
     /// Render cell with inverted colors (for cursor)
@@ -108,12 +227,9 @@ impl Rasterizer {
         let x = col * self.font.width();
         let y = row * self.font.height();
 
-        // Invert fg/bg for cursor
-        let (fg, bg) = if cell.flags.contains(CellFlags::REVERSE) {
-            (cell.fg_color, cell.bg_color)  // Already reversed, so swap back
-        } else {
-            (cell.bg_color, cell.fg_color)  // Normal, so invert
-        };
+        let (fg, bg) = self.options.cell_colors(cell);
+        // Invert fg/bg for cursor, keeping the block visible against the cell background
+        let (fg, bg) = self.palette.cursor_colors(fg, bg, self.options.cursor_contrast);
 
         // Get character bitmap with UTF-8 mapping (supports both FD and TrueType fonts)
         let glyph = self.font.get_glyph_utf8(cell.character);
@@ -132,6 +248,10 @@ impl Rasterizer {
                 }
             }
         }
+
+        if cell.flags.contains(CellFlags::OVERLINE) {
+            canvas.fill_rect(x, y, self.font.width(), 1, fg);
+        }
     }
 
     /// Render a title string at the specified position with size multiplier
@@ -144,18 +264,16 @@ impl Rasterizer {
 /// When compiled with --features gpu, attempts to use GPU and falls back to CPU
 /// When compiled without gpu feature, always uses CPU
 #[cfg(feature = "gpu")]
-pub fn create_renderer_auto(font_name: Option<&str>) -> Box<dyn RenderBackend> {
-    let font = Font::load(font_name);
-    let palette = Palette::default();
-
+pub fn create_renderer_auto(font: Font, palette: Palette) -> Box<dyn RenderBackend> {
     // Try GPU first, automatically falls back to CPU if GPU unavailable
     Box::new(GpuRenderer::new(font, palette))
 }
 
 #[cfg(not(feature = "gpu"))]
-pub fn create_renderer_auto(font_name: Option<&str>) -> Box<dyn RenderBackend> {
-    let font = Font::load(font_name);
-    Box::new(Rasterizer::new(font_name))
+pub fn create_renderer_auto(font: Font, palette: Palette) -> Box<dyn RenderBackend> {
+    let mut rasterizer = Rasterizer::with_font(font);
+    rasterizer.set_palette(palette);
+    Box::new(rasterizer)
 }
 
 /// Trait for render backends (CPU or GPU)
@@ -164,6 +282,19 @@ pub trait RenderBackend {
     fn render_grid_with_cursor(&self, grid: &Grid, cursor_x: usize, cursor_y: usize) -> Canvas;
     fn canvas_size(&self, cols: usize, rows: usize) -> (usize, usize);
     fn render_title(&self, canvas: &mut Canvas, x: i32, y: i32, text: &str, fg_color: u8, bg_color: u8, size: f32);
+
+    /// True when frames are rendered on the GPU and should be batched
+    fn is_gpu_available(&self) -> bool {
+        false
+    }
+
+    /// Render several grids at once; backends without batching render them one by one
+    fn render_grids_batch(&self, grids: &[Grid]) -> anyhow::Result<Vec<Canvas>> {
+        Ok(grids.iter().map(|grid| self.render_grid(grid)).collect())
+    }
+
+    /// See [`RenderOptions::default_background_mask`]; `canvas` is the grid as this backend drew it
+    fn default_background_mask(&self, grid: &Grid, canvas: &Canvas, default_bg: u8, cursor: Option<(usize, usize)>) -> Vec<bool>;
 }
 
 // Implement RenderBackend for CPU Rasterizer
@@ -180,7 +311,178 @@ impl RenderBackend for Rasterizer {
         self.canvas_size(cols, rows)
     }
 
+    fn default_background_mask(&self, grid: &Grid, canvas: &Canvas, default_bg: u8, cursor: Option<(usize, usize)>) -> Vec<bool> {
+        self.options.default_background_mask(&self.font, grid, canvas, default_bg, cursor)
+    }
+
     fn render_title(&self, canvas: &mut Canvas, x: i32, y: i32, text: &str, fg_color: u8, bg_color: u8, size: f32) {
         self.render_title(canvas, x, y, text, fg_color, bg_color, size)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_background_block_has_no_seams() {
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let mut grid = Grid::new(3, 2, 7, 0);
+        for y in 0..2 {
+            for x in 0..3 {
+                grid.write_cell(x, y, Cell::new(' ', 7, 4, CellFlags::empty()));
+            }
+        }
+
+        let canvas = rasterizer.render_grid(&grid);
+        assert!(canvas.data().iter().all(|&pixel| pixel == 4), "every pixel between cells keeps the background");
+    }
+
+    #[test]
+    fn proportional_glyphs_do_not_overlap() {
+        // A narrow and a wide fully lit glyph in a 4-pixel cell font
+        let glyphs = [('i', vec![255; 2 * 2]), ('W', vec![255; 6 * 2])].into_iter().collect();
+        let mut rasterizer = Rasterizer::with_font(Font::BitmapIntensity { width: 4, height: 2, glyphs });
+        rasterizer.set_options(RenderOptions { proportional: true, ..RenderOptions::default() });
+
+        let mut grid = Grid::new(3, 1, 7, 0);
+        for (x, (character, fg)) in [('i', 1), ('W', 2), ('i', 3)].into_iter().enumerate() {
+            grid.write_cell(x, 0, Cell::new(character, fg, 0, CellFlags::empty()));
+        }
+
+        let canvas = rasterizer.render_grid(&grid);
+        let row: Vec<u8> = (0..canvas.width()).map(|x| canvas.get_pixel(x, 0).unwrap()).collect();
+        assert_eq!(row[..10], [1, 1, 2, 2, 2, 2, 2, 2, 3, 3]);
+        assert!(row[10..].iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn cursor_stands_out_on_a_low_contrast_cell() {
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let mut grid = Grid::new(2, 1, 7, 0);
+        // Near-black on black: a plain swap would draw an invisible block
+        grid.write_cell(0, 0, Cell::new(' ', 232, 0, CellFlags::empty()));
+
+        let canvas = rasterizer.render_grid_with_cursor(&grid, 0, 0);
+        let block = canvas.get_pixel(0, 0).unwrap();
+        let palette = Palette::default();
+        assert!(palette.contrast_ratio(block, 0) >= DEFAULT_CURSOR_CONTRAST, "cursor block {} on background 0", block);
+    }
+
+    #[test]
+    fn options_set_on_the_rasterizer_take_effect() {
+        let mut grid = Grid::new(1, 1, 7, 0);
+        grid.write_cell(0, 0, Cell::new('X', 7, 0, CellFlags::CONCEAL));
+
+        let mut rasterizer = Rasterizer::with_font(Font::load(None));
+        assert!(rasterizer.render_grid(&grid).data().iter().all(|&pixel| pixel == 0));
+
+        rasterizer.set_options(RenderOptions { reveal_concealed: true, ..RenderOptions::default() });
+        assert!(rasterizer.render_grid(&grid).data().contains(&7));
+    }
+
+    #[test]
+    fn concealed_text_is_drawn_as_background() {
+        let mut terminal = crate::terminal::TerminalEmulator::new(12, 1, true, 7, 0);
+        terminal.feed_bytes(b"\x1b[8msecret\x1b[28mok");
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let canvas = rasterizer.render_grid(terminal.grid());
+
+        let (cell_width, _) = rasterizer.canvas_size(1, 1);
+        let (hidden, shown) = canvas.data().chunks(canvas.width()).fold((true, false), |(hidden, shown), row| {
+            (hidden && row[..6 * cell_width].iter().all(|&pixel| pixel == 0), shown || row[6 * cell_width..].contains(&7))
+        });
+        assert!(hidden, "concealed glyph pixels differ from the background");
+        assert!(shown, "text after SGR 28 is not drawn");
+    }
+
+    #[test]
+    fn overline_sets_the_top_pixel_row_of_its_cells() {
+        let mut terminal = crate::terminal::TerminalEmulator::new(6, 1, true, 7, 0);
+        terminal.feed_bytes(b"\x1b[53mtop\x1b[55m  ");
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let canvas = rasterizer.render_grid(terminal.grid());
+
+        let (cell_width, _) = rasterizer.canvas_size(1, 1);
+        let top: Vec<u8> = (0..canvas.width()).map(|x| canvas.get_pixel(x, 0).unwrap()).collect();
+        assert!(top[..3 * cell_width].iter().all(|&pixel| pixel == 7));
+        assert!(top[3 * cell_width..].iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn reverse_video_swaps_the_colors_exactly_once() {
+        let mut terminal = crate::terminal::TerminalEmulator::new(3, 1, true, 7, 0);
+        terminal.feed_bytes(b"X\x1b[7mX\x1b[0m\x1b[1;7mX");
+        let reversed = terminal.grid().get_cell(1, 0).unwrap();
+        assert_eq!((reversed.fg_color, reversed.bg_color), (0, 7));
+        assert!(!reversed.flags.contains(CellFlags::REVERSE));
+
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let canvas = rasterizer.render_grid(terminal.grid());
+        let (cell_width, _) = rasterizer.canvas_size(1, 1);
+        let inverse = |pixel: u8| match pixel { 7 => 0, 0 => 7, other => other };
+        for row in canvas.data().chunks(canvas.width()) {
+            let plain = &row[..cell_width];
+            for cell in [&row[cell_width..2 * cell_width], &row[2 * cell_width..]] {
+                assert!(plain.iter().zip(cell).all(|(&a, &b)| inverse(a) == b));
+            }
+        }
+    }
+
+    const UNDERLAY: u8 = 4;
+
+    /// Composite a rendered grid over an underlay the way convert does for transparent themes
+    fn composite_over_underlay(rasterizer: &Rasterizer, grid: &Grid, cursor: Option<(usize, usize)>) -> Canvas {
+        let term_canvas = match cursor {
+            Some((x, y)) => rasterizer.render_grid_with_cursor(grid, x, y),
+            None => rasterizer.render_grid(grid),
+        };
+        let mask = RenderBackend::default_background_mask(rasterizer, grid, &term_canvas, 0, cursor);
+        let mut canvas = Canvas::new(term_canvas.width(), term_canvas.height(), &Palette::default());
+        canvas.fill(UNDERLAY);
+        for y in 0..term_canvas.height() {
+            for x in 0..term_canvas.width() {
+                if !mask[y * term_canvas.width() + x] {
+                    canvas.set_pixel(x, y, term_canvas.get_pixel(x, y).unwrap());
+                }
+            }
+        }
+        canvas
+    }
+
+    fn cell_pixels(canvas: &Canvas, rasterizer: &Rasterizer, col: usize) -> Vec<u8> {
+        let (width, height) = rasterizer.canvas_size(1, 1);
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (col * width + x, y)))
+            .map(|(x, y)| canvas.get_pixel(x, y).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn default_background_cells_reveal_the_underlay() {
+        let rasterizer = Rasterizer::with_font(Font::load(None));
+        let mut grid = Grid::new(4, 1, 7, 0);
+        grid.write_cell(0, 0, Cell::new('A', 7, 0, CellFlags::empty()));
+        // Explicit background, with the glyph drawn in the default background's color
+        grid.write_cell(1, 0, Cell::new('B', 0, 1, CellFlags::empty()));
+        // Reverse video puts the default background in the glyph, not behind it
+        grid.write_cell(2, 0, Cell::new('C', 7, 0, CellFlags::REVERSE));
+
+        let canvas = composite_over_underlay(&rasterizer, &grid, Some((3, 0)));
+
+        let default_cell = cell_pixels(&canvas, &rasterizer, 0);
+        assert!(default_cell.contains(&UNDERLAY), "blank pixels of a default-background cell show the underlay");
+        assert!(default_cell.contains(&7), "the glyph is still drawn");
+        assert!(!default_cell.contains(&0));
+
+        let explicit_cell = cell_pixels(&canvas, &rasterizer, 1);
+        assert!(!explicit_cell.contains(&UNDERLAY));
+        assert!(explicit_cell.contains(&0) && explicit_cell.contains(&1));
+
+        let reversed_cell = cell_pixels(&canvas, &rasterizer, 2);
+        assert!(!reversed_cell.contains(&UNDERLAY));
+        assert!(reversed_cell.contains(&0));
+
+        assert!(!cell_pixels(&canvas, &rasterizer, 3).contains(&UNDERLAY), "the cursor cell stays opaque");
+    }
+}
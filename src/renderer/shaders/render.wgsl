@@ -26,6 +26,7 @@ struct RenderParams {
 @group(0) @binding(4) var<uniform> params: RenderParams;
 
 const REVERSE_FLAG: u32 = 1u;
+const OVERLINE_FLAG: u32 = 2u;
 
 @compute @workgroup_size(8, 8)
 fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
@@ -69,8 +70,9 @@ fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 
     // Select color based on intensity
     // Intensity 0 = background, intensity > 0 = foreground
+    // Overlined cells draw their top pixel row in the foreground
     var color_idx: u32;
-    if (intensity > 0u) {
+    if (intensity > 0u || (gy == 0u && (cell.flags & OVERLINE_FLAG) != 0u)) {
         color_idx = fg_color;
     } else {
         color_idx = bg_color;
@@ -1,4 +1,5 @@
 use bitflags::bitflags;
+use std::fmt::Write as _;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -6,12 +7,20 @@ bitflags! {
         const BOLD = 0b00000001;
         const ITALIC = 0b00000010;
         const UNDERLINE = 0b00000100;
+        /// Swap fg/bg when drawn. The emulator never sets this: SGR 7 is applied
+        /// once, in `write`, by storing the colors already swapped
         const REVERSE = 0b00001000;
         const BLINK = 0b00010000;
+        /// Blank advanced over by a tab, drawn as a guide under --show-tabs
+        const TAB = 0b00100000;
+        /// Hidden text (SGR 8), drawn in the background color
+        const CONCEAL = 0b01000000;
+        /// Line across the top of the cell (SGR 53)
+        const OVERLINE = 0b10000000;
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Cell {
     pub character: char,
     pub fg_color: u8,
@@ -19,6 +28,45 @@ pub struct Cell {
     pub flags: CellFlags,
 }
 
+/// Text of a row of cells with trailing blanks removed (tab guides read as blanks)
+pub fn row_text(cells: &[Cell]) -> String {
+    let text: String = cells
+        .iter()
+        .map(|cell| if cell.flags.contains(CellFlags::TAB) { ' ' } else { cell.character })
+        .collect();
+    text.trim_end().to_string()
+}
+
+/// Like [`row_text`], with SGR codes for each cell's colors, bold, italic and
+/// underline; trailing blanks on the default background are removed
+pub fn row_ansi(cells: &[Cell], default_bg: u8) -> String {
+    let shown = |cell: &Cell| if cell.flags.contains(CellFlags::TAB) { ' ' } else { cell.character };
+    let end = cells.iter()
+        .rposition(|cell| shown(cell) != ' ' || cell.bg_color != default_bg)
+        .map_or(0, |i| i + 1);
+
+    let mut text = String::new();
+    let mut current = None;
+    for cell in &cells[..end] {
+        let style = (cell.fg_color, cell.bg_color, cell.flags & (CellFlags::BOLD | CellFlags::ITALIC | CellFlags::UNDERLINE));
+        if current != Some(style) {
+            text.push_str("\x1b[0");
+            for (flag, code) in [(CellFlags::BOLD, ";1"), (CellFlags::ITALIC, ";3"), (CellFlags::UNDERLINE, ";4")] {
+                if style.2.contains(flag) {
+                    text.push_str(code);
+                }
+            }
+            let _ = write!(text, ";38;5;{};48;5;{}m", style.0, style.1);
+            current = Some(style);
+        }
+        text.push(shown(cell));
+    }
+    if current.is_some() {
+        text.push_str("\x1b[0m");
+    }
+    text
+}
+
 impl Cell {
     pub fn new(character: char, fg_color: u8, bg_color: u8, flags: CellFlags) -> Self {
         Self {
@@ -1,6 +1,6 @@
-use super::Cell;
+use super::{Cell, CellFlags};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Grid {
     cells: Vec<Cell>,
     width: usize,
@@ -39,10 +39,45 @@ impl Grid {
         }
     }
 
+    /// Copy into a grid of a new size, keeping the top-left content and filling new cells with blanks
+    pub fn resized(&self, width: usize, height: usize, fg_color: u8, bg_color: u8) -> Grid {
+        let mut grid = Grid::new(width, height, fg_color, bg_color);
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                grid.cells[y * width + x] = self.cells[y * self.width + x];
+            }
+        }
+        grid
+    }
+
     pub fn cells(&self) -> &[Cell] {
         &self.cells
     }
 
+    /// Number of cells that differ from `other`; every cell counts when the sizes differ
+    pub fn changed_cells(&self, other: &Grid) -> usize {
+        if self.width != other.width || self.height != other.height {
+            return self.cells.len().max(other.cells.len());
+        }
+        self.cells.iter().zip(&other.cells).filter(|(a, b)| a != b).count()
+    }
+
+    /// True when any cell carries the blink attribute (SGR 5)
+    pub fn has_blinking(&self) -> bool {
+        self.cells.iter().any(|cell| cell.flags.contains(CellFlags::BLINK))
+    }
+
+    /// Draw blinking cells in their background color, for the "off" half of the blink cycle
+    pub fn hide_blinking(&mut self) {
+        for cell in self.cells.iter_mut().filter(|cell| cell.flags.contains(CellFlags::BLINK)) {
+            cell.fg_color = cell.bg_color;
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[Cell] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
     pub fn clear(&mut self, fg_color: u8, bg_color: u8) {
         for cell in &mut self.cells {
             *cell = Cell::empty(fg_color, bg_color);
@@ -74,6 +109,10 @@ impl Grid {
     }
 
     pub fn scroll_region_up(&mut self, top: usize, bottom: usize, lines: usize, fg_color: u8, bg_color: u8) {
+        let bottom = bottom.min(self.height.saturating_sub(1));
+        if top > bottom {
+            return;
+        }
         let region_height = bottom.saturating_sub(top) + 1;
         if lines == 0 || lines >= region_height {
             // Clear the entire region
@@ -109,6 +148,10 @@ impl Grid {
     }
 
     pub fn scroll_region_down(&mut self, top: usize, bottom: usize, lines: usize, fg_color: u8, bg_color: u8) {
+        let bottom = bottom.min(self.height.saturating_sub(1));
+        if top > bottom {
+            return;
+        }
         let region_height = bottom.saturating_sub(top) + 1;
         if lines == 0 || lines >= region_height {
             // Clear the entire region
@@ -142,3 +185,26 @@ impl Grid {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_cells_counts_each_differing_cell() {
+        let grid = Grid::new(10, 4, 7, 0);
+        let mut other = grid.clone();
+        assert!(grid == other);
+        assert_eq!(grid.changed_cells(&other), 0);
+
+        other.write_cell(3, 2, Cell::new('x', 7, 0, CellFlags::empty()));
+        assert!(grid != other);
+        assert_eq!(grid.changed_cells(&other), 1);
+
+        // Same character, different attribute
+        other.write_cell(3, 2, Cell::new(' ', 7, 0, CellFlags::BOLD));
+        assert_eq!(grid.changed_cells(&other), 1);
+
+        assert_eq!(grid.changed_cells(&Grid::new(10, 5, 7, 0)), 50);
+    }
+}
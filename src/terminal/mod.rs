@@ -3,12 +3,82 @@ mod grid;
 mod state;
 pub mod parser;
 
-pub use cell::{Cell, CellFlags};
+pub use cell::{row_ansi, row_text, Cell, CellFlags};
 pub use grid::Grid;
 pub use state::TerminalState;
 
 use parser::{Event, Command, EscapeType, parse_ansi_stream};
 use crate::renderer::Palette;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use unicode_width::UnicodeWidthChar;
+
+thread_local! {
+    /// Set while this thread replays events under --fuzz-safe
+    static GUARDED_FEED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Install, once per process, a panic hook that stays quiet for panics caught by
+/// a guarded feed (they become warnings) and reports every other panic as before
+fn install_guarded_panic_hook() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !GUARDED_FEED.with(|guarded| guarded.get()) {
+                previous_hook(info);
+            }
+        }));
+    });
+}
+
+/// Columns between tab stops; a tab moves the cursor to the next multiple of this
+const TAB_STOP_WIDTH: i32 = 8;
+
+/// Private modes that only affect input reporting or the host terminal;
+/// they have no effect on rendered output and are consumed silently
+const IGNORED_PRIVATE_MODES: &[i32] = &[
+    1,     // application cursor keys
+    12,    // cursor blink
+    1000,  // mouse click reporting
+    1002,  // mouse drag reporting
+    1003,  // mouse motion reporting
+    1004,  // focus in/out reporting
+    1005,  // UTF-8 mouse encoding
+    1006,  // SGR mouse encoding
+    1015,  // urxvt mouse encoding
+    1034,  // interpret meta key
+    2004,  // bracketed paste
+];
+
+/// CSI commands (private marker and intermediates included) that query the host
+/// terminal or configure keyboard input; a recording has nothing to draw for them
+const IGNORED_CSI_COMMANDS: &[&str] = &[
+    "c", ">c", "=c",        // device attributes (DA1, DA2, DA3)
+    "n", "?n",              // device status report
+    ">m", "?m",             // xterm key modifier options (modifyOtherKeys)
+    ">u", "<u", "=u", "?u", // kitty keyboard protocol
+];
+
+/// Largest slice of an event parsed at once; output such as `cat bigfile` arrives
+/// as one event of megabytes, which is fed in pieces to bound intermediate buffers
+const FEED_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Longest unterminated escape sequence kept waiting for its end; past this a
+/// control string or CSI is skipped through its terminator (its payload, such as
+/// an OSC 1337 image, is never drawn) and any other ESC is treated as stray
+const MAX_PENDING_ESCAPE: usize = 64 * 1024;
+
+/// How an oversized sequence being skipped ends
+#[derive(Debug, Clone, Copy)]
+enum SequenceEnd {
+    /// OSC, DCS, SOS, PM and APC strings: BEL or ST
+    StringTerminator,
+    /// CSI: the first final byte (0x40-0x7E)
+    CsiFinal,
+}
+
+/// OSC codes that carry metadata only (titles, links, shell integration) and are consumed silently
+const IGNORED_OSC_CODES: &[&str] = &["0", "1", "2", "7", "8", "52", "133", "633", "1337"];
 
 pub struct TerminalEmulator {
     grid: Grid,
@@ -16,8 +86,25 @@ pub struct TerminalEmulator {
     alt_grid: Grid,
     alt_state: TerminalState,
     display_alt_screen: Option<bool>,  // None=main, Some(true)=alt
+    alt_screen_enabled: bool,  // false: alternate screen switches are ignored
+    bs_wrap: bool,  // Backspace at column 0 moves to the end of the previous line
+    show_tabs: bool,  // Mark cells a tab advances over with a guide glyph
+    fuzz_safe: bool,  // Skip, with a warning, any sequence whose handling panics
     extra_text: String,  // Buffer for partial escape sequences across events
+    extra_bytes: Vec<u8>,  // Incomplete UTF-8 sequence at the end of the last event
+    skipping: Option<SequenceEnd>,  // Oversized sequence being dropped up to its terminator
     palette: Palette,
+    bell_count: usize,  // BEL characters seen since the last take_bell()
+    last_char: Option<char>,  // Last printed character, repeated by REP (CSI b)
+    clipped_column: Option<usize>,  // Widest column (1-based) a cursor move asked for past the right edge
+    color_overrides: Vec<Option<(u8, u8, u8)>>,  // OSC 4 palette redefinitions by index
+    initial_colors: (i32, i32),  // Default fg/bg at construction, restored by OSC 110/111 and RIS
+    verbose: bool,
+    reported_modes: HashSet<i32>,  // Unsupported private modes already logged
+    scrollback: Option<VecDeque<Vec<Cell>>>,  // Rows scrolled off the top of the main screen
+    scrollback_limit: usize,
+    collect_unhandled: bool,
+    unhandled: BTreeMap<String, usize>,  // Distinct unsupported sequences and how often each was seen
 }
 
 impl TerminalEmulator {
@@ -35,53 +122,252 @@ impl TerminalEmulator {
             alt_grid: Grid::new(width, height, default_fg, default_bg),
             alt_state,
             display_alt_screen: None,
+            alt_screen_enabled: true,
+            bs_wrap: false,
+            show_tabs: false,
+            fuzz_safe: false,
             extra_text: String::new(),
+            skipping: None,
+            extra_bytes: Vec::new(),
             palette: Palette::default(),
+            bell_count: 0,
+            last_char: None,
+            clipped_column: None,
+            color_overrides: vec![None; 256],
+            initial_colors: (default_fg as i32, default_bg as i32),
+            verbose: false,
+            reported_modes: HashSet::new(),
+            scrollback: None,
+            scrollback_limit: 0,
+            collect_unhandled: false,
+            unhandled: BTreeMap::new(),
         }
     }
 
-    // Exact translation of parser.pyx has_escape lines 367-371
-    fn has_escape(&self, text: &str) -> bool {
-        for ch in text.chars() {
-            if ch as u32 == 0x1B {
-                return true;
-            }
+    /// Record every unsupported escape sequence with a count (see `unhandled`)
+    pub fn collect_unhandled(&mut self, on: bool) {
+        self.collect_unhandled = on;
+    }
+
+    /// Unsupported sequences seen while collecting, e.g. `CSI x` or `OSC 9`, with counts
+    pub fn unhandled(&self) -> &BTreeMap<String, usize> {
+        &self.unhandled
+    }
+
+    /// Widest column (1-based) that absolute cursor positioning asked for beyond
+    /// the screen width, if any; content written there was clipped to the last column
+    pub fn clipped_column(&self) -> Option<usize> {
+        self.clipped_column
+    }
+
+    fn note_column(&mut self, x: i32) {
+        if x >= self.state.width {
+            let column = x as usize + 1;
+            self.clipped_column = Some(self.clipped_column.map_or(column, |widest| widest.max(column)));
+        }
+    }
+
+    fn note_unhandled(&mut self, sequence: impl FnOnce() -> String) {
+        if self.collect_unhandled {
+            *self.unhandled.entry(sequence()).or_insert(0) += 1;
         }
-        false
+    }
+
+    /// Keep up to `limit` rows that scroll off the top of the main screen. Once
+    /// `limit` rows are held, each new row evicts the oldest, so the latest are kept
+    pub fn enable_scrollback(&mut self, limit: usize) {
+        self.scrollback = Some(VecDeque::with_capacity(limit.min(1024)));
+        self.scrollback_limit = limit;
+    }
+
+    /// Rows scrolled off the main screen, oldest first (empty unless enabled)
+    pub fn scrollback(&self) -> impl Iterator<Item = &[Cell]> {
+        self.scrollback.iter().flatten().map(|row| row.as_slice())
+    }
+
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.as_ref().map_or(0, |rows| rows.len())
+    }
+
+    /// The main screen grid, even while the alternate screen is displayed
+    pub fn main_grid(&self) -> &Grid {
+        if self.display_alt_screen.is_some() { &self.alt_grid } else { &self.grid }
+    }
+
+    /// Full session text: scrollback followed by the main screen, trailing blanks trimmed
+    pub fn scrollback_text(&self) -> String {
+        let screen = self.main_grid();
+        let mut lines: Vec<String> = self.scrollback()
+            .chain((0..screen.height()).map(|y| screen.row(y)))
+            .map(row_text)
+            .collect();
+        while lines.last().is_some_and(|line| line.is_empty()) {
+            lines.pop();
+        }
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// Honor alternate screen switches (on by default); when off, full-screen
+    /// programs draw over the main screen and its scrollback
+    pub fn set_alt_screen(&mut self, on: bool) {
+        self.alt_screen_enabled = on;
+    }
+
+    /// Let backspace at column 0 move to the last column of the previous line
+    /// (off by default, where it stays at column 0)
+    pub fn set_bs_wrap(&mut self, on: bool) {
+        self.bs_wrap = on;
+    }
+
+    /// Draw a dim guide in the cells a tab advances over, to make indentation visible
+    pub fn set_show_tabs(&mut self, on: bool) {
+        self.show_tabs = on;
+    }
+
+    /// Contain faults from malformed input: a sequence whose handling panics is
+    /// skipped with a warning and the cursor is pulled back onto the screen
+    pub fn set_fuzz_safe(&mut self, on: bool) {
+        self.fuzz_safe = on;
+        if on {
+            install_guarded_panic_hook();
+        }
+    }
+
+    /// Pick emulation quirks from the recorded TERM: the Linux console treats a
+    /// bare line feed as CR+LF, xterm-likes only move down
+    pub fn set_term(&mut self, term: &str) {
+        let mode = if term == "linux" || term.starts_with("linux-") { "linux" } else { "xterm" };
+        self.set_mode(mode);
+    }
+
+    /// Emulation mode for terminal-specific quirks ("linux" or "xterm")
+    pub fn set_mode(&mut self, mode: &str) {
+        self.state.mode = mode.to_string();
+        self.alt_state.mode = mode.to_string();
+    }
+
+    /// Log unsupported sequences (once each) to stderr
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
     }
 
     // Exact translation of parser.pyx add_event + stream_2_sequence lines 373-360
     pub fn feed_bytes(&mut self, bytes: &[u8]) {
-        let text = String::from_utf8_lossy(bytes);
+        // Split points may fall inside a character or sequence; both are carried over
+        for chunk in bytes.chunks(FEED_CHUNK_BYTES) {
+            self.feed_chunk(chunk);
+        }
+    }
+
+    fn feed_chunk(&mut self, bytes: &[u8]) {
+        // A multi-byte character split across events is held back until it is complete
+        let mut joined;
+        let bytes = if self.extra_bytes.is_empty() {
+            bytes
+        } else {
+            joined = std::mem::take(&mut self.extra_bytes);
+            joined.extend_from_slice(bytes);
+            &joined[..]
+        };
+        let complete = complete_utf8_len(bytes);
+        self.extra_bytes = bytes[complete..].to_vec();
+        let text = String::from_utf8_lossy(&bytes[..complete]);
+
         // Line 386: self.stream_2_sequence(self.extra_text+event_io,timestamp,0)
-        let full_text = format!("{}{}", self.extra_text, text);
+        let mut full_text = std::mem::take(&mut self.extra_text);
+        full_text.push_str(&text);
+
+        loop {
+            if let Some(end) = self.skipping {
+                match find_sequence_end(&full_text, end) {
+                    Some(stop) => {
+                        self.skipping = None;
+                        full_text = full_text[stop..].to_string();
+                    }
+                    // All of it belongs to the sequence being skipped, except an ESC
+                    // at the very end, which may start the ST that closes it
+                    None => {
+                        if full_text.ends_with('\x1b') {
+                            self.extra_text = "\x1b".to_string();
+                        }
+                        return;
+                    }
+                }
+            }
+
+            // Use parser module but we need to track the last parsed position
+            // We'll use parse_ansi_stream_with_position which we need to create
+            let (mut events, last_pos) = parser::parse_ansi_stream_with_position(&full_text);
 
-        // Use parser module but we need to track the last parsed position
-        // We'll use parse_ansi_stream_with_position which we need to create
-        let (events, last_pos) = parser::parse_ansi_stream_with_position(&full_text);
+            // Lines 355-360: an unmatched escape in the remainder may be the start of a
+            // sequence completed by a later event, so keep it; plain text before it is
+            // drawn now rather than waiting for that event
+            let remaining = &full_text[last_pos..];
+            let (text, pending) = remaining.split_at(remaining.find('\x1b').unwrap_or(remaining.len()));
+            if !text.is_empty() {
+                events.push(Event::Text(text.chars().collect()));
+            }
+
+            if self.fuzz_safe {
+                self.process_events_guarded(events);
+            } else {
+                for event in &events {
+                    self.process_event(event);
+                }
+            }
 
-        // Process all parsed events
-        for event in events {
-            match event {
-                Event::Text(chars) => self.cmd_render_text(&chars),
-                Event::Command(cmd) => self.process_command(cmd),
+            if pending.len() <= MAX_PENDING_ESCAPE {
+                self.extra_text = pending.to_string();
+                return;
             }
+            full_text = match pending_sequence(pending) {
+                Some((name, end)) => {
+                    self.note_unhandled(|| format!("{} longer than {} KiB", name, MAX_PENDING_ESCAPE / 1024));
+                    self.skipping = Some(end);
+                    pending[2..].to_string()
+                }
+                // Not the start of a sequence: drop the stray ESC and parse what follows it
+                None => pending[1..].to_string(),
+            };
         }
+    }
 
-        // Lines 355-360: Check if remaining text contains escape
-        let remaining = &full_text[last_pos..];
+    fn process_event(&mut self, event: &Event) {
+        match event {
+            Event::Text(chars) => self.cmd_render_text(chars),
+            Event::Command(cmd) => self.process_command(cmd),
+        }
+    }
 
-        if self.has_escape(remaining) {
-            // Line 356: self.extra_text=text[cursor:]
-            self.extra_text = remaining.to_string();
-        } else {
-            // Line 359-360
-            self.extra_text.clear();
-            if !remaining.is_empty() {
-                let chars: Vec<char> = remaining.chars().collect();
-                self.cmd_render_text(&chars);
+    /// Process events one at a time, turning a panic into a skipped event and a warning
+    fn process_events_guarded(&mut self, events: Vec<Event>) {
+        GUARDED_FEED.with(|guarded| guarded.set(true));
+        for event in &events {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.process_event(event)));
+            if let Err(payload) = result {
+                let reason = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown fault".to_string());
+                eprintln!("Warning: skipped malformed input {:?} ({})", event, reason);
+                self.state.recover();
             }
         }
+        GUARDED_FEED.with(|guarded| guarded.set(false));
+    }
+
+    /// Resize both screens (asciicast "r" events); existing content is kept from the top-left
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == 0 || height == 0 || (width == self.grid.width() && height == self.grid.height()) {
+            return;
+        }
+        let fg = self.state.default_foreground as u8;
+        let bg = self.state.default_background as u8;
+        self.grid = self.grid.resized(width, height, fg, bg);
+        self.alt_grid = self.alt_grid.resized(width, height, fg, bg);
+        self.state.resize(width, height);
+        self.alt_state.resize(width, height);
     }
 
     pub fn grid(&self) -> &Grid {
@@ -92,9 +378,31 @@ impl TerminalEmulator {
         &self.state
     }
 
+    /// Returns true if the bell rang since the last call, and resets the counter
+    pub fn take_bell(&mut self) -> bool {
+        let rang = self.bell_count > 0;
+        self.bell_count = 0;
+        rang
+    }
+
+    /// Palette used to match truecolor and redefined colors to output indices
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Map a color index through any OSC 4 redefinition to the nearest output palette index
+    fn resolve_color(&self, color: i32) -> i32 {
+        match self.color_overrides.get(color as usize) {
+            Some(Some((r, g, b))) if color >= 0 => {
+                self.palette.match_color_index(*r as i32, *g as i32, *b as i32) as i32
+            }
+            _ => color,
+        }
+    }
+
     // Exact translation of terminal_graphics.pyx alternate_screen_on lines 77-89
     fn alternate_screen_on(&mut self) {
-        if self.display_alt_screen.is_none() {
+        if self.display_alt_screen.is_none() && self.alt_screen_enabled {
             self.display_alt_screen = Some(true);
             // Swap grids
             std::mem::swap(&mut self.grid, &mut self.alt_grid);
@@ -116,54 +424,103 @@ impl TerminalEmulator {
 
     // Exact translation of parser.pyx cmd_render_text lines 59-90
     fn cmd_render_text(&mut self, data: &[char]) {
+        const BEL: u32 = 7;  // Bell
         const BS: u32 = 8;   // Backspace
         const FI: u32 = 9;   // Forward Index
         const LF: u32 = 10;  // Line feed
         const CR: u32 = 13;  // Carriage return
+        const DEL: u32 = 0x7F; // Delete (padding on real terminals, never a glyph)
 
         self.state.text_mode_on();
 
         for &character in data {
             // Line 68-70: while self.g.state.scroll!=0:
-            while self.state.scroll != 0 {
-                self.scroll_buffer();
-            }
+            self.apply_scroll();
 
             let char_ord = character as u32;
 
             // Line 73-84: if char_ord<32 and self.no_codes==None:
             if char_ord < 32 {
-                if char_ord == BS {
-                    self.state.cursor_left(1);
+                if char_ord == BEL {
+                    self.bell_count += 1;
+                } else if char_ord == BS {
+                    if self.bs_wrap && self.state.cursor_x == 0 && self.state.cursor_y > 0 {
+                        self.state.cursor_absolute(self.state.width - 1, self.state.cursor_y - 1);
+                    } else {
+                        self.state.cursor_left(1);
+                    }
                 } else if char_ord == FI {
-                    self.state.cursor_right(1);
+                    // The next tab stop, or the last column when none is left; a tab never wraps
+                    let start_x = self.state.cursor_x;
+                    let stop = ((start_x / TAB_STOP_WIDTH + 1) * TAB_STOP_WIDTH).min(self.state.width - 1);
+                    self.state.cursor_absolute_x(stop);
+                    if self.show_tabs {
+                        self.write_tab_guide(start_x, self.state.cursor_x);
+                    }
                 } else if char_ord == LF {
                     self.state.cursor_down(1);
                     if self.state.mode == "linux" {
                         self.state.cursor_absolute_x(0);
                     }
                 } else if char_ord == CR {
+                    // CR only moves the cursor: a shorter redraw leaves the old tail
+                    // in place ("100%\r50%" reads "50%%"), and check_bounds drops any
+                    // pending wrap now that the cursor has left the last column
                     self.state.cursor_absolute_x(0);
                 }
+            } else if char_ord == DEL {
+                continue;
             } else {
                 // Line 86-89
+                self.last_char = Some(character);
                 if self.state.pending_wrap {
+                    // The wrap may scroll; do it now so the character lands on the new line
                     self.state.cursor_right(1);
+                    self.apply_scroll();
+                }
+                if character.width() == Some(2) {
+                    self.write_wide(char_ord);
+                    continue;
                 }
                 self.write(char_ord);
                 self.state.cursor_right(1);
             }
         }
 
+        // A trailing line feed scrolls now rather than waiting for the next character,
+        // so the grid between events never shows the old bottom line under the cursor
+        self.apply_scroll();
         self.state.text_mode_off();
     }
 
+    /// Fill the cells from `start_x` up to (not including) `end_x` on the cursor row
+    /// with tab guides: a dim marker in the first cell, blanks after it
+    fn write_tab_guide(&mut self, start_x: i32, end_x: i32) {
+        const GUIDE: char = '→';  // Present in CP437 bitmap fonts as well as TrueType ones
+        const GUIDE_COLOR: u8 = 8;  // Bright black
+        let bg = self.resolve_color(self.state.background) as u8;
+        let y = self.state.cursor_y as usize;
+        for x in start_x..end_x {
+            let character = if x == start_x { GUIDE } else { ' ' };
+            let cell = Cell::new(character, GUIDE_COLOR, bg, CellFlags::TAB);
+            self.grid.write_cell(x as usize, y, cell);
+        }
+    }
+
+    /// Carry out scrolling queued by cursor movement past the scroll region
+    fn apply_scroll(&mut self) {
+        while self.state.scroll != 0 {
+            self.scroll_buffer();
+        }
+    }
+
     // Write a character - like terminal_graphics.write()
     fn write(&mut self, char_ord: u32) {
-        let mut fg = self.state.foreground;
-        let mut bg = self.state.background;
+        let mut fg = self.resolve_color(self.state.foreground);
+        let mut bg = self.resolve_color(self.state.background);
 
-        // Apply reverse video
+        // Apply reverse video here, and only here: the stored colors are final, so the
+        // cell must not also carry CellFlags::REVERSE or renderers would swap them back
         if self.state.reverse_video {
             std::mem::swap(&mut fg, &mut bg);
         }
@@ -178,12 +535,47 @@ impl TerminalEmulator {
         self.grid.write_cell(self.state.cursor_x as usize, self.state.cursor_y as usize, cell);
     }
 
+    /// Write a double-width character over two cells: the glyph, then a blank
+    /// continuation cell with the same attributes
+    fn write_wide(&mut self, char_ord: u32) {
+        // A wide character never splits across lines: with only the last column
+        // left it is blanked and the character wraps to the next line
+        if self.state.autowrap && self.state.cursor_x == self.state.width - 1 && self.state.width > 1 {
+            self.write(' ' as u32);
+            self.state.cursor_right(1);
+            self.state.cursor_right(1);
+            self.apply_scroll();
+        }
+
+        self.write(char_ord);
+        if self.state.cursor_x < self.state.width - 1 {
+            self.state.cursor_right(1);
+            self.write(' ' as u32);
+        }
+        self.state.cursor_right(1);
+    }
+
     fn scroll_buffer(&mut self) {
         let amount = self.state.scroll.abs() as usize;
         let fg = self.state.foreground as u8;
         let bg = self.state.background as u8;
 
         if self.state.scroll > 0 {
+            // Rows leaving the top of the main screen go to scrollback
+            if self.state.scroll_top == 0 && self.display_alt_screen.is_none() {
+                if let Some(ref mut scrollback) = self.scrollback {
+                    let region_height = self.state.scroll_bottom as usize + 1;
+                    for y in 0..amount.min(region_height).min(self.grid.height()) {
+                        if scrollback.len() == self.scrollback_limit {
+                            scrollback.pop_front();
+                        }
+                        if self.scrollback_limit > 0 {
+                            scrollback.push_back(self.grid.row(y).to_vec());
+                        }
+                    }
+                }
+            }
+
             // Scroll up
             self.grid.scroll_region_up(
                 self.state.scroll_top as usize,
@@ -206,16 +598,81 @@ impl TerminalEmulator {
         self.state.scroll = 0;
     }
 
-    fn process_command(&mut self, cmd: Command) {
+    fn process_command(&mut self, cmd: &Command) {
         match cmd.esc_type {
             EscapeType::Single => self.process_single(&cmd.command),
             EscapeType::Csi => self.process_csi(&cmd.command, &cmd.params),
-            EscapeType::CharSet | EscapeType::G0 | EscapeType::G1 | EscapeType::Osc | EscapeType::Title => {
+            EscapeType::Osc => self.process_osc(&cmd.command),
+            EscapeType::CharSet | EscapeType::G0 | EscapeType::G1 | EscapeType::Title => {
                 // Ignore
             }
             EscapeType::BracketPaste => {
                 // Ignore
             }
+            EscapeType::Dcs => {
+                // Consumed so sixel, DECRQSS and tmux passthrough don't leak as text;
+                // nothing is drawn until sixel graphics are supported
+            }
+        }
+    }
+
+    // OSC: only color control is acted on, everything else (titles, hyperlinks) is dropped
+    fn process_osc(&mut self, body: &str) {
+        let (code, args) = body.split_once(';').unwrap_or((body, ""));
+        match code {
+            // OSC 4 ; index ; spec [; index ; spec ...] - redefine palette entries
+            "4" => {
+                let parts: Vec<&str> = args.split(';').collect();
+                for pair in parts.chunks_exact(2) {
+                    if let (Ok(index), Some(rgb)) = (pair[0].parse::<u8>(), parse_color_spec(pair[1])) {
+                        self.color_overrides[index as usize] = Some(rgb);
+                    }
+                }
+            }
+            // OSC 104 [; index ...] - reset palette entries (all when no index given)
+            "104" => {
+                if args.is_empty() {
+                    self.color_overrides.iter_mut().for_each(|c| *c = None);
+                } else {
+                    for index in args.split(';').filter_map(|s| s.parse::<u8>().ok()) {
+                        self.color_overrides[index as usize] = None;
+                    }
+                }
+            }
+            // OSC 10 / 11 ; spec - change the default foreground / background
+            "10" | "11" => {
+                if let Some((r, g, b)) = parse_color_spec(args) {
+                    let color = self.palette.match_color_index(r as i32, g as i32, b as i32) as i32;
+                    if code == "10" {
+                        self.set_default_colors(Some(color), None);
+                    } else {
+                        self.set_default_colors(None, Some(color));
+                    }
+                }
+            }
+            // OSC 110 / 111 - restore the original default foreground / background
+            "110" => self.set_default_colors(Some(self.initial_colors.0), None),
+            "111" => self.set_default_colors(None, Some(self.initial_colors.1)),
+            _ if IGNORED_OSC_CODES.contains(&code) => {}
+            _ => self.note_unhandled(|| format!("OSC {}", code)),
+        }
+    }
+
+    /// Change default colors on both screens; a current color still at its old default follows the change
+    fn set_default_colors(&mut self, fg: Option<i32>, bg: Option<i32>) {
+        for state in [&mut self.state, &mut self.alt_state] {
+            if let Some(fg) = fg {
+                if state.foreground == state.default_foreground {
+                    state.foreground = fg;
+                }
+                state.default_foreground = fg;
+            }
+            if let Some(bg) = bg {
+                if state.background == state.default_background {
+                    state.background = bg;
+                }
+                state.default_background = bg;
+            }
         }
     }
 
@@ -223,12 +680,36 @@ impl TerminalEmulator {
     fn process_single(&mut self, command: &str) {
         if command == "7" {
             self.state.cursor_save_position();
-        }
-        if command == "8" {
+        } else if command == "8" {
             self.state.cursor_restore_position();
+        } else if command == "c" {
+            self.cmd_ris();
+        } else if command == "=" || command == ">" || command == "Z" {
+            // Keypad modes and terminal identification do not affect output
+        } else {
+            self.note_unhandled(|| format!("ESC {}", command));
         }
     }
 
+    // RIS (ESC c): full reset - back to the main screen with a cleared grid
+    fn cmd_ris(&mut self) {
+        self.alternate_screen_off();
+        self.set_default_colors(Some(self.initial_colors.0), Some(self.initial_colors.1));
+        self.state.reset();
+        self.alt_state.reset();
+        let fg = self.state.default_foreground as u8;
+        let bg = self.state.default_background as u8;
+        self.grid.clear(fg, bg);
+        self.alt_grid.clear(fg, bg);
+        self.color_overrides.iter_mut().for_each(|c| *c = None);
+        self.last_char = None;
+    }
+
+    // DECSTR (CSI ! p): soft reset - state only, grid contents are kept
+    fn cmd_decstr(&mut self) {
+        self.state.reset();
+    }
+
     // Exact translation of parser.pyx process_CSI lines 260-317
     fn process_csi(&mut self, command: &str, params: &[i32]) {
         let param_len = params.len();
@@ -271,6 +752,10 @@ impl TerminalEmulator {
             self.cmd_el(value1);
         } else if command == "P" {
             self.cmd_dch(value1);
+        } else if command == "@" {
+            self.cmd_ich(value1);
+        } else if command == "b" {
+            self.cmd_rep(value1);
         } else if command == "X" {
             self.cmd_ech(value1);
         } else if command == "d" {
@@ -295,6 +780,12 @@ impl TerminalEmulator {
             self.cmd_decset(value1);
         } else if command == "?l" {
             self.cmd_decrst(value1);
+        } else if command == "!p" {
+            self.cmd_decstr();
+        } else if IGNORED_CSI_COMMANDS.contains(&command) {
+            // Answered by the host terminal, if at all; nothing changes on screen
+        } else {
+            self.note_unhandled(|| format!("CSI {}", command));
         }
     }
 
@@ -306,8 +797,8 @@ impl TerminalEmulator {
             self.state.show_cursor();
         } else if code == 1049 {
             self.alternate_screen_on();
-        } else if code == 2004 {
-            // bracketed paste - ignore
+        } else {
+            self.ignore_private_mode(code, 'h');
         }
     }
 
@@ -319,11 +810,21 @@ impl TerminalEmulator {
             self.state.hide_cursor();
         } else if code == 1049 {
             self.alternate_screen_off();
-        } else if code == 2004 {
-            // bracketed paste - ignore
+        } else {
+            self.ignore_private_mode(code, 'l');
         }
     }
 
+    fn ignore_private_mode(&mut self, code: i32, action: char) {
+        if IGNORED_PRIVATE_MODES.contains(&code) {
+            return;
+        }
+        if self.verbose && self.reported_modes.insert(code) {
+            eprintln!("Note: unsupported private mode {} ignored", code);
+        }
+        self.note_unhandled(|| format!("CSI ?{}{}", code, action));
+    }
+
     // Exact translation of cmd_bracketed_paste lines 348-356
     fn cmd_bracketed_paste(&mut self, _value: i32) {
         // bracketed paste handling - ignore for now
@@ -336,14 +837,24 @@ impl TerminalEmulator {
             self.state.set_background(self.state.default_background);
             self.state.bold = false;
             self.state.reverse_video = false;
+            self.state.flags.remove(CellFlags::BLINK | CellFlags::CONCEAL | CellFlags::OVERLINE);
         } else if cmd == 1 {
             self.state.bold = true;
+        } else if cmd == 5 || cmd == 6 {
+            // Slow and rapid blink both blink at the same rate
+            self.state.flags.insert(CellFlags::BLINK);
         } else if cmd == 7 {
             self.state.reverse_video = true;
+        } else if cmd == 8 {
+            self.state.flags.insert(CellFlags::CONCEAL);
         } else if cmd == 22 {
             self.state.bold = false;
+        } else if cmd == 25 {
+            self.state.flags.remove(CellFlags::BLINK);
         } else if cmd == 27 {
             self.state.reverse_video = false;
+        } else if cmd == 28 {
+            self.state.flags.remove(CellFlags::CONCEAL);
         } else if cmd >= 30 && cmd <= 37 {
             if self.state.bold {
                 self.set_foreground(cmd - 30 + 8);
@@ -360,6 +871,10 @@ impl TerminalEmulator {
             }
         } else if cmd == 49 {
             self.state.set_background(self.state.default_background);
+        } else if cmd == 53 {
+            self.state.flags.insert(CellFlags::OVERLINE);
+        } else if cmd == 55 {
+            self.state.flags.remove(CellFlags::OVERLINE);
         } else if cmd >= 90 && cmd <= 97 {
             self.set_foreground(cmd - 90 + 8);
         } else if cmd >= 100 && cmd <= 107 {
@@ -473,11 +988,13 @@ impl TerminalEmulator {
 
     // Exact translation of cmd_CHA lines 464-465
     fn cmd_cha(&mut self, x: i32) {
+        self.note_column(x);
         self.state.cursor_absolute_x(x);
     }
 
     // Exact translation of cmd_CUP lines 467-468
     fn cmd_cup(&mut self, x: i32, y: i32) {
+        self.note_column(x);
         self.state.cursor_absolute(x, y);
     }
 
@@ -489,7 +1006,8 @@ impl TerminalEmulator {
                 self.state.cursor_absolute_x(x);
                 self.write(0);
             }
-            for y in 0..self.state.cursor_y - 1 {
+            // Every row above the cursor's (none when it is on the top row)
+            for y in 0..cp.1 {
                 for x in 0..self.state.width {
                     self.state.cursor_absolute(x, y);
                     self.write(0);
@@ -543,6 +1061,8 @@ impl TerminalEmulator {
         let x = self.state.cursor_x;
         let y = self.state.cursor_y;
         let width = self.state.width;
+        // A missing or zero count means one; more than the rest of the row clears to its end
+        let distance = distance.clamp(1, (width - x).max(1));
 
         // Copy elements to buffer
         for x2 in (x + distance)..width {
@@ -558,10 +1078,42 @@ impl TerminalEmulator {
         }
     }
 
+    // ICH (CSI n @): insert blanks at the cursor, pushing the rest of the row right
+    fn cmd_ich(&mut self, distance: i32) {
+        let x = self.state.cursor_x;
+        let y = self.state.cursor_y;
+        let width = self.state.width;
+        let distance = distance.clamp(1, (width - x).max(1));
+
+        for x2 in ((x + distance)..width).rev() {
+            if let Some(cell) = self.grid.get_cell((x2 - distance) as usize, y as usize).cloned() {
+                self.grid.write_cell(x2 as usize, y as usize, cell);
+            }
+        }
+
+        for x2 in x..(x + distance) {
+            let cell = Cell::empty(self.state.foreground as u8, self.state.background as u8);
+            self.grid.write_cell(x2 as usize, y as usize, cell);
+        }
+    }
+
+    // REP (CSI n b): print the last printed character n more times, wrapping
+    // and scrolling exactly as if it had been sent that many times
+    fn cmd_rep(&mut self, count: i32) {
+        if let Some(character) = self.last_char {
+            // Beyond a full screen more repeats only scroll the same character through
+            let screen = (self.state.width * self.state.height).max(1);
+            let repeated = vec![character; count.clamp(1, screen) as usize];
+            self.cmd_render_text(&repeated);
+        }
+    }
+
     // Exact translation of cmd_ECH lines 530-535
     fn cmd_ech(&mut self, distance: i32) {
         let cp = self.state.cursor_get_position();
-        for x in self.state.cursor_x..(self.state.cursor_x + distance) {
+        // Erasing stops at the right edge
+        let end = self.state.cursor_x.saturating_add(distance).min(self.state.width);
+        for x in self.state.cursor_x..end {
             self.state.cursor_absolute_x(x);
             self.write(0);
         }
@@ -570,11 +1122,13 @@ impl TerminalEmulator {
 
     // Exact translation of cmd_HVP lines 537-538
     fn cmd_hvp(&mut self, x: i32, y: i32) {
+        self.note_column(x);
         self.state.cursor_absolute(x, y);
     }
 
     // Exact translation of cmd_HPA lines 540-541
     fn cmd_hpa(&mut self, x: i32) {
+        self.note_column(x);
         self.state.cursor_absolute_x(x);
     }
 
@@ -593,3 +1147,462 @@ impl TerminalEmulator {
         self.state.cursor_absolute(0, position);
     }
 }
+
+/// Parse an X11 color spec (`rgb:R/G/B` with 1-4 hex digits per channel, or `#RRGGBB`)
+fn parse_color_spec(spec: &str) -> Option<(u8, u8, u8)> {
+    if let Some(rgb) = spec.strip_prefix("rgb:") {
+        let channels: Vec<u8> = rgb.split('/')
+            .map(|c| {
+                let value = u32::from_str_radix(c, 16).ok()?;
+                let max = match c.len() {
+                    1..=4 => (1u32 << (4 * c.len())) - 1,
+                    _ => return None,
+                };
+                Some((value * 255 / max) as u8)
+            })
+            .collect::<Option<_>>()?;
+        if channels.len() == 3 {
+            return Some((channels[0], channels[1], channels[2]));
+        }
+    } else if let Some(hex) = spec.strip_prefix('#') {
+        if hex.len() == 6 {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            return Some(((value >> 16) as u8, (value >> 8) as u8, value as u8));
+        }
+    }
+    None
+}
+
+/// Name and ending of the sequence an unterminated `pending` text (starting with
+/// ESC) opens, if its introducer starts a control string or CSI
+fn pending_sequence(pending: &str) -> Option<(&'static str, SequenceEnd)> {
+    match pending.as_bytes().get(1)? {
+        b']' => Some(("OSC", SequenceEnd::StringTerminator)),
+        b'P' => Some(("DCS", SequenceEnd::StringTerminator)),
+        b'X' | b'^' | b'_' => Some(("SOS/PM/APC", SequenceEnd::StringTerminator)),
+        b'[' => Some(("CSI", SequenceEnd::CsiFinal)),
+        _ => None,
+    }
+}
+
+/// Byte offset just past the terminator of a sequence being skipped
+fn find_sequence_end(text: &str, end: SequenceEnd) -> Option<usize> {
+    match end {
+        SequenceEnd::StringTerminator => text.char_indices().find_map(|(i, c)| match c {
+            '\x07' | '\u{9c}' => Some(i + c.len_utf8()),
+            '\x1b' if text[i + 1..].starts_with('\\') => Some(i + 2),
+            _ => None,
+        }),
+        SequenceEnd::CsiFinal => text.bytes().position(|b| (0x40..=0x7E).contains(&b)).map(|i| i + 1),
+    }
+}
+
+/// Length of `bytes` without a trailing incomplete UTF-8 sequence
+fn complete_utf8_len(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let start = bytes.len() - back;
+        let byte = bytes[start];
+        // Skip continuation bytes until the byte that starts the last character
+        if byte & 0xC0 != 0x80 {
+            let needed = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            return if needed > back { start } else { bytes.len() };
+        }
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Terminal with the common defaults (light gray on black)
+    fn terminal(width: usize, height: usize) -> TerminalEmulator {
+        TerminalEmulator::new(width, height, true, 7, 0)
+    }
+
+    fn screen_text(terminal: &TerminalEmulator) -> Vec<String> {
+        (0..terminal.grid().height()).map(|y| row_text(terminal.grid().row(y))).collect()
+    }
+
+    #[test]
+    fn full_reset_restores_scroll_region_and_attributes() {
+        let mut terminal = terminal(10, 4);
+        terminal.feed_bytes(b"\x1b[2;3r\x1b[1;31;44mtext\x1b[3;5H");
+        terminal.feed_bytes(b"\x1bc");
+
+        let state = terminal.state();
+        assert_eq!((state.scroll_top, state.scroll_bottom), (0, 3));
+        assert_eq!((state.foreground, state.background), (7, 0));
+        assert!(!state.bold && state.flags.is_empty());
+        assert_eq!(state.cursor_get_position(), (0, 0));
+        assert!(screen_text(&terminal).iter().all(|line| line.is_empty()));
+    }
+
+    #[test]
+    fn soft_reset_keeps_the_screen() {
+        let mut terminal = terminal(10, 4);
+        terminal.feed_bytes(b"\x1b[1;31mtext\x1b[2;3r\x1b[!p");
+
+        let state = terminal.state();
+        assert_eq!((state.scroll_top, state.scroll_bottom), (0, 3));
+        assert_eq!((state.foreground, state.background), (7, 0));
+        assert_eq!(screen_text(&terminal)[0], "text");
+    }
+
+    #[test]
+    fn osc_4_redefines_a_palette_entry() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"\x1b[31mA\x1b]4;1;rgb:00/00/ff\x07\x1b[31mB");
+
+        let blue = Palette::default().match_color_index(0, 0, 255);
+        assert_eq!(terminal.grid().get_cell(0, 0).unwrap().fg_color, 1);
+        assert_eq!(terminal.grid().get_cell(1, 0).unwrap().fg_color, blue);
+    }
+
+    #[test]
+    fn osc_11_changes_the_background_of_cleared_cells() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"\x1b]11;rgb:00/00/80\x07\x1b[2J");
+
+        let navy = Palette::default().match_color_index(0, 0, 128);
+        assert_ne!(navy, 0);
+        assert!((0..10).all(|x| terminal.grid().get_cell(x, 1).unwrap().bg_color == navy));
+    }
+
+    #[test]
+    fn focus_reporting_is_consumed_and_unknown_modes_logged_once() {
+        let mut terminal = terminal(10, 2);
+        terminal.set_verbose(true);
+        terminal.collect_unhandled(true);
+        terminal.feed_bytes(b"a\x1b[?1004hb\x1b[?1004l");
+        assert_eq!(screen_text(&terminal), ["ab", ""]);
+        assert!(terminal.unhandled().is_empty());
+        assert!(terminal.reported_modes.is_empty());
+
+        terminal.feed_bytes(b"\x1b[?9999h\x1b[?9999h");
+        assert_eq!(terminal.reported_modes.len(), 1);
+    }
+
+    #[test]
+    fn scrollback_keeps_the_latest_lines_up_to_the_limit() {
+        let mut emulator = terminal(10, 2);
+        emulator.enable_scrollback(100);
+        emulator.feed_bytes(b"one\r\ntwo\r\nthree\r\nfour");
+
+        let scrolled: Vec<String> = emulator.scrollback().map(row_text).collect();
+        assert_eq!(scrolled, ["one", "two"]);
+        assert_eq!(screen_text(&emulator), ["three", "four"]);
+
+        // Five lines scroll three rows off; a limit of two drops the oldest
+        let mut overflowed = terminal(10, 2);
+        overflowed.enable_scrollback(2);
+        overflowed.feed_bytes(b"one\r\ntwo\r\nthree\r\nfour\r\nfive");
+
+        let scrolled: Vec<String> = overflowed.scrollback().map(row_text).collect();
+        assert_eq!(scrolled, ["two", "three"]);
+        assert_eq!(screen_text(&overflowed), ["four", "five"]);
+    }
+
+    #[test]
+    fn unhandled_final_byte_is_collected() {
+        let mut terminal = terminal(10, 2);
+        terminal.collect_unhandled(true);
+        terminal.feed_bytes(b"\x1b[1xa\x1b[2x");
+
+        assert_eq!(terminal.unhandled().get("CSI x"), Some(&2));
+        assert_eq!(screen_text(&terminal)[0], "a");
+    }
+
+    #[test]
+    fn carriage_return_overwrites_without_erasing() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"100%\r50%");
+        assert_eq!(screen_text(&terminal)[0], "50%%");
+        assert_eq!(terminal.state().cursor_get_position(), (3, 0));
+    }
+
+    #[test]
+    fn del_writes_nothing() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"a\x7fb");
+
+        assert_eq!(screen_text(&terminal)[0], "ab");
+        assert_eq!(terminal.state().cursor_get_position(), (2, 0));
+    }
+
+    #[test]
+    fn sequences_and_characters_split_across_events_are_joined() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"a\x1b");
+        // Text before the unfinished sequence is drawn right away
+        assert_eq!(screen_text(&terminal)[0].trim_end_matches(['\0', ' ']), "a");
+        terminal.feed_bytes(b"[31");
+        terminal.feed_bytes(b"mb");
+        let cell = terminal.grid().get_cell(1, 0).unwrap();
+        assert_eq!((cell.character, cell.fg_color), ('b', 1));
+
+        // "é" is two bytes
+        terminal.feed_bytes(&[0xC3]);
+        terminal.feed_bytes(&[0xA9]);
+        assert_eq!(terminal.grid().get_cell(2, 0).unwrap().character, 'é');
+        assert_eq!(screen_text(&terminal)[0].trim_end_matches(['\0', ' ']), "abé");
+    }
+
+    #[test]
+    fn dcs_strings_write_nothing() {
+        let mut terminal = terminal(10, 2);
+        // DECRQSS reply, then a multi-line sixel body
+        terminal.feed_bytes(b"\x1bP1$r0;1m\x1b\\\x1bPq#0;2;0;0;0~~\n--\x1b\\ok");
+        assert_eq!(screen_text(&terminal)[0].trim_end_matches(['\0', ' ']), "ok");
+        assert_eq!(screen_text(&terminal)[1].trim_end_matches(['\0', ' ']), "");
+    }
+
+    #[test]
+    fn oversized_dch_clears_only_from_the_cursor() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"0123456789\x1b[1;6H\x1b[999P");
+        assert_eq!(screen_text(&terminal)[0], "01234");
+
+        // ICH pushes the rest of the row right, dropping what falls off the end
+        terminal.feed_bytes(b"\x1b[1;2H\x1b[2@");
+        assert_eq!(screen_text(&terminal)[0], "0  1234");
+    }
+
+    #[test]
+    fn erase_above_clears_up_to_the_cursor() {
+        let mut terminal = terminal(6, 3);
+        terminal.feed_bytes(b"abcdef\r\nghijkl\r\nmnopqr\x1b[1;3H\x1b[1J");
+        assert_eq!(screen_text(&terminal), ["\0\0\0def", "ghijkl", "mnopqr"]);
+
+        // From a lower row every row above goes too
+        terminal.feed_bytes(b"\x1b[3;2H\x1b[1J");
+        assert_eq!(screen_text(&terminal), ["\0\0\0\0\0\0", "\0\0\0\0\0\0", "\0\0opqr"]);
+    }
+
+    #[test]
+    fn wide_character_at_the_last_column_wraps() {
+        let mut terminal = terminal(4, 2);
+        terminal.feed_bytes("abc漢".as_bytes());
+        assert_eq!(screen_text(&terminal)[0], "abc");
+        assert_eq!(terminal.grid().get_cell(3, 0).unwrap().character, ' ');
+        assert_eq!(terminal.grid().get_cell(0, 1).unwrap().character, '漢');
+        assert_eq!(terminal.grid().get_cell(1, 1).unwrap().character, ' ');
+
+        // The next character goes after both of its cells
+        terminal.feed_bytes(b"d");
+        assert_eq!(terminal.grid().get_cell(2, 1).unwrap().character, 'd');
+    }
+
+    #[test]
+    fn disabled_alt_screen_keeps_output_on_the_main_grid() {
+        let session = b"shell\r\n\x1b[?1049hvim\x1b[?1049l";
+
+        let mut switching = terminal(10, 2);
+        switching.feed_bytes(session);
+        assert_eq!(screen_text(&switching), ["shell", ""]);
+
+        let mut main_only = terminal(10, 2);
+        main_only.set_alt_screen(false);
+        main_only.feed_bytes(session);
+        assert_eq!(screen_text(&main_only), ["shell", "vim"]);
+    }
+
+    #[test]
+    fn rep_repeats_the_last_printed_character() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"A\x1b[4b");
+        assert_eq!(screen_text(&terminal)[0].trim_end_matches(['\0', ' ']), "AAAAA");
+        assert_eq!(terminal.state().cursor_get_position(), (5, 0));
+    }
+
+    #[test]
+    fn backspace_at_column_0_wraps_only_with_bs_wrap() {
+        let mut clamped = terminal(6, 3);
+        clamped.feed_bytes(b"\x1b[2;1H\x08");
+        assert_eq!(clamped.state().cursor_get_position(), (0, 1));
+
+        let mut wrapping = terminal(6, 3);
+        wrapping.set_bs_wrap(true);
+        wrapping.feed_bytes(b"\x1b[2;1H\x08");
+        assert_eq!(wrapping.state().cursor_get_position(), (5, 0));
+        // Nowhere to go from the top-left corner
+        wrapping.feed_bytes(b"\x1b[1;1H\x08");
+        assert_eq!(wrapping.state().cursor_get_position(), (0, 0));
+    }
+
+    #[test]
+    fn lines_past_the_bottom_scroll_exactly_once_each() {
+        let mut scrolled = terminal(6, 3);
+        // Five lines on a three-row screen: two scrolls
+        scrolled.feed_bytes(b"one\r\ntwo\r\nthree\r\nfour\r\nfive");
+        assert_eq!(screen_text(&scrolled), ["three", "four", "five"]);
+
+        // A trailing line feed scrolls right away, leaving a blank bottom row
+        scrolled.feed_bytes(b"\r\n");
+        assert_eq!(screen_text(&scrolled), ["four", "five", ""]);
+
+        // Wrapping off the bottom row scrolls once, with the character on the new line
+        let mut wrapped = terminal(3, 2);
+        wrapped.feed_bytes(b"abc\r\ndefg");
+        assert_eq!(screen_text(&wrapped), ["def", "g"]);
+    }
+
+    #[test]
+    fn show_tabs_marks_where_a_tab_advanced() {
+        let mut plain = terminal(20, 1);
+        plain.feed_bytes(b"\tx");
+        assert!(!plain.grid().get_cell(0, 0).unwrap().flags.contains(CellFlags::TAB));
+        assert_eq!(screen_text(&plain)[0], "        x");
+
+        // Every cell the tab skipped is a guide, up to the stop at column 8
+        let mut guided = terminal(20, 1);
+        guided.set_show_tabs(true);
+        guided.feed_bytes(b"\tx");
+        let guides: Vec<usize> = (0..20).filter(|&x| guided.grid().get_cell(x, 0).unwrap().flags.contains(CellFlags::TAB)).collect();
+        assert_eq!(guides, (0..8).collect::<Vec<_>>());
+        assert_eq!(guided.grid().get_cell(8, 0).unwrap().character, 'x');
+        let guide = guided.grid().get_cell(0, 0).unwrap();
+        assert_eq!((guide.character, guide.fg_color), ('→', 8));
+        // Guides read as blanks in text output
+        assert_eq!(screen_text(&guided)[0], "        x");
+
+        // Past the last stop a tab stops at the final column
+        let mut last = terminal(20, 1);
+        last.feed_bytes(b"\t\t\tx");
+        assert_eq!(last.state().cursor_get_position(), (19, 0));
+        assert_eq!(last.grid().get_cell(19, 0).unwrap().character, 'x');
+    }
+
+    #[test]
+    fn cursor_moves_past_the_width_are_noted() {
+        let mut terminal = terminal(10, 2);
+        terminal.feed_bytes(b"\x1b[1;10Hx\x1b[2Gy");
+        assert_eq!(terminal.clipped_column(), None);
+
+        // CUP, CHA and HPA all count; the widest one is kept
+        terminal.feed_bytes(b"\x1b[1;120Hx\x1b[40G\x1b[60`");
+        assert_eq!(terminal.clipped_column(), Some(120));
+    }
+
+    #[test]
+    fn random_cursor_moves_never_panic() {
+        let finals = [
+            'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'f', 'd', '`', 'a', 'e', 'P', '@', 'X', 'L', 'M', 'S', 'T', 'b', 'r', 'J', 'K',
+        ];
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        let numbers: [u64; 10] = [0, 1, 2, 5, 79, 80, 81, 1000, 65535, 4_294_967_295];
+
+        let mut emulator = terminal(8, 4);
+        for _ in 0..5000 {
+            let mut bytes = match next(3) {
+                0 => format!("\x1b[{}{}", numbers[next(numbers.len())], finals[next(finals.len())]),
+                1 => format!("\x1b[{};{}{}", numbers[next(numbers.len())], numbers[next(numbers.len())], finals[next(finals.len())]),
+                _ => ((b'!' + next(94) as u8) as char).to_string(),
+            };
+            if next(10) == 0 {
+                bytes.push_str("\r\n");
+            }
+            emulator.feed_bytes(bytes.as_bytes());
+        }
+
+        let (x, y) = emulator.state().cursor_get_position();
+        assert!((0..8).contains(&x) && (0..4).contains(&y));
+
+        let mut grid = Grid::new(8, 4, 7, 0);
+        let before = grid.clone();
+        for (x, y) in [(8, 0), (0, 4), (usize::MAX, 0), (0, usize::MAX)] {
+            grid.write_cell(x, y, Cell::new('x', 7, 0, CellFlags::empty()));
+        }
+        assert!(grid == before);
+    }
+
+    #[test]
+    fn private_and_intermediate_csi_forms_write_nothing() {
+        let mut emulator = terminal(20, 2);
+        emulator.feed_bytes(b"A\x1b[>0cB\x1b[=1hC\x1b[2 qD\x1b[>4;1mE");
+        assert_eq!(screen_text(&emulator)[0], "ABCDE");
+        assert_eq!(emulator.state().cursor_get_position(), (5, 0));
+        // > 4 ; 1 m is modifyOtherKeys, not SGR 1
+        assert!(!emulator.grid().get_cell(4, 0).unwrap().flags.contains(CellFlags::BOLD));
+
+        // > 1 u pushes a keyboard mode; it must not restore the saved cursor
+        let mut emulator = terminal(20, 2);
+        emulator.feed_bytes(b"12\x1b[s345\x1b[>1uF");
+        assert_eq!(screen_text(&emulator)[0], "12345F");
+    }
+
+    #[test]
+    fn fuzz_safe_survives_pathological_sequences() {
+        let mut terminal = terminal(10, 4);
+        terminal.set_fuzz_safe(true);
+        for input in [
+            "\x1b[4294967295;3@",
+            "\x1b[0;0r\n\n\n",
+            "\x1b[99999999999B\x1b[2147483647C",
+            "\x1b[-5P\x1b[65535X",
+            "x\x1b[2147483647b",
+            "\x1b[5;2r\x1bM\x1bM",
+            "\u{2603} snow\u{ad}\u{97}\t\x08\r\n",
+            "\x1b[?1049h\x1b[200;200H\x1b[2J\x1b[?1049l",
+            "done\r\n",
+        ] {
+            terminal.feed_bytes(input.as_bytes());
+        }
+
+        let (x, y) = terminal.state().cursor_get_position();
+        assert!((0..10).contains(&x) && (0..4).contains(&y), "cursor off screen at {},{}", x, y);
+        assert_eq!((terminal.grid().width(), terminal.grid().height()), (10, 4));
+        assert!(screen_text(&terminal).iter().any(|line| line == "done"));
+    }
+
+    #[test]
+    fn one_megabyte_event_is_fed_in_pieces() {
+        let mut terminal = terminal(80, 24);
+        let line = format!("{}\r\n", "x".repeat(78));
+        let mut data = line.repeat(1024 * 1024 / line.len());
+        data.push_str("end");
+
+        let started = std::time::Instant::now();
+        terminal.feed_bytes(data.as_bytes());
+        assert!(started.elapsed() < std::time::Duration::from_secs(30), "took {:?}", started.elapsed());
+
+        let screen = screen_text(&terminal);
+        assert_eq!(screen[23], "end");
+        assert_eq!(screen[22], "x".repeat(78));
+    }
+
+    #[test]
+    fn oversized_osc_payload_is_skipped_not_drawn() {
+        let mut terminal = terminal(40, 3);
+        terminal.collect_unhandled(true);
+        let image = format!("\x1b]1337;File=inline=1:{}\x07", "QUFB".repeat(256 * 1024));
+        let data = format!("before{}after", image);
+        // Split mid-payload, the way the recorder cuts output into events
+        let (first, second) = data.as_bytes().split_at(data.len() / 2);
+        terminal.feed_bytes(first);
+        terminal.feed_bytes(second);
+
+        assert_eq!(screen_text(&terminal)[0], "beforeafter");
+        assert!(terminal.unhandled().keys().any(|sequence| sequence.starts_with("OSC longer than")));
+    }
+
+    #[test]
+    fn oversized_dcs_ends_at_string_terminator_split_across_events() {
+        let mut terminal = terminal(40, 3);
+        let data = format!("a\x1bPq{}\x1b", "#0;2;0;0;0".repeat(10_000));
+        terminal.feed_bytes(data.as_bytes());
+        terminal.feed_bytes(b"\\b");
+        assert_eq!(screen_text(&terminal)[0], "ab");
+    }
+}
@@ -9,9 +9,12 @@ lazy_static! {
         // ANSI_G0       = '[\033]\\(([B0UK])'
         // ANSI_G1       = '[\033]\\)([B0UK])'
         // ANSI_CSI_RE   = '[\033]\\[((?:\\d|;|<|>|=|\?)*)([a-zA-Z])\002?'
+        //   (extended with intermediate bytes 0x20-0x2F so DECSTR, CSI ! p, and
+        //   DECSCUSR, CSI Ps SP q, are matched, and the '@' final for ICH)
         // ANSI_OSC      = '(?:\033\\]|\x9d).*?(?:\033\\\\|[\a\x9c])'
         // BRACKET_PASTE = '[\033]\\[(20[0-1]~)'
         // ANSI_TITLE    = '[\033][k](.*)[\033][\\\\]'
+        // ANSI_DCS      = ESC P (or 0x90) ... ST; the body may span lines (sixel data)
 
         let pattern = concat!(
             r"([\x1b]([cDEHMZ6789>=i]))",
@@ -22,13 +25,15 @@ lazy_static! {
             "|",
             r"([\x1b]\)([B0UK]))",
             "|",
-            r"([\x1b]\[((?:\d|;|<|>|=|\?)*)([a-zA-Z`~])\x02?)",
+            r"([\x1b]\[((?:\d|;|<|>|=|\?|[ -/])*)([a-zA-Z@`~])\x02?)",
             "|",
             r"((?:[\x1b]\]|\x9d).*?(?:[\x1b]\\|[\x07\x9c]))",
             "|",
             r"([\x1b]\[(20[0-1])~)",
             "|",
-            r"([\x1b][k](.*?)[\x1b]\\)"
+            r"([\x1b][k](.*?)[\x1b]\\)",
+            "|",
+            r"((?:[\x1b]P|\x90)(?s:.*?)(?:[\x1b]\\|\x9c))"
         );
         Regex::new(pattern).unwrap()
     };
@@ -44,6 +49,7 @@ pub enum EscapeType {
     Osc,
     BracketPaste,
     Title,
+    Dcs,
 }
 
 #[derive(Debug, Clone)]
@@ -155,11 +161,17 @@ fn parse_escape_sequence(cap: &regex::Captures) -> Option<Event> {
         }));
     }
 
-    // OSC (group 12)
-    if cap.get(12).is_some() {
+    // OSC (group 12) - command carries the body between the introducer and terminator
+    if let Some(osc) = cap.get(12) {
+        let body = osc.as_str();
+        let body = body.strip_prefix("\x1b]").or_else(|| body.strip_prefix('\u{9d}')).unwrap_or(body);
+        let body = body.strip_suffix("\x1b\\")
+            .or_else(|| body.strip_suffix('\x07'))
+            .or_else(|| body.strip_suffix('\u{9c}'))
+            .unwrap_or(body);
         return Some(Event::Command(Command {
             esc_type: EscapeType::Osc,
-            command: String::new(),
+            command: body.to_string(),
             params: vec![],
         }));
     }
@@ -185,10 +197,42 @@ fn parse_escape_sequence(cap: &regex::Captures) -> Option<Event> {
         }));
     }
 
+    // DCS (group 17) - command carries the body between the introducer and terminator
+    if let Some(dcs) = cap.get(17) {
+        let body = dcs.as_str();
+        let body = body.strip_prefix("\x1bP").or_else(|| body.strip_prefix('\u{90}')).unwrap_or(body);
+        let body = body.strip_suffix("\x1b\\").or_else(|| body.strip_suffix('\u{9c}')).unwrap_or(body);
+        return Some(Event::Command(Command {
+            esc_type: EscapeType::Dcs,
+            command: body.to_string(),
+            params: vec![],
+        }));
+    }
+
     None
 }
 
 fn parse_csi_params(param_str: &str, command: &str) -> (String, Vec<i32>) {
+    // A leading private marker (? > = <) and trailing intermediate bytes (0x20-0x2F)
+    // are part of the command, so CSI > 0 c or CSI > 1 u never reach the plain c / u handlers
+    let marker = param_str.chars().next().filter(|c| matches!(c, '<' | '=' | '>' | '?'));
+    let body = &param_str[marker.map_or(0, char::len_utf8)..];
+    let (body, intermediates) = body.split_at(body.trim_end_matches(|c| matches!(c, ' '..='/')).len());
+
+    // Markers or intermediates anywhere else make the sequence malformed; keep it whole
+    // so it is reported as unhandled rather than run with half its parameters
+    if body.chars().any(|c| !c.is_ascii_digit() && c != ';') {
+        return (format!("{}{}", param_str, command), vec![]);
+    }
+
+    if marker.is_some() || !intermediates.is_empty() {
+        let cmd_str = format!("{}{}{}", marker.map(String::from).unwrap_or_default(), intermediates, command);
+        let params: Vec<i32> = body.split(';')
+            .filter_map(|s| s.parse().ok())
+            .collect();
+        return (cmd_str, params);
+    }
+
     // Handle H and f specially (cursor position)
     if command == "H" || command == "f" {
         let parts: Vec<&str> = param_str.split(';').collect();
@@ -202,16 +246,6 @@ fn parse_csi_params(param_str: &str, command: &str) -> (String, Vec<i32>) {
         return (command.to_string(), params);
     }
 
-    // Handle DEC Private Mode (DECSET/DECRST) sequences
-    if !param_str.is_empty() && param_str.starts_with('?') {
-        let cmd_str = format!("?{}", command);
-        let param_tokens: Vec<&str> = param_str[1..].split(';').collect();
-        let params: Vec<i32> = param_tokens.iter()
-            .filter_map(|s| s.parse().ok())
-            .collect();
-        return (cmd_str, params);
-    }
-
     // Normal CSI parameters
     let params: Vec<i32> = param_str.split(';')
         .filter(|s| !s.is_empty())
@@ -61,6 +61,55 @@ impl TerminalState {
         }
     }
 
+    /// Return to power-on defaults (used by RIS and DECSTR).
+    /// Dimensions and emulation mode are preserved.
+    pub fn reset(&mut self) {
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.reverse_video = false;
+        self.bold = false;
+        self.text_mode = false;
+        self.autowrap = true;
+        self.foreground = self.default_foreground;
+        self.background = self.default_background;
+        self.pending_wrap = false;
+        self.display_cursor = true;
+        self.scroll = 0;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.height - 1;
+        self.saved_cursor_x = 0;
+        self.saved_cursor_y = 0;
+        self.flags = CellFlags::empty();
+    }
+
+    /// Change dimensions, resetting the scroll region and keeping the cursor on screen
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width as i32;
+        self.height = height as i32;
+        self.scroll = 0;
+        self.scroll_top = 0;
+        self.scroll_bottom = self.height - 1;
+        self.pending_wrap = false;
+        self.cursor_x = self.cursor_x.min(self.width - 1);
+        self.cursor_y = self.cursor_y.min(self.height - 1);
+        self.saved_cursor_x = self.saved_cursor_x.min(self.width - 1);
+        self.saved_cursor_y = self.saved_cursor_y.min(self.height - 1);
+    }
+
+    /// Bring the cursor, scroll region and pending scroll back into range after
+    /// a sequence was abandoned part way through
+    pub fn recover(&mut self) {
+        let last_column = (self.width - 1).max(0);
+        let last_row = (self.height - 1).max(0);
+        self.cursor_x = self.cursor_x.clamp(0, last_column);
+        self.cursor_y = self.cursor_y.clamp(0, last_row);
+        self.saved_cursor_x = self.saved_cursor_x.clamp(0, last_column);
+        self.saved_cursor_y = self.saved_cursor_y.clamp(0, last_row);
+        self.pending_wrap = false;
+        self.text_mode = false;
+        self.set_scroll_region(self.scroll_top, self.scroll_bottom);
+    }
+
     pub fn text_mode_on(&mut self) {
         self.text_mode = true;
     }
@@ -79,8 +128,17 @@ impl TerminalState {
 
     pub fn set_scroll_region(&mut self, top: i32, bottom: i32) {
         self.scroll = 0;
-        self.scroll_top = top;
-        self.scroll_bottom = bottom;
+        // Margins outside the screen or out of order fall back to the full screen
+        let last = (self.height - 1).max(0);
+        let top = top.clamp(0, last);
+        let bottom = bottom.clamp(0, last);
+        if top < bottom {
+            self.scroll_top = top;
+            self.scroll_bottom = bottom;
+        } else {
+            self.scroll_top = 0;
+            self.scroll_bottom = last;
+        }
     }
 
     pub fn show_cursor(&mut self) {
@@ -109,14 +167,14 @@ impl TerminalState {
 
         if self.cursor_y < self.scroll_top {
             if self.text_mode {
-                self.scroll -= self.scroll_top - self.cursor_y; // negative
+                self.scroll = self.scroll.saturating_sub(self.scroll_top.saturating_sub(self.cursor_y)); // negative
             }
             self.cursor_y = self.scroll_top;
         }
 
         if self.cursor_y > self.scroll_bottom {
             if self.text_mode {
-                self.scroll += self.cursor_y - self.scroll_bottom; // positive
+                self.scroll = self.scroll.saturating_add(self.cursor_y.saturating_sub(self.scroll_bottom)); // positive
             }
             self.cursor_y = self.scroll_bottom;
         }
@@ -124,19 +182,19 @@ impl TerminalState {
 
     // Exact translation of Python cursor_up lines 79-81
     pub fn cursor_up(&mut self, distance: i32) {
-        self.cursor_y -= distance;
+        self.cursor_y = self.cursor_y.saturating_sub(distance);
         self.check_bounds();
     }
 
     // Exact translation of Python cursor_down lines 83-85
     pub fn cursor_down(&mut self, distance: i32) {
-        self.cursor_y += distance;
+        self.cursor_y = self.cursor_y.saturating_add(distance);
         self.check_bounds();
     }
 
     // Exact translation of Python cursor_left lines 87-89
     pub fn cursor_left(&mut self, distance: i32) {
-        self.cursor_x -= distance;
+        self.cursor_x = self.cursor_x.saturating_sub(distance);
         self.check_bounds();
     }
 
@@ -146,12 +204,13 @@ impl TerminalState {
         if !self.pending_wrap && self.autowrap && self.cursor_x == self.width - 1 {
             self.pending_wrap = true;
         } else {
-            self.cursor_x += distance;
-            if self.autowrap {
-                while self.cursor_x >= self.width {
-                    self.cursor_x = self.cursor_x - self.width;
-                    self.cursor_down(1);
-                }
+            self.cursor_x = self.cursor_x.saturating_add(distance);
+            if self.autowrap && self.cursor_x >= self.width {
+                // Wrap onto as many following lines as the move covers (saturating
+                // arithmetic keeps a huge count from overflowing or looping for ages)
+                let lines = self.cursor_x / self.width.max(1);
+                self.cursor_x %= self.width.max(1);
+                self.cursor_down(lines);
             }
             self.check_bounds();
         }
@@ -2,7 +2,7 @@ use anyhow::{Result, bail, Context};
 use image::{Rgba, RgbaImage};
 use std::path::Path;
 use std::io::Cursor;
-use crate::theme::{Layer, NineSliceConfig};
+use crate::theme::{BlendMode, Layer, LayerTiming, NineSliceConfig};
 use crate::renderer::Canvas;
 use rust_embed::RustEmbed;
 
@@ -29,6 +29,21 @@ impl LayerImage {
         }
     }
 
+    /// A copy with every frame resized by `factor` (nearest neighbor, so pixel art stays crisp)
+    pub fn scaled(&self, factor: f64) -> Self {
+        let width = ((self.width as f64 * factor).round() as u32).max(1);
+        let height = ((self.height as f64 * factor).round() as u32).max(1);
+        Self {
+            frames: self.frames.iter()
+                .map(|frame| image::imageops::resize(frame, width, height, image::imageops::FilterType::Nearest))
+                .collect(),
+            delays: self.delays.clone(),
+            width,
+            height,
+            is_animated: self.is_animated,
+        }
+    }
+
     /// Get total number of frames
     pub fn frame_count(&self) -> usize {
         self.frames.len()
@@ -99,6 +114,35 @@ impl LayerImage {
         }
     }
 
+    /// Load an image embedded in the theme as base64, with or without a `data:` URI prefix
+    pub fn load_from_base64(data: &str, name: &str) -> Result<Self> {
+        use base64::Engine;
+
+        let (mime, encoded) = match data.trim().strip_prefix("data:") {
+            Some(uri) => {
+                let (header, payload) = uri.split_once(',')
+                    .with_context(|| format!("Malformed data URI for layer image: {}", name))?;
+                if !header.ends_with(";base64") {
+                    anyhow::bail!("Layer image data URI must be base64 encoded: {}", name);
+                }
+                (Some(header.trim_end_matches(";base64")), payload)
+            }
+            None => (None, data.trim()),
+        };
+
+        // YAML block scalars wrap long strings, so drop any whitespace before decoding
+        let encoded: String = encoded.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        let bytes = base64::engine::general_purpose::STANDARD.decode(encoded)
+            .with_context(|| format!("Invalid base64 in layer image: {}", name))?;
+
+        let is_gif = mime == Some("image/gif") || bytes.starts_with(b"GIF8");
+        if is_gif && !name.ends_with(".gif") {
+            Self::load_from_bytes(&bytes, &format!("{}.gif", name))
+        } else {
+            Self::load_from_bytes(&bytes, name)
+        }
+    }
+
     fn load_animated_gif(path: &Path) -> Result<Self> {
         let file = std::fs::File::open(path)
             .with_context(|| format!("Failed to open GIF: {}", path.display()))?;
@@ -469,8 +513,11 @@ impl LayerImage {
         Ok(())
     }
 
-    /// Composite a specific frame of this layer onto a canvas
-    pub fn composite_onto(&self, canvas: &mut Canvas, offset_x: i32, offset_y: i32, palette: &[u8], frame_index: usize) {
+    /// Composite a specific frame of this layer onto a canvas, drawing only
+    /// pixels whose alpha is above the layer's `alpha_threshold`, combined
+    /// with the canvas pixel beneath by its `blend` mode
+    pub fn composite_onto(&self, canvas: &mut Canvas, offset_x: i32, offset_y: i32, palette: &[u8], frame_index: usize, layer: &Layer) {
+        let (alpha_threshold, blend) = (layer.alpha_threshold, layer.blend);
         let image = self.get_frame(frame_index);
         let canvas_width = canvas.width();
         let canvas_height = canvas.height();
@@ -485,8 +532,19 @@ impl LayerImage {
                     let pixel = image.get_pixel(x, y);
 
                     // Convert RGBA to palette index (find nearest color)
-                    if pixel[3] > 128 { // Check alpha threshold
-                        let color_idx = self.find_nearest_palette_color(pixel, palette);
+                    if pixel[3] > alpha_threshold {
+                        let base = match blend {
+                            BlendMode::Normal => None,
+                            _ => canvas.get_pixel(dst_x as usize, dst_y as usize)
+                                .and_then(|index| palette.get(index as usize * 3..index as usize * 3 + 3)),
+                        };
+                        let color_idx = match base {
+                            Some(&[r, g, b]) => {
+                                let [r, g, b] = blend.blend([r, g, b], [pixel[0], pixel[1], pixel[2]]);
+                                self.find_nearest_palette_color(&Rgba([r, g, b, 255]), palette)
+                            }
+                            _ => self.find_nearest_palette_color(pixel, palette),
+                        };
                         canvas.set_pixel(dst_x as usize, dst_y as usize, color_idx);
                     }
                 }
@@ -526,6 +584,15 @@ impl LayerImage {
     }
 }
 
+/// Time into the recording, on both clocks an animated layer can follow
+#[derive(Debug, Clone, Copy)]
+pub struct LayerClock {
+    /// Elapsed output (playback) time, after --speed and speed curves
+    pub output_ms: f64,
+    /// The matching point in the original recording
+    pub recording_ms: f64,
+}
+
 pub struct LayerRenderer {
     layers: Vec<(LayerImage, Layer)>,
 }
@@ -542,7 +609,7 @@ impl LayerRenderer {
     }
 
     /// Calculate which frame to display for an animated layer based on elapsed time
-    fn calculate_frame_index(&self, image: &LayerImage, layer: &Layer, current_time_ms: f64) -> usize {
+    fn calculate_frame_index(&self, image: &LayerImage, layer: &Layer, clock: LayerClock) -> usize {
         if !image.is_animated || image.frame_count() == 1 {
             return 0;
         }
@@ -551,6 +618,10 @@ impl LayerRenderer {
         let speed = anim_config.map(|a| a.speed).unwrap_or(1.0);
         let should_loop = anim_config.map(|a| a.r#loop).unwrap_or(true);
         let start_frame = anim_config.map(|a| a.start_frame).unwrap_or(0);
+        let current_time_ms = match anim_config.map(|a| a.timing).unwrap_or_default() {
+            LayerTiming::Output => clock.output_ms,
+            LayerTiming::Recording => clock.recording_ms,
+        };
 
         // Calculate total animation duration in milliseconds
         let total_duration_ms: f64 = image.delays.iter()
@@ -588,22 +659,16 @@ impl LayerRenderer {
         }
     }
 
-    pub fn render_underlays(&self, canvas: &mut Canvas, palette: &[u8], current_time_ms: f64) {
-        for (image, layer) in &self.layers {
-            if layer.depth < 0 {
-                let frame_index = self.calculate_frame_index(image, layer, current_time_ms);
-                self.render_layer(image, layer, canvas, palette, frame_index);
-            }
-        }
+    /// Depth of each image layer, in the order they were added
+    pub fn depths(&self) -> impl Iterator<Item = i32> + '_ {
+        self.layers.iter().map(|(_, layer)| layer.depth)
     }
 
-    pub fn render_overlays(&self, canvas: &mut Canvas, palette: &[u8], current_time_ms: f64) {
-        for (image, layer) in &self.layers {
-            if layer.depth >= 0 {
-                let frame_index = self.calculate_frame_index(image, layer, current_time_ms);
-                self.render_layer(image, layer, canvas, palette, frame_index);
-            }
-        }
+    /// Draw the image layer at `index` (in the order added) at its frame for `clock`
+    pub fn render_layer_at(&self, index: usize, canvas: &mut Canvas, palette: &[u8], clock: LayerClock) {
+        let (image, layer) = &self.layers[index];
+        let frame_index = self.calculate_frame_index(image, layer, clock);
+        self.render_layer(image, layer, canvas, palette, frame_index);
     }
 
     fn render_layer(&self, image: &LayerImage, layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
@@ -626,10 +691,10 @@ impl LayerRenderer {
     fn render_copy(&self, image: &LayerImage, layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
         // Copy mode: copy source bounds to dst bounds without scaling
         let (dst_x, dst_y, _dst_w, _dst_h) = self.calculate_dst_rect(layer, canvas, image);
-        image.composite_onto(canvas, dst_x, dst_y, palette, frame_index);
+        image.composite_onto(canvas, dst_x, dst_y, palette, frame_index, layer);
     }
 
-    fn render_center(&self, image: &LayerImage, _layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
+    fn render_center(&self, image: &LayerImage, layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
         // Center mode: center the image on the canvas
         let canvas_width = canvas.width() as i32;
         let canvas_height = canvas.height() as i32;
@@ -639,7 +704,7 @@ impl LayerRenderer {
         let offset_x = (canvas_width - image_width) / 2;
         let offset_y = (canvas_height - image_height) / 2;
 
-        image.composite_onto(canvas, offset_x, offset_y, palette, frame_index);
+        image.composite_onto(canvas, offset_x, offset_y, palette, frame_index, layer);
     }
 
     fn render_9slice(&self, image: &LayerImage, layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
@@ -658,7 +723,7 @@ impl LayerRenderer {
                         is_animated: false,
                     };
                     // Composite at the calculated destination position
-                    scaled_layer_image.composite_onto(canvas, dst_x, dst_y, palette, 0);
+                    scaled_layer_image.composite_onto(canvas, dst_x, dst_y, palette, 0, layer);
                 }
                 Err(e) => {
                     eprintln!("Warning: Failed to 9-slice scale layer: {}", e);
@@ -707,7 +772,7 @@ impl LayerRenderer {
         };
 
         // Composite the scaled image onto the canvas
-        scaled_layer.composite_onto(canvas, dst_x, dst_y, palette, 0);
+        scaled_layer.composite_onto(canvas, dst_x, dst_y, palette, 0, layer);
     }
 
     fn render_tile(&self, image: &LayerImage, layer: &Layer, canvas: &mut Canvas, palette: &[u8], frame_index: usize) {
@@ -719,7 +784,7 @@ impl LayerRenderer {
         while y < canvas_height {
             let mut x = 0;
             while x < canvas_width {
-                image.composite_onto(canvas, x, y, palette, frame_index);
+                image.composite_onto(canvas, x, y, palette, frame_index, layer);
                 x += image.width as i32;
             }
             y += image.height as i32;
@@ -809,3 +874,101 @@ impl LayerRenderer {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::Palette;
+
+    /// Base64 PNG of a single row of RGBA pixels
+    fn png_base64(pixels: &[[u8; 4]]) -> String {
+        use base64::Engine;
+        let image = RgbaImage::from_fn(pixels.len() as u32, 1, |x, _| Rgba(pixels[x as usize]));
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        base64::engine::general_purpose::STANDARD.encode(png)
+    }
+
+    fn layer(yaml: &str) -> Layer {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    /// Render one layer onto a 4x1 canvas of `background` and return its pixels
+    fn render(image: LayerImage, layer: Layer, background: u8) -> Vec<u8> {
+        render_at(image, layer, background, LayerClock { output_ms: 0.0, recording_ms: 0.0 })
+    }
+
+    fn render_at(image: LayerImage, layer: Layer, background: u8, clock: LayerClock) -> Vec<u8> {
+        let palette = Palette::default();
+        let mut canvas = Canvas::new(4, 1, &palette);
+        canvas.fill(background);
+        let mut renderer = LayerRenderer::new();
+        renderer.add_layer(image, layer);
+        renderer.render_layer_at(0, &mut canvas, palette.colors(), clock);
+        canvas.data().to_vec()
+    }
+
+    #[test]
+    fn embedded_base64_png_renders() {
+        let data = format!("data:image/png;base64,{}", png_base64(&[[255, 0, 0, 255], [0, 0, 255, 255]]));
+        let image = LayerImage::load_from_base64(&data, "inline").unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+
+        let palette = Palette::default();
+        let (red, blue) = (palette.match_color_index(255, 0, 0), palette.match_color_index(0, 0, 255));
+        assert_eq!(render(image, layer("depth: 1\nmode: copy\n"), 0), [red, blue, 0, 0]);
+    }
+
+    #[test]
+    fn output_timed_layers_ignore_the_recording_speed() {
+        // Red for 100ms, then blue for 100ms
+        let frame = |rgba| RgbaImage::from_pixel(1, 1, Rgba(rgba));
+        let image = || LayerImage {
+            frames: vec![frame([255, 0, 0, 255]), frame([0, 0, 255, 255])],
+            delays: vec![10, 10],
+            width: 1,
+            height: 1,
+            is_animated: true,
+        };
+        let palette = Palette::default();
+        let (red, blue) = (palette.match_color_index(255, 0, 0), palette.match_color_index(0, 0, 255));
+
+        // 50ms into the output, which is 50ms into the recording at --speed 1 and 150ms at --speed 3
+        let pixel_at = |timing: &str, speed: f64| {
+            let layer = layer(&format!("depth: 1\nmode: tile\nanimation:\n  timing: {}\n", timing));
+            render_at(image(), layer, 0, LayerClock { output_ms: 50.0, recording_ms: 50.0 * speed })[0]
+        };
+        assert_eq!((pixel_at("output", 1.0), pixel_at("output", 3.0)), (red, red));
+        assert_eq!((pixel_at("recording", 1.0), pixel_at("recording", 3.0)), (red, blue));
+    }
+
+    #[test]
+    fn alpha_threshold_picks_which_soft_pixels_draw() {
+        let data = format!("data:image/png;base64,{}", png_base64(&[[255, 0, 0, 100]]));
+        let red = Palette::default().match_color_index(255, 0, 0);
+        let first_pixel = |yaml: &str| {
+            let image = LayerImage::load_from_base64(&data, "soft").unwrap();
+            render(image, layer(yaml), 0)[0]
+        };
+
+        assert_eq!(first_pixel("depth: 1\nmode: copy\nalpha_threshold: 50\n"), red);
+        assert_eq!(first_pixel("depth: 1\nmode: copy\nalpha_threshold: 200\n"), 0);
+        // The default of 128 leaves it out
+        assert_eq!(first_pixel("depth: 1\nmode: copy\n"), 0);
+    }
+
+    #[test]
+    fn multiply_darkens_the_canvas_by_a_gray_layer() {
+        let data = format!("data:image/png;base64,{}", png_base64(&[[128, 128, 128, 255]]));
+        let palette = Palette::default();
+        let red = palette.match_color_index(255, 0, 0);
+        let first_pixel = |yaml: &str| {
+            let image = LayerImage::load_from_base64(&data, "gray").unwrap();
+            render(image, layer(yaml), red)[0]
+        };
+
+        // Half-gray halves each channel of the red beneath: dark red, not gray
+        assert_eq!(first_pixel("depth: 1\nmode: copy\nblend: multiply\n"), palette.match_color_index(128, 0, 0));
+        assert_eq!(first_pixel("depth: 1\nmode: copy\n"), palette.match_color_index(128, 128, 128));
+    }
+}
@@ -24,6 +24,11 @@ pub struct Theme {
     #[serde(default)]
     pub transparent: u8,
 
+    /// Leave terminal pixels in the default background color undrawn, so underlay
+    /// layers show through the terminal area
+    #[serde(default)]
+    pub transparent_background: bool,
+
     #[serde(default)]
     pub title: Option<TitleConfig>,
 
@@ -33,9 +38,24 @@ pub struct Theme {
     #[serde(default)]
     pub layers: Vec<Layer>,
 
+    #[serde(default)]
+    pub text_layers: Vec<TextLayer>,
+
     #[serde(default)]
     pub palette: Option<Palette>,
 
+    /// Bitmap font used when no font is given on the command line
+    #[serde(default)]
+    pub font: Option<String>,
+
+    /// System (TrueType/OpenType) font used when no font is given on the command line
+    #[serde(default)]
+    pub system_font: Option<String>,
+
+    /// TrueType font size used when --font-size is not given
+    #[serde(default)]
+    pub font_size: Option<usize>,
+
     /// Internal flag: true if theme was loaded from embedded sources
     #[serde(skip)]
     pub is_embedded: bool,
@@ -54,12 +74,38 @@ pub struct TitleConfig {
 
     #[serde(default = "default_font_size")]
     pub font_size: f32,
+
+    /// Overlay layers with a depth below this draw under the title, the rest over it
+    #[serde(default)]
+    pub depth: i32,
 }
 
 fn default_font_size() -> f32 {
     1.0
 }
 
+/// Fixed text baked into every frame, e.g. a caption or URL footer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextLayer {
+    pub text: String,
+    pub x: i32,
+    pub y: i32,
+
+    #[serde(default = "default_foreground", alias = "fg")]
+    pub foreground: u8,
+
+    #[serde(default, alias = "bg")]
+    pub background: u8,
+
+    #[serde(default = "default_font_size", alias = "size")]
+    pub font_size: f32,
+
+    /// Drawn in depth order with the image layers (after them at equal depths);
+    /// negative depths are under the terminal
+    #[serde(default)]
+    pub depth: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Padding {
     #[serde(default)]
@@ -81,8 +127,13 @@ pub struct Layer {
     pub depth: i32,
 
     /// Path to the image file
+    #[serde(default)]
     pub file: String,
 
+    /// Inline image data (base64, optionally as a `data:image/...;base64,` URI), used instead of `file`
+    #[serde(default)]
+    pub data: Option<String>,
+
     /// Rendering mode
     pub mode: LayerMode,
 
@@ -98,6 +149,14 @@ pub struct Layer {
     #[serde(default = "default_copy_mode")]
     pub copy_mode: CopyMode,
 
+    /// Pixels with alpha above this (0-255) are drawn; the rest are left out
+    #[serde(default = "default_alpha_threshold")]
+    pub alpha_threshold: u8,
+
+    /// How the layer's colors combine with the pixels already on the canvas
+    #[serde(default)]
+    pub blend: BlendMode,
+
     /// Animation settings
     #[serde(default)]
     pub animation: Option<AnimationConfig>,
@@ -116,6 +175,57 @@ pub struct AnimationConfig {
     /// Frame to start on (0-indexed, default: 0)
     #[serde(default)]
     pub start_frame: usize,
+
+    /// Clock the animation follows (default: output)
+    #[serde(default)]
+    pub timing: LayerTiming,
+}
+
+/// Which clock drives an animated layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerTiming {
+    /// Output playback time: the animation runs at its own rate whatever --speed is
+    #[default]
+    Output,
+    /// Recording time: the animation speeds up and slows down with --speed and speed curves
+    Recording,
+}
+
+/// How a layer pixel combines with the canvas pixel beneath it; the result is
+/// matched back to the nearest palette color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// The layer pixel replaces the canvas pixel
+    #[default]
+    Normal,
+    /// Darkens: white leaves the canvas unchanged, black gives black
+    Multiply,
+    /// Lightens: black leaves the canvas unchanged, white gives white
+    Screen,
+    /// Multiply over dark canvas pixels and screen over light ones, raising contrast
+    Overlay,
+}
+
+impl BlendMode {
+    /// Blend a layer color onto a canvas color, per RGB channel
+    pub fn blend(self, base: [u8; 3], layer: [u8; 3]) -> [u8; 3] {
+        let multiply = |a: u32, b: u32| a * b / 255;
+        let screen = |a: u32, b: u32| 255 - (255 - a) * (255 - b) / 255;
+        let mut out = [0; 3];
+        for channel in 0..3 {
+            let (a, b) = (base[channel] as u32, layer[channel] as u32);
+            out[channel] = match self {
+                BlendMode::Normal => b,
+                BlendMode::Multiply => multiply(a, b),
+                BlendMode::Screen => screen(a, b),
+                BlendMode::Overlay if a < 128 => multiply(2 * a, b),
+                BlendMode::Overlay => screen(2 * a - 255, b),
+            } as u8;
+        }
+        out
+    }
 }
 
 fn default_animation_speed() -> f64 {
@@ -130,6 +240,10 @@ fn default_copy_mode() -> CopyMode {
     CopyMode::Copy
 }
 
+fn default_alpha_threshold() -> u8 {
+    128
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LayerMode {
@@ -338,6 +452,7 @@ impl Theme {
             .with_context(|| format!("Failed to parse theme YAML: {}", path.display()))?;
 
         theme.is_embedded = false; // Loaded from filesystem
+        theme.clamp_color_indices();
         Ok(theme)
     }
 
@@ -404,9 +519,129 @@ impl Theme {
             .with_context(|| format!("Failed to parse embedded theme: {}", name))?;
 
         theme.is_embedded = true; // Loaded from embedded source
+        theme.clamp_color_indices();
         Ok(theme)
     }
 
+    /// Pull color indices that point past a reduced palette (e.g. 2-bit/4-bit themes)
+    /// back to its last entry, warning for each one
+    fn clamp_color_indices(&mut self) {
+        let Some(color_count) = self.palette.as_ref().map(|palette| palette.rgb.len()) else {
+            return;
+        };
+        let last = (color_count.clamp(1, 256) - 1) as u8;
+
+        let name = &self.name;
+        for (field, index) in [
+            ("default_foreground", &mut self.default_foreground),
+            ("default_background", &mut self.default_background),
+            ("foreground", &mut self.foreground),
+            ("background", &mut self.background),
+            ("transparent", &mut self.transparent),
+        ] {
+            if *index > last {
+                eprintln!(
+                    "Warning: theme '{}' {} {} is outside its {}-color palette; using {}",
+                    name, field, index, color_count, last
+                );
+                *index = last;
+            }
+        }
+    }
+
+    /// Apply a `--set key=value` override, where the key is a dotted path into the
+    /// theme (e.g. `padding.left=20`, `title.text=Demo`, `layers.0.depth=-1`)
+    pub fn apply_override(&mut self, assignment: &str) -> Result<()> {
+        let (path, value) = assignment
+            .split_once('=')
+            .with_context(|| format!("Invalid theme override '{}': expected key=value", assignment))?;
+        let value: serde_yaml::Value = serde_yaml::from_str(value)
+            .with_context(|| format!("Invalid value in theme override '{}'", assignment))?;
+
+        let mut root = serde_yaml::to_value(&*self)?;
+        let mut node = &mut root;
+        for (depth, key) in path.trim().split('.').enumerate() {
+            let current = node;
+            // Unset optional sections (e.g. padding: null) start out empty
+            if current.is_null() && depth > 0 {
+                *current = serde_yaml::Value::Mapping(Default::default());
+            }
+            node = match current {
+                serde_yaml::Value::Mapping(map) => {
+                    if depth == 0 && !map.contains_key(key) {
+                        anyhow::bail!("Unknown theme field '{}'", key);
+                    }
+                    map.entry(serde_yaml::Value::String(key.to_string()))
+                        .or_insert(serde_yaml::Value::Null)
+                }
+                serde_yaml::Value::Sequence(items) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| items.get_mut(index))
+                    .with_context(|| format!("No list entry '{}' in theme override '{}'", key, path))?,
+                _ => anyhow::bail!("Theme field '{}' in '{}' has no sub-fields", key, path),
+            };
+        }
+        *node = value;
+
+        let is_embedded = self.is_embedded;
+        *self = serde_yaml::from_value(root)
+            .with_context(|| format!("Theme override '{}' does not fit the theme", assignment))?;
+        self.is_embedded = is_embedded;
+        self.clamp_color_indices();
+        Ok(())
+    }
+
+    /// Resize the theme's pixel geometry by `factor` (--theme-scale): padding, title
+    /// and text positions, and layer bounds and nine-slice edges. Layer images must
+    /// be scaled to match (`LayerImage::scaled`) for the nine-slice edges to line up.
+    pub fn scale(&mut self, factor: f64) {
+        let scale = |value: i32| (value as f64 * factor).round() as i32;
+        // Inclusive end coordinates map to the last pixel of the scaled span
+        let scale_end = |value: i32| ((value + 1) as f64 * factor).round() as i32 - 1;
+
+        if let Some(ref mut padding) = self.padding {
+            padding.left = scale(padding.left);
+            padding.top = scale(padding.top);
+            padding.right = scale(padding.right);
+            padding.bottom = scale(padding.bottom);
+        }
+        if let Some(ref mut title) = self.title {
+            title.x = scale(title.x);
+            title.y = scale(title.y);
+        }
+        for text in &mut self.text_layers {
+            text.x = scale(text.x);
+            text.y = scale(text.y);
+        }
+        for layer in &mut self.layers {
+            for bounds in [&mut layer.bounds, &mut layer.dst_bounds].into_iter().flatten() {
+                for value in [&mut bounds.left, &mut bounds.top, &mut bounds.right, &mut bounds.bottom] {
+                    if let BoundValue::Value(v) = value {
+                        *v = scale(*v);
+                    }
+                }
+            }
+            if let Some(ref mut nineslice) = layer.nineslice {
+                // The corner regions run from outer_left to inner_left and from inner_right to outer_right
+                for (value, is_end) in [
+                    (&mut nineslice.outer_left, false),
+                    (&mut nineslice.outer_top, false),
+                    (&mut nineslice.outer_right, true),
+                    (&mut nineslice.outer_bottom, true),
+                    (&mut nineslice.inner_left, true),
+                    (&mut nineslice.inner_top, true),
+                    (&mut nineslice.inner_right, false),
+                    (&mut nineslice.inner_bottom, false),
+                ] {
+                    if let NineSliceValue::Value(v) = value {
+                        *v = if is_end { scale_end(*v) } else { scale(*v) };
+                    }
+                }
+            }
+        }
+    }
+
     pub fn find_layer_file(&self, layer_file: &str, theme_dir: &Path) -> PathBuf {
         // Try relative to theme file first
         let relative_path = theme_dir.join(layer_file);
@@ -434,11 +669,80 @@ impl Default for Theme {
             default_background: 0,
             default_foreground: 7,
             transparent: 0,
+            transparent_background: false,
             title: None,
             padding: None,
             layers: Vec::new(),
+            text_layers: Vec::new(),
             palette: None,
+            font: None,
+            system_font: None,
+            font_size: None,
             is_embedded: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme_with_palette(colors: usize) -> Theme {
+        Theme {
+            palette: Some(Palette { colors, rgb: vec![[0, 0, 0]; colors] }),
+            ..Theme::default()
+        }
+    }
+
+    #[test]
+    fn full_palette_keeps_index_255() {
+        let mut theme = theme_with_palette(256);
+        theme.background = 255;
+        theme.default_foreground = 255;
+        theme.clamp_color_indices();
+        assert_eq!(theme.background, 255);
+        assert_eq!(theme.default_foreground, 255);
+    }
+
+    #[test]
+    fn reduced_palette_clamps_to_last_entry() {
+        let mut theme = theme_with_palette(16);
+        theme.background = 200;
+        theme.foreground = 15;
+        theme.clamp_color_indices();
+        assert_eq!(theme.background, 15);
+        assert_eq!(theme.foreground, 15);
+    }
+
+    #[test]
+    fn theme_scale_resizes_padding_and_layer_geometry() {
+        let mut theme = Theme::load_by_name("bar").unwrap();
+        theme.scale(2.0);
+
+        let padding = theme.padding.as_ref().unwrap();
+        assert_eq!((padding.left, padding.top, padding.right, padding.bottom), (30, 0, 30, 130));
+
+        let layer = &theme.layers[0];
+        let bounds = layer.dst_bounds.as_ref().unwrap();
+        assert!(matches!(bounds.top, BoundValue::Value(-100)));
+        assert!(matches!(bounds.right, BoundValue::Auto));
+
+        // Slice edges land on the same image pixels once the layer image is scaled too
+        let nineslice = layer.nineslice.as_ref().unwrap();
+        assert!(matches!(nineslice.inner_left, NineSliceValue::Value(501)));
+        assert!(matches!(nineslice.inner_right, NineSliceValue::Value(600)));
+        assert!(matches!(nineslice.inner_bottom, NineSliceValue::Value(98)));
+        assert!(matches!(nineslice.outer_right, NineSliceValue::Auto));
+
+        let image = layers::LayerImage {
+            frames: vec![image::RgbaImage::new(300, 50)],
+            delays: vec![0],
+            width: 300,
+            height: 50,
+            is_animated: false,
+        };
+        let scaled = image.scaled(2.0);
+        assert_eq!((scaled.width, scaled.height), (600, 100));
+        assert_eq!(scaled.frames[0].dimensions(), (600, 100));
+    }
+}